@@ -22,8 +22,8 @@ pub fn benchmark(c: &mut Criterion) {
 
     let set: FuzzyPhraseSet = {
         let mut builder = FuzzyPhraseSetBuilder::new(&dir.path()).unwrap();
-        for phrase in phrases.iter() {
-            builder.insert_str(phrase).unwrap();
+        for (i, phrase) in phrases.iter().enumerate() {
+            builder.insert_str(phrase, i as u64).unwrap();
         }
         builder.finish().unwrap();
 
@@ -39,14 +39,14 @@ pub fn benchmark(c: &mut Criterion) {
 
         for group in abbrevs {
             for from in group[1..].iter() {
-                replacements.push(WordReplacement { from: from.to_owned(), to: group[0].to_owned() });
+                replacements.push(WordReplacement::new(from, &group[0]));
             }
         }
 
         let mut builder = FuzzyPhraseSetBuilder::new(&resp_dir.path()).unwrap();
         builder.load_word_replacements(replacements);
-        for phrase in phrases.iter() {
-            builder.insert_str(phrase).unwrap();
+        for (i, phrase) in phrases.iter().enumerate() {
+            builder.insert_str(phrase, i as u64).unwrap();
         }
         builder.finish().unwrap();
 
@@ -50,11 +50,12 @@ pub fn build_phrase_graph(file_loc: &str) -> (BTreeMap<String, u32>, PhraseSet)
     }
 
     phrases.sort();
+    phrases.dedup();
 
     let mut build = PhraseSetBuilder::memory();
 
-    for phrase in phrases.iter() {
-        build.insert(&phrase).unwrap();
+    for (i, phrase) in phrases.iter().enumerate() {
+        build.insert(&phrase, i as u64).unwrap();
     }
 
     let bytes = build.into_inner().unwrap();
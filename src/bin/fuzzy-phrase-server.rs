@@ -0,0 +1,220 @@
+extern crate clap;
+extern crate fuzzy_phrase;
+extern crate serde;
+#[macro_use]
+extern crate serde_derive;
+#[macro_use]
+extern crate serde_json;
+extern crate tiny_http;
+
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::path::{Path, PathBuf};
+use std::process;
+
+use clap::{App, Arg};
+use fuzzy_phrase::glue::FuzzyPhraseSet;
+use serde::de::DeserializeOwned;
+use tiny_http::{Header, Method, Request, Response, Server};
+
+// a loaded index, and where to reload it from
+struct Index {
+    path: PathBuf,
+    set: FuzzyPhraseSet,
+}
+
+fn default_dist() -> u8 {
+    1
+}
+
+#[derive(Deserialize)]
+struct ContainsRequest {
+    index: Option<String>,
+    phrase: Vec<String>,
+    #[serde(default)]
+    prefix: bool,
+}
+
+#[derive(Deserialize)]
+struct MatchRequest {
+    index: Option<String>,
+    phrase: Vec<String>,
+    #[serde(default = "default_dist")]
+    max_word_dist: u8,
+    #[serde(default = "default_dist")]
+    max_phrase_dist: u8,
+    #[serde(default)]
+    ends_in_prefix: bool,
+}
+
+#[derive(Deserialize)]
+struct MultiPhrase {
+    phrase: Vec<String>,
+    #[serde(default)]
+    ends_in_prefix: bool,
+}
+
+#[derive(Deserialize)]
+struct MultiRequest {
+    index: Option<String>,
+    phrases: Vec<MultiPhrase>,
+    #[serde(default = "default_dist")]
+    max_word_dist: u8,
+    #[serde(default = "default_dist")]
+    max_phrase_dist: u8,
+}
+
+#[derive(Deserialize, Default)]
+struct ReloadRequest {
+    index: Option<String>,
+}
+
+// an error response: the HTTP status code and a message for the body
+struct HttpError(u16, String);
+
+fn main() {
+    let matches = App::new("fuzzy-phrase-server")
+        .about("Serve queries against fuzzy-phrase indexes over HTTP, on localhost only")
+        .arg(Arg::with_name("port").long("port").takes_value(true).default_value("8080"))
+        .arg(Arg::with_name("INDEX").required(true).multiple(true)
+            .help("An index directory or packed file to serve, optionally named as NAME=PATH; otherwise it's named after the last component of its path"))
+        .get_matches();
+
+    let mut indexes: BTreeMap<String, Index> = BTreeMap::new();
+    for arg in matches.values_of("INDEX").unwrap() {
+        let (name, path) = match arg.find('=') {
+            Some(i) => (arg[..i].to_owned(), PathBuf::from(&arg[i + 1..])),
+            None => {
+                let path = PathBuf::from(arg);
+                let name = path.file_stem().map(|stem| stem.to_string_lossy().into_owned()).unwrap_or_else(|| arg.to_owned());
+                (name, path)
+            },
+        };
+        let set = load(&path).unwrap_or_else(|e| exit(&format!("couldn't load {}: {}", path.display(), e)));
+        if indexes.insert(name.clone(), Index { path, set }).is_some() {
+            exit(&format!("more than one index is named {}", name));
+        }
+    }
+
+    let port: u16 = matches.value_of("port").unwrap().parse().unwrap_or_else(|_| exit("invalid port"));
+    let server = Server::http(("127.0.0.1", port)).unwrap_or_else(|e| exit(&e.to_string()));
+    eprintln!("serving {} index(es) on http://{}", indexes.len(), server.server_addr());
+
+    // requests are handled one at a time, which also keeps reloads from racing queries
+    for request in server.incoming_requests() {
+        respond(&mut indexes, request);
+    }
+}
+
+fn exit(message: &str) -> ! {
+    eprintln!("error: {}", message);
+    process::exit(1);
+}
+
+fn load(path: &Path) -> Result<FuzzyPhraseSet, Box<Error>> {
+    if path.is_dir() {
+        FuzzyPhraseSet::from_path(path)
+    } else {
+        FuzzyPhraseSet::from_packed_path(path)
+    }
+}
+
+fn respond(indexes: &mut BTreeMap<String, Index>, mut request: Request) {
+    let mut body = Vec::new();
+    let result = match request.as_reader().read_to_end(&mut body) {
+        Ok(_) => handle(indexes, request.method(), request.url(), &body),
+        Err(e) => Err(HttpError(400, e.to_string())),
+    };
+    let (status, output) = match result {
+        Ok(output) => (200, output),
+        Err(HttpError(status, message)) => (status, json!({ "error": message })),
+    };
+    let content_type = Header::from_bytes(&b"Content-Type"[..], &b"application/json"[..]).unwrap();
+    let response = Response::from_string(output.to_string()).with_status_code(status).with_header(content_type);
+    if let Err(e) = request.respond(response) {
+        eprintln!("error: couldn't send response: {}", e);
+    }
+}
+
+fn handle(indexes: &mut BTreeMap<String, Index>, method: &Method, url: &str, body: &[u8]) -> Result<serde_json::Value, HttpError> {
+    let path = url.split('?').next().unwrap_or("");
+    match (method, path) {
+        (&Method::Get, "/health") => {
+            let loaded: BTreeMap<&str, serde_json::Value> = indexes.iter().map(|(name, index)| {
+                (name.as_str(), json!({ "path": index.path, "words": index.set.words().len() }))
+            }).collect();
+            Ok(json!({ "status": "ok", "indexes": loaded }))
+        },
+        (&Method::Post, "/reload") => {
+            let request: ReloadRequest = if body.is_empty() { ReloadRequest::default() } else { parse(body)? };
+            let names: Vec<String> = match request.index {
+                Some(name) => {
+                    find(indexes, &Some(name.clone()))?;
+                    vec![name]
+                },
+                None => indexes.keys().cloned().collect(),
+            };
+            // load everything before replacing anything, so a failed reload leaves the old
+            // indexes in place
+            let mut reloaded = Vec::new();
+            for name in &names {
+                let index = &indexes[name];
+                let set = load(&index.path).map_err(|e| HttpError(500, format!("couldn't reload {}: {}", name, e)))?;
+                reloaded.push((name.clone(), set));
+            }
+            for (name, set) in reloaded {
+                indexes.get_mut(&name).unwrap().set = set;
+            }
+            Ok(json!({ "reloaded": names }))
+        },
+        (&Method::Post, "/contains") => {
+            let request: ContainsRequest = parse(body)?;
+            let set = find(indexes, &request.index)?;
+            let contains = if request.prefix { set.contains_prefix(&request.phrase) } else { set.contains(&request.phrase) };
+            Ok(json!(contains.map_err(query_error)?))
+        },
+        (&Method::Post, "/fuzzy_match") => {
+            let request: MatchRequest = parse(body)?;
+            let set = find(indexes, &request.index)?;
+            Ok(json!(set.fuzzy_match(&request.phrase, request.max_word_dist, request.max_phrase_dist).map_err(query_error)?))
+        },
+        (&Method::Post, "/fuzzy_match_prefix") => {
+            let request: MatchRequest = parse(body)?;
+            let set = find(indexes, &request.index)?;
+            Ok(json!(set.fuzzy_match_prefix(&request.phrase, request.max_word_dist, request.max_phrase_dist).map_err(query_error)?))
+        },
+        (&Method::Post, "/fuzzy_match_windows") => {
+            let request: MatchRequest = parse(body)?;
+            let set = find(indexes, &request.index)?;
+            Ok(json!(set.fuzzy_match_windows(&request.phrase, request.max_word_dist, request.max_phrase_dist, request.ends_in_prefix).map_err(query_error)?))
+        },
+        (&Method::Post, "/fuzzy_match_multi") => {
+            let request: MultiRequest = parse(body)?;
+            let set = find(indexes, &request.index)?;
+            let phrases: Vec<(&[String], bool)> = request.phrases.iter().map(|p| (p.phrase.as_slice(), p.ends_in_prefix)).collect();
+            Ok(json!(set.fuzzy_match_multi(&phrases, request.max_word_dist, request.max_phrase_dist).map_err(query_error)?))
+        },
+        (_, "/health") | (_, "/reload") | (_, "/contains") | (_, "/fuzzy_match") | (_, "/fuzzy_match_prefix") |
+        (_, "/fuzzy_match_windows") | (_, "/fuzzy_match_multi") => {
+            Err(HttpError(405, format!("{} doesn't support {}", path, method)))
+        },
+        _ => Err(HttpError(404, format!("no such endpoint: {}", path))),
+    }
+}
+
+fn parse<T: DeserializeOwned>(body: &[u8]) -> Result<T, HttpError> {
+    serde_json::from_slice(body).map_err(|e| HttpError(400, format!("invalid request: {}", e)))
+}
+
+// the index a request names, or the only index if it doesn't name one
+fn find<'a>(indexes: &'a BTreeMap<String, Index>, name: &Option<String>) -> Result<&'a FuzzyPhraseSet, HttpError> {
+    match *name {
+        Some(ref name) => indexes.get(name).map(|index| &index.set).ok_or_else(|| HttpError(404, format!("no such index: {}", name))),
+        None if indexes.len() == 1 => Ok(&indexes.values().next().unwrap().set),
+        None => Err(HttpError(400, "more than one index is loaded, so requests must name one".to_owned())),
+    }
+}
+
+fn query_error(e: Box<Error>) -> HttpError {
+    HttpError(400, e.to_string())
+}
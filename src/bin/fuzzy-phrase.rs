@@ -0,0 +1,219 @@
+extern crate clap;
+extern crate fuzzy_phrase;
+#[macro_use]
+extern crate serde_json;
+
+use std::error::Error;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, BufWriter, Write};
+use std::path::Path;
+use std::process;
+
+use clap::{App, AppSettings, Arg, ArgMatches, SubCommand};
+use fuzzy_phrase::glue::{FuzzyPhraseSet, FuzzyPhraseSetBuilder, FuzzyPhraseSetBuilderOptions, NormalizationForm, PhraseReplacement, Tokenizer, TokenizerType, WordReplacement};
+
+fn main() {
+    let matches = App::new("fuzzy-phrase")
+        .about("Build, query and inspect fuzzy-phrase indexes")
+        .setting(AppSettings::SubcommandRequiredElseHelp)
+        .subcommand(SubCommand::with_name("build")
+            .about("Build an index directory from a file with one phrase per line; each phrase's ID is its (zero-based) line number, and blank lines are skipped")
+            .arg(Arg::with_name("PHRASES").required(true).help("The phrase file"))
+            .arg(Arg::with_name("OUTPUT").required(true).help("The directory to write the index to"))
+            .arg(Arg::with_name("replacements").long("replacements").takes_value(true)
+                .help("A JSON file containing an array of {\"from\": ..., \"to\": ...} word replacements, each with an optional \"scope\""))
+            .arg(Arg::with_name("phrase-replacements").long("phrase-replacements").takes_value(true)
+                .help("A JSON file containing an array of {\"from\": [...], \"to\": [...]} phrase replacements, each side a list of words"))
+            .arg(Arg::with_name("max-edit-distance").long("max-edit-distance").takes_value(true))
+            .arg(Arg::with_name("min-fuzzy-word-length").long("min-fuzzy-word-length").takes_value(true))
+            .arg(Arg::with_name("tokenizer").long("tokenizer").takes_value(true).possible_values(&["unicode-words", "space"])
+                .help("How to split phrases (and, later, queries) into words [default: unicode-words]"))
+            .arg(Arg::with_name("normalization-form").long("normalization-form").takes_value(true).possible_values(&["nfc", "nfkc"]))
+            .arg(Arg::with_name("case-fold").long("case-fold"))
+            .arg(Arg::with_name("strip-accents").long("strip-accents"))
+            .arg(Arg::with_name("accent-edit-distance").long("accent-edit-distance").takes_value(true)
+                .help("Charge this many edits for words that differ from the query only in their accents"))
+            .arg(Arg::with_name("phonetic-edit-distance").long("phonetic-edit-distance").takes_value(true)
+                .help("Also match words that sound like the query, charging this many edits for them"))
+            .arg(Arg::with_name("transliteration-edit-distance").long("transliteration-edit-distance").takes_value(true)
+                .help("Also match Cyrillic, Greek and Arabic words that the query romanizes, charging this many edits for them"))
+            .arg(Arg::with_name("language").long("language").takes_value(true)
+                .help("The phrases' language tag, for word replacements scoped to a language"))
+            .arg(Arg::with_name("display").long("display").help("Keep each line as its phrase's display string")))
+        .subcommand(SubCommand::with_name("query")
+            .about("Query an index and print the results as JSON")
+            .arg(Arg::with_name("INDEX").required(true).help("An index directory or packed file"))
+            .arg(Arg::with_name("MODE").required(true).possible_values(&["contains", "prefix", "fuzzy", "windows", "multi"]))
+            .arg(Arg::with_name("QUERY").required(true).multiple(true)
+                .help("The words to look up; for multi, each argument is a whole phrase, and one ending in * is matched as a prefix"))
+            .arg(Arg::with_name("prefix").long("prefix").help("For contains, check whether the query begins any phrase"))
+            .arg(Arg::with_name("ends-in-prefix").long("ends-in-prefix").help("For windows, allow the last word to be a prefix"))
+            .arg(Arg::with_name("query-replacements").long("query-replacements").takes_value(true)
+                .help("A JSON file containing an array of {\"from\": ..., \"to\": ...} word replacements to apply to the query before looking it up"))
+            .arg(Arg::with_name("max-word-dist").long("max-word-dist").takes_value(true).default_value("1"))
+            .arg(Arg::with_name("max-phrase-dist").long("max-phrase-dist").takes_value(true).default_value("1")))
+        .subcommand(SubCommand::with_name("stats")
+            .about("Print the size and options of an index as JSON")
+            .arg(Arg::with_name("INDEX").required(true).help("An index directory or packed file")))
+        .subcommand(SubCommand::with_name("dump")
+            .about("Print an index's lexicon (ID and word) or phrases (phrase and IDs), one per line")
+            .arg(Arg::with_name("INDEX").required(true).help("An index directory or packed file"))
+            .arg(Arg::with_name("WHAT").required(true).possible_values(&["words", "phrases"])))
+        .get_matches();
+
+    let result = match matches.subcommand() {
+        ("build", Some(m)) => build(m),
+        ("query", Some(m)) => query(m),
+        ("stats", Some(m)) => stats(m),
+        ("dump", Some(m)) => dump(m),
+        _ => unreachable!(),
+    };
+    if let Err(e) = result {
+        // stop quietly if our output is piped into something like head that exits early
+        if let Some(io_error) = e.downcast_ref::<io::Error>() {
+            if io_error.kind() == io::ErrorKind::BrokenPipe {
+                return;
+            }
+        }
+        eprintln!("error: {}", e);
+        process::exit(1);
+    }
+}
+
+fn open(path: &str) -> Result<FuzzyPhraseSet, Box<Error>> {
+    if Path::new(path).is_dir() {
+        FuzzyPhraseSet::from_path(path)
+    } else {
+        FuzzyPhraseSet::from_packed_path(path)
+    }
+}
+
+fn print_json(value: &serde_json::Value) -> Result<(), Box<Error>> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    serde_json::to_writer_pretty(&mut out, value)?;
+    writeln!(out)?;
+    Ok(())
+}
+
+fn build(m: &ArgMatches) -> Result<(), Box<Error>> {
+    let mut options = FuzzyPhraseSetBuilderOptions::default();
+    if let Some(max_edit_distance) = m.value_of("max-edit-distance") {
+        options.max_edit_distance = max_edit_distance.parse()?;
+    }
+    if let Some(min_fuzzy_word_length) = m.value_of("min-fuzzy-word-length") {
+        options.min_fuzzy_word_length = min_fuzzy_word_length.parse()?;
+    }
+    if m.value_of("tokenizer") == Some("space") {
+        options.tokenizer = TokenizerType::Space;
+    }
+    options.normalization.form = match m.value_of("normalization-form") {
+        Some("nfc") => Some(NormalizationForm::Nfc),
+        Some("nfkc") => Some(NormalizationForm::Nfkc),
+        _ => None,
+    };
+    options.normalization.case_fold = m.is_present("case-fold");
+    options.normalization.strip_accents = m.is_present("strip-accents");
+    if let Some(accent_edit_distance) = m.value_of("accent-edit-distance") {
+        options.normalization.accent_edit_distance = Some(accent_edit_distance.parse()?);
+    }
+    if let Some(phonetic_edit_distance) = m.value_of("phonetic-edit-distance") {
+        options.phonetic_edit_distance = Some(phonetic_edit_distance.parse()?);
+    }
+    if let Some(transliteration_edit_distance) = m.value_of("transliteration-edit-distance") {
+        options.transliteration_edit_distance = Some(transliteration_edit_distance.parse()?);
+    }
+    options.language = m.value_of("language").map(|language| language.to_owned());
+    let tokenizer = options.tokenizer;
+    let display = m.is_present("display");
+
+    let mut builder = FuzzyPhraseSetBuilder::new_with_options(m.value_of("OUTPUT").unwrap(), options)?;
+    if let Some(replacements) = m.value_of("replacements") {
+        let word_replacements: Vec<WordReplacement> = serde_json::from_reader(BufReader::new(File::open(replacements)?))?;
+        builder.load_word_replacements(word_replacements);
+    }
+    if let Some(phrase_replacements) = m.value_of("phrase-replacements") {
+        let phrase_replacements: Vec<PhraseReplacement> = serde_json::from_reader(BufReader::new(File::open(phrase_replacements)?))?;
+        builder.load_phrase_replacements(phrase_replacements)?;
+    }
+
+    let phrases = BufReader::new(File::open(m.value_of("PHRASES").unwrap())?);
+    for (i, line) in phrases.lines().enumerate() {
+        let line = line?;
+        let phrase = tokenizer.tokenize(&line);
+        if phrase.is_empty() {
+            continue;
+        }
+        if display {
+            builder.insert_with_display(&phrase, i as u64, &line)?;
+        } else {
+            builder.insert(&phrase, i as u64)?;
+        }
+    }
+    builder.finish()
+}
+
+fn query(m: &ArgMatches) -> Result<(), Box<Error>> {
+    let mut set = open(m.value_of("INDEX").unwrap())?;
+    if let Some(query_replacements) = m.value_of("query-replacements") {
+        let query_replacements: Vec<WordReplacement> = serde_json::from_reader(BufReader::new(File::open(query_replacements)?))?;
+        set.load_query_replacements(query_replacements)?;
+    }
+    let max_word_dist: u8 = m.value_of("max-word-dist").unwrap().parse()?;
+    let max_phrase_dist: u8 = m.value_of("max-phrase-dist").unwrap().parse()?;
+    let queries: Vec<&str> = m.values_of("QUERY").unwrap().collect();
+    // outside of multi, the arguments are all parts of the same query, however they were quoted
+    let query = queries.join(" ");
+    let words = set.tokenize(&query);
+
+    let output = match m.value_of("MODE").unwrap() {
+        "contains" if m.is_present("prefix") => json!(set.contains_prefix(&words)?),
+        "contains" => json!(set.contains(&words)?),
+        "prefix" => json!(set.fuzzy_match_prefix(&words, max_word_dist, max_phrase_dist)?),
+        "fuzzy" => json!(set.fuzzy_match(&words, max_word_dist, max_phrase_dist)?),
+        "windows" => json!(set.fuzzy_match_windows(&words, max_word_dist, max_phrase_dist, m.is_present("ends-in-prefix"))?),
+        "multi" => {
+            let phrases: Vec<(Vec<&str>, bool)> = queries.iter().map(|query| {
+                let ends_in_prefix = query.ends_with('*');
+                (set.tokenize(query.trim_end_matches('*')), ends_in_prefix)
+            }).collect();
+            json!(set.fuzzy_match_multi(&phrases, max_word_dist, max_phrase_dist)?)
+        },
+        _ => unreachable!(),
+    };
+    print_json(&output)
+}
+
+fn stats(m: &ArgMatches) -> Result<(), Box<Error>> {
+    let set = open(m.value_of("INDEX").unwrap())?;
+    let (phrase_count, phrase_id_count) = set.phrases().fold((0, 0), |(phrases, ids), (_, phrase_ids)| {
+        (phrases + 1, ids + phrase_ids.len())
+    });
+    let output = json!({
+        "words": set.words().len(),
+        "phrases": phrase_count,
+        "phrase_ids": phrase_id_count,
+        "deleted_phrases": set.deleted_len(),
+        "word_replacements": set.word_replacements().len(),
+        "phrase_replacements": set.phrase_replacements().len(),
+        "options": set.options(),
+    });
+    print_json(&output)
+}
+
+fn dump(m: &ArgMatches) -> Result<(), Box<Error>> {
+    let set = open(m.value_of("INDEX").unwrap())?;
+    let stdout = io::stdout();
+    let mut out = BufWriter::new(stdout.lock());
+    match m.value_of("WHAT").unwrap() {
+        "words" => for (id, word) in set.words().iter().enumerate() {
+            writeln!(out, "{}\t{}", id, word)?;
+        },
+        "phrases" => for (phrase, phrase_ids) in set.phrases() {
+            let phrase_ids: Vec<String> = phrase_ids.iter().map(|id| id.to_string()).collect();
+            writeln!(out, "{}\t{}", phrase.join(" "), phrase_ids.join(","))?;
+        },
+        _ => unreachable!(),
+    }
+    Ok(())
+}
@@ -0,0 +1,146 @@
+use std::io::prelude::*;
+#[cfg(feature = "mmap")]
+use std::path::Path;
+use std::collections::BTreeSet;
+use fst::{IntoStreamer, SetBuilder, Streamer};
+use fst::raw;
+use fst::Error as FstError;
+use byteorder::{BigEndian, ByteOrder};
+
+// An alias graph is a set rather than a map: each key is an alias, a zero byte, and the ID of a
+// word it stands for as a big-endian u32, so all the words that share an alias are adjacent and
+// can be read out with a range query, without a side list of IDs like the fuzzy graph's.
+
+/// A many-to-many mapping from strings to word IDs, for finding lexicon words by something other
+/// than their spelling: by how they sound (see `phonetic::phonetic_keys`), or by how they'd be
+/// written in another script (see `transliteration`).
+pub struct AliasMap(raw::Fst);
+
+impl AliasMap {
+    #[cfg(feature = "mmap")]
+    pub unsafe fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, FstError> {
+        raw::Fst::from_path(path).map(AliasMap)
+    }
+
+    #[cfg(feature = "mmap")]
+    pub fn from_mmap(mmap: raw::MmapReadOnly) -> Result<Self, FstError> {
+        raw::Fst::from_mmap(mmap).map(AliasMap)
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, FstError> {
+        raw::Fst::from_bytes(bytes).map(AliasMap)
+    }
+
+    pub fn from_reader<R: Read>(mut rdr: R) -> Result<Self, FstError> {
+        let mut bytes: Vec<u8> = Vec::new();
+        rdr.read_to_end(&mut bytes)?;
+        AliasMap::from_bytes(bytes)
+    }
+
+    /// The IDs of all the words any of `aliases` stand for, in order.
+    pub fn lookup<T: AsRef<str>>(&self, aliases: &[T]) -> Vec<u32> {
+        let mut ids: BTreeSet<u32> = BTreeSet::new();
+        for alias in aliases {
+            let mut start = alias.as_ref().as_bytes().to_vec();
+            start.push(0);
+            let mut end = start.clone();
+            *end.last_mut().unwrap() = 1;
+
+            let mut stream = self.0.range().ge(&start).lt(&end).into_stream();
+            while let Some((entry, _)) = stream.next() {
+                if entry.len() == start.len() + 4 {
+                    ids.insert(BigEndian::read_u32(&entry[start.len()..]));
+                }
+            }
+        }
+        ids.into_iter().collect()
+    }
+
+    /// Check every entry's word ID against a lexicon of `word_count` words, returning each
+    /// distinct bad ID once.
+    pub fn dangling_references(&self, word_count: usize) -> Vec<u32> {
+        let mut dangling: BTreeSet<u32> = BTreeSet::new();
+        let mut stream = self.0.stream();
+        while let Some((entry, _)) = stream.next() {
+            if entry.len() < 5 || entry[entry.len() - 5] != 0 {
+                continue;
+            }
+            let id = BigEndian::read_u32(&entry[(entry.len() - 4)..]);
+            if id as usize >= word_count {
+                dangling.insert(id);
+            }
+        }
+        dangling.into_iter().collect()
+    }
+}
+
+// Entries for different aliases of the same word arrive out of order, so unlike the other
+// builders this one holds on to them until the end, and only then streams the sorted graph out.
+pub struct AliasMapBuilder<W = Vec<u8>> {
+    entries: Vec<Vec<u8>>,
+    wtr: W,
+}
+
+impl AliasMapBuilder<Vec<u8>> {
+    pub fn memory() -> Self {
+        AliasMapBuilder::new(Vec::new())
+    }
+}
+
+impl<W: Write> AliasMapBuilder<W> {
+    pub fn new(wtr: W) -> Self {
+        AliasMapBuilder { entries: Vec::new(), wtr }
+    }
+
+    /// Record that each of `aliases` stands for the word `id`. Aliases can't contain zero bytes.
+    pub fn insert<T: AsRef<str>>(&mut self, aliases: &[T], id: u32) {
+        for alias in aliases {
+            let mut entry = alias.as_ref().as_bytes().to_vec();
+            entry.push(0);
+            let mut id_bytes = [0u8; 4];
+            BigEndian::write_u32(&mut id_bytes, id);
+            entry.extend_from_slice(&id_bytes);
+            self.entries.push(entry);
+        }
+    }
+
+    pub fn into_inner(mut self) -> Result<W, FstError> {
+        self.entries.sort();
+        self.entries.dedup();
+        let mut builder = SetBuilder::new(self.wtr)?;
+        builder.extend_iter(self.entries)?;
+        builder.into_inner()
+    }
+
+    pub fn finish(self) -> Result<(), FstError> {
+        self.into_inner().map(|_| ())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn alias_map_test() -> () {
+        let mut builder = AliasMapBuilder::memory();
+        // words can have several aliases, and aliases several words, inserted in any order
+        builder.insert(&["MSK", "MSKW"], 1);
+        builder.insert(&["MSK"], 0);
+        builder.insert(&["MNXN"], 3);
+        builder.insert(&["MSKWA"], 4);
+        builder.insert(&["MNK", "MNXN"], 2);
+        builder.insert(&["MSK"], 0);
+        let map = AliasMap::from_bytes(builder.into_inner().unwrap()).unwrap();
+
+        assert_eq!(map.lookup(&["MSK"]), vec![0, 1]);
+        assert_eq!(map.lookup(&["MNXN", "MSKW"]), vec![1, 2, 3]);
+        assert_eq!(map.lookup(&["BSTN"]), Vec::<u32>::new());
+        assert_eq!(map.lookup(&Vec::<String>::new()), Vec::<u32>::new());
+        // a key that's a prefix of another key doesn't find the other key's words
+        assert_eq!(map.lookup(&["MS"]), Vec::<u32>::new());
+        assert_eq!(map.lookup(&["MSKW"]), vec![1]);
+        assert_eq!(map.dangling_references(5), Vec::<u32>::new());
+        assert_eq!(map.dangling_references(2), vec![2, 3, 4]);
+    }
+}
@@ -1,13 +1,16 @@
 use std::fs;
 use std::error::Error;
 use std::cmp::{min, Ordering};
+use std::collections::BTreeSet;
 use itertools::Itertools;
+use fst::Streamer;
 use fst::raw;
 use fst::Error as FstError;
-#[cfg(feature = "mmap")]
 use std::path::{Path, PathBuf};
 use std::fs::File;
-use std::io::{BufReader, BufWriter};
+#[cfg(feature = "mmap")]
+use std::io::BufReader;
+use std::io::{BufWriter, Read, Write};
 use serde::{Deserialize, Serialize};
 use rmps::{Deserializer, Serializer};
 use std::io::{Error as IoError, ErrorKind as IoErrorKind};
@@ -25,6 +28,14 @@ pub struct FuzzyMap {
 #[derive(Serialize, Deserialize)]
 pub struct SerializableIdList(Vec<Vec<u32>>);
 
+/// Something in the map that refers to a word or ID list that doesn't exist, as found by
+/// `FuzzyMap::dangling_references`.
+#[derive(PartialEq, Eq, PartialOrd, Ord, Debug, Clone)]
+pub enum FuzzyMapDanglingReference {
+    WordId(u32),
+    IdListIndex(u64),
+}
+
 #[derive(PartialEq, Eq, Debug)]
 pub struct FuzzyMapLookupResult {
     pub word: String,
@@ -44,14 +55,66 @@ impl PartialOrd for FuzzyMapLookupResult {
     }
 }
 
+// the msgpack-encoded list of IDs shared by variants, as FuzzyMapBuilder writes it
+fn read_id_list<R: Read>(rdr: R) -> Result<Vec<Vec<u32>>, FstError> {
+    let id_list: SerializableIdList = Deserialize::deserialize(&mut Deserializer::new(rdr))
+        .map_err(|_e| FstError::Io(IoError::new(IoErrorKind::InvalidData, "Malformed fuzzy ID list")))?;
+    Ok(id_list.0)
+}
+
 impl FuzzyMap {
     #[cfg(feature = "mmap")]
     pub unsafe fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, FstError> {
         let file_start = path.as_ref();
         let fst = raw::Fst::from_path(file_start.with_extension("fst"))?;
         let mf_reader = BufReader::new(fs::File::open(file_start.with_extension("msg"))?);
-        let id_list: SerializableIdList = Deserialize::deserialize(&mut Deserializer::new(mf_reader)).unwrap();
-        Ok(FuzzyMap { id_list: id_list.0, fst: fst })
+        Ok(FuzzyMap { id_list: read_id_list(mf_reader)?, fst: fst })
+    }
+
+    /// Read the variant graph and the ID list (the `.fst` and `.msg` files `FuzzyMapBuilder::new`
+    /// writes) into memory; this is how to load them without the `mmap` feature.
+    pub fn from_readers<R: Read, S: Read>(mut fst_rdr: R, id_list_rdr: S) -> Result<Self, FstError> {
+        let mut fst_bytes: Vec<u8> = Vec::new();
+        fst_rdr.read_to_end(&mut fst_bytes)?;
+        let fst = raw::Fst::from_bytes(fst_bytes)?;
+        Ok(FuzzyMap { id_list: read_id_list(id_list_rdr)?, fst: fst })
+    }
+
+    /// Like `from_bytes`, but with the variant graph in a region of a memory map.
+    #[cfg(feature = "mmap")]
+    pub fn from_mmap(fst_mmap: raw::MmapReadOnly, id_list_bytes: &[u8]) -> Result<Self, FstError> {
+        let fst = raw::Fst::from_mmap(fst_mmap)?;
+        Ok(FuzzyMap { id_list: read_id_list(id_list_bytes)?, fst: fst })
+    }
+
+    /// Create from the two buffers written by an in-memory `FuzzyMapBuilder`: the variant graph,
+    /// and the msgpack-encoded list of IDs shared by variants.
+    pub fn from_bytes(fst_bytes: Vec<u8>, id_list_bytes: &[u8]) -> Result<Self, FstError> {
+        let fst = raw::Fst::from_bytes(fst_bytes)?;
+        Ok(FuzzyMap { id_list: read_id_list(id_list_bytes)?, fst: fst })
+    }
+
+    /// Check every variant's output, and every entry of the shared ID lists, against a lexicon of
+    /// `word_count` words, returning each distinct bad reference once.
+    pub fn dangling_references(&self, word_count: usize) -> Vec<FuzzyMapDanglingReference> {
+        let mut dangling: BTreeSet<FuzzyMapDanglingReference> = BTreeSet::new();
+        let mut stream = self.fst.stream();
+        while let Some((_key, output)) = stream.next() {
+            let value = output.value();
+            if value & MULTI_FLAG != 0 {
+                if (value & MULTI_MASK) as usize >= self.id_list.len() {
+                    dangling.insert(FuzzyMapDanglingReference::IdListIndex(value & MULTI_MASK));
+                }
+            } else if value as usize >= word_count {
+                dangling.insert(FuzzyMapDanglingReference::WordId(value as u32));
+            }
+        }
+        for id in self.id_list.iter().flat_map(|ids| ids.iter()) {
+            if *id as usize >= word_count {
+                dangling.insert(FuzzyMapDanglingReference::WordId(*id));
+            }
+        }
+        dangling.into_iter().collect()
     }
 
     fn find_matching_variants(&self, query: &[u8], indices: &[usize], position: usize, edit_distance: usize, node: &raw::Node, so_far: u64, out: &mut Vec<u64>) {
@@ -145,10 +208,11 @@ impl FuzzyMap {
     }
 }
 
-pub struct FuzzyMapBuilder {
+pub struct FuzzyMapBuilder<W = BufWriter<File>> {
     id_builder: Vec<Vec<u32>>,
-    builder: raw::Builder<BufWriter<File>>,
-    file_path: PathBuf,
+    builder: raw::Builder<W>,
+    // where the ID list goes; in-memory builders hand it back instead
+    file_path: Option<PathBuf>,
     word_variants: Vec<(String, u32)>,
     edit_distance: u8,
 }
@@ -161,7 +225,7 @@ impl FuzzyMapBuilder {
         Ok(FuzzyMapBuilder {
             builder: raw::Builder::new_type(fst_wtr, 0)?,
             id_builder: Vec::<Vec<u32>>::new(),
-            file_path: file_start,
+            file_path: Some(file_start),
             word_variants: Vec::<(String, u32)>::new(),
             edit_distance: edit_distance,
         })
@@ -177,6 +241,42 @@ impl FuzzyMapBuilder {
         Ok(())
     }
 
+    pub fn finish(mut self) -> Result<(), FstError> {
+        self.insert_variants()?;
+        let file_path = self.file_path.take().expect("file-backed builders always have a path");
+        let mf_wtr = BufWriter::new(fs::File::create(file_path.with_extension("msg"))?);
+        match SerializableIdList(self.id_builder).serialize(&mut Serializer::new(mf_wtr)) {
+            Err(_e) => return Err(FstError::Io(IoError::new(IoErrorKind::InvalidInput, "File exists and is not a directory"))),
+            Ok(()) => ()
+        };
+        self.builder.finish()
+    }
+}
+
+impl FuzzyMapBuilder<Vec<u8>> {
+    pub fn memory(edit_distance: u8) -> Self {
+        FuzzyMapBuilder {
+            builder: raw::Builder::memory(),
+            id_builder: Vec::<Vec<u32>>::new(),
+            file_path: None,
+            word_variants: Vec::<(String, u32)>::new(),
+            edit_distance: edit_distance,
+        }
+    }
+
+    /// Finish building, and return the variant graph and the encoded ID list, in the form
+    /// `FuzzyMap::from_bytes` expects them.
+    pub fn into_inner(mut self) -> Result<(Vec<u8>, Vec<u8>), FstError> {
+        self.insert_variants()?;
+        let mut id_list_bytes: Vec<u8> = Vec::new();
+        if SerializableIdList(self.id_builder).serialize(&mut Serializer::new(&mut id_list_bytes)).is_err() {
+            return Err(FstError::Io(IoError::new(IoErrorKind::InvalidInput, "Couldn't encode fuzzy ID list")));
+        }
+        Ok((self.builder.into_inner()?, id_list_bytes))
+    }
+}
+
+impl<W: Write> FuzzyMapBuilder<W> {
     pub fn insert(&mut self, key: &str, id: u32) -> () {
         self.word_variants.push((key.to_owned(), id));
         let variants = super::get_variants(&key, self.edit_distance);
@@ -185,7 +285,7 @@ impl FuzzyMapBuilder {
         }
     }
 
-    pub fn finish(mut self) -> Result<(), FstError> {
+    fn insert_variants(&mut self) -> Result<(), FstError> {
         self.word_variants.sort();
 
         for (key, group) in &(&self.word_variants).iter().dedup().group_by(|t| &t.0) {
@@ -198,12 +298,7 @@ impl FuzzyMapBuilder {
             };
             self.builder.insert(key, id)?;
         }
-        let mf_wtr = BufWriter::new(fs::File::create(self.file_path.with_extension("msg"))?);
-        match SerializableIdList(self.id_builder).serialize(&mut Serializer::new(mf_wtr)) {
-            Err(_e) => return Err(FstError::Io(IoError::new(IoErrorKind::InvalidInput, "File exists and is not a directory"))),
-            Ok(()) => ()
-        };
-        self.builder.finish()
+        Ok(())
     }
 }
 
@@ -242,17 +337,31 @@ mod tests {
             let file_start = dir.path().join("fuzzy");
             FuzzyMapBuilder::build_from_iter(&file_start, WORDS.iter().cloned(), 1).unwrap();
 
-            unsafe { FuzzyMap::from_path(&file_start).unwrap() }
+            load(&file_start)
         };
         static ref MAP_D2: FuzzyMap = {
             let dir = tempfile::tempdir().unwrap();
             let file_start = dir.path().join("fuzzy");
             FuzzyMapBuilder::build_from_iter(&file_start, WORDS.iter().cloned(), 2).unwrap();
 
-            unsafe { FuzzyMap::from_path(&file_start).unwrap() }
+            load(&file_start)
         };
     }
 
+    // exercise whichever way of loading from files the current features allow
+    #[cfg(feature = "mmap")]
+    fn load(file_start: &Path) -> FuzzyMap {
+        unsafe { FuzzyMap::from_path(file_start).unwrap() }
+    }
+
+    #[cfg(not(feature = "mmap"))]
+    fn load(file_start: &Path) -> FuzzyMap {
+        FuzzyMap::from_readers(
+            File::open(file_start.with_extension("fst")).unwrap(),
+            File::open(file_start.with_extension("msg")).unwrap()
+        ).unwrap()
+    }
+
     fn expect(word: &'static str, query: &'static str) -> FuzzyMapLookupResult {
         FuzzyMapLookupResult { word: word.to_owned(), id: WORDS.binary_search(&word).unwrap() as u32, edit_distance: multi_modified_damlev(&word, &[&query])[0] as u8 }
     }
@@ -324,6 +433,20 @@ mod tests {
         lazy_static::initialize(&MAP_D2);
     }
 
+    #[test]
+    fn lookup_test_memory_d1() {
+        let mut builder = FuzzyMapBuilder::memory(1);
+        for (i, word) in WORDS.iter().enumerate() {
+            builder.insert(word, i as u32);
+        }
+        let (fst_bytes, id_list_bytes) = builder.into_inner().unwrap();
+        let map = FuzzyMap::from_bytes(fst_bytes, &id_list_bytes).unwrap();
+
+        for query in &["Shleton", "Москва", "Christina", "Grayton"] {
+            assert_eq!(map.lookup(query, 1, get_word).unwrap(), MAP_D1.lookup(query, 1, get_word).unwrap());
+        }
+    }
+
     #[test]
     fn lookup_test_cases_d_2() {
         let query = "Keedy";
@@ -1,8 +1,15 @@
 use std::collections::HashSet;
 pub mod map;
+pub mod alias;
+pub mod phonetic;
+pub mod transliteration;
 mod util;
 pub use self::map::FuzzyMap;
 pub use self::map::FuzzyMapBuilder;
+pub use self::alias::AliasMap;
+pub use self::alias::AliasMapBuilder;
+pub use self::phonetic::PhoneticMap;
+pub use self::phonetic::PhoneticMapBuilder;
 
 #[inline(always)]
 fn get_variants<'a>(word: &str, edit_distance: u8) -> HashSet<String> {
@@ -0,0 +1,294 @@
+use std::io::prelude::*;
+#[cfg(feature = "mmap")]
+use std::path::Path;
+#[cfg(feature = "mmap")]
+use fst::raw;
+use fst::Error as FstError;
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::is_combining_mark;
+
+use super::alias::{AliasMap, AliasMapBuilder};
+
+/// Words that sound alike, grouped by their phonetic keys (see `phonetic_keys`), for catching
+/// misspellings made by ear that are too far from the real word in edit distance for the fuzzy
+/// graph to find. This is an `AliasMap` whose aliases are phonetic keys, so either can read the
+/// same graph.
+pub struct PhoneticMap(AliasMap);
+
+impl PhoneticMap {
+    #[cfg(feature = "mmap")]
+    pub unsafe fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, FstError> {
+        AliasMap::from_path(path).map(PhoneticMap)
+    }
+
+    #[cfg(feature = "mmap")]
+    pub fn from_mmap(mmap: raw::MmapReadOnly) -> Result<Self, FstError> {
+        AliasMap::from_mmap(mmap).map(PhoneticMap)
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, FstError> {
+        AliasMap::from_bytes(bytes).map(PhoneticMap)
+    }
+
+    pub fn from_reader<R: Read>(rdr: R) -> Result<Self, FstError> {
+        AliasMap::from_reader(rdr).map(PhoneticMap)
+    }
+
+    /// The IDs of all the words that share a phonetic key with `word`, in order.
+    pub fn lookup(&self, word: &str) -> Vec<u32> {
+        self.0.lookup(&phonetic_keys(word))
+    }
+
+    /// Check every entry's word ID against a lexicon of `word_count` words, returning each
+    /// distinct bad ID once.
+    pub fn dangling_references(&self, word_count: usize) -> Vec<u32> {
+        self.0.dangling_references(word_count)
+    }
+}
+
+pub struct PhoneticMapBuilder(AliasMapBuilder);
+
+impl PhoneticMapBuilder {
+    pub fn memory() -> Self {
+        PhoneticMapBuilder(AliasMapBuilder::memory())
+    }
+
+    /// Add a word under each of its phonetic keys. Words without any (because they aren't
+    /// written in the Latin alphabet, for instance) are left out.
+    pub fn insert(&mut self, word: &str, id: u32) {
+        self.0.insert(&phonetic_keys(word), id)
+    }
+
+    pub fn into_inner(self) -> Result<Vec<u8>, FstError> {
+        self.0.into_inner()
+    }
+}
+
+/// The phonetic keys for a word: a simplified Double Metaphone encoding, which keeps a word's
+/// consonant sounds and drops most of its vowels, so that, e.g., "Philadelphia" and "Filadelfia"
+/// are both "FLTLF". Letters with more than one likely pronunciation, like the "ch" in "Michael"
+/// or "Schenectady", produce a second, alternate key. Words that contain anything other than
+/// (possibly accented) Latin letters and apostrophes or hyphens have no keys, and neither do words
+/// whose keys would be shorter than two sounds, since those match too many other words to be
+/// useful.
+pub fn phonetic_keys(word: &str) -> Vec<String> {
+    let letters = match to_ascii_letters(word) {
+        Some(letters) => letters,
+        None => return Vec::new(),
+    };
+    let mut encoder = Encoder { letters: &letters, primary: String::new(), alternate: String::new() };
+    encoder.encode();
+
+    let mut keys: Vec<String> = Vec::new();
+    for key in [encoder.primary, encoder.alternate] {
+        if key.len() >= 2 && !keys.contains(&key) {
+            keys.push(key);
+        }
+    }
+    keys
+}
+
+// uppercase, unaccented ASCII letters, or nothing if the word has anything we can't spell that way
+fn to_ascii_letters(word: &str) -> Option<Vec<u8>> {
+    let mut letters: Vec<u8> = Vec::with_capacity(word.len());
+    for c in word.nfd().filter(|c| !is_combining_mark(*c)) {
+        match c {
+            'a'..='z' | 'A'..='Z' => letters.push(c.to_ascii_uppercase() as u8),
+            '\'' | '’' | '-' => (),
+            'ß' => letters.extend_from_slice(b"SS"),
+            'æ' | 'Æ' => letters.extend_from_slice(b"AE"),
+            'œ' | 'Œ' => letters.extend_from_slice(b"OE"),
+            'ø' | 'Ø' => letters.push(b'O'),
+            'ł' | 'Ł' => letters.push(b'L'),
+            'đ' | 'Đ' | 'ð' | 'Ð' => letters.push(b'D'),
+            'þ' | 'Þ' => letters.extend_from_slice(b"TH"),
+            _ => return None,
+        }
+    }
+    if letters.is_empty() {
+        None
+    } else {
+        Some(letters)
+    }
+}
+
+fn is_vowel(letter: u8) -> bool {
+    matches!(letter, b'A' | b'E' | b'I' | b'O' | b'U' | b'Y')
+}
+
+struct Encoder<'a> {
+    letters: &'a [u8],
+    primary: String,
+    alternate: String,
+}
+
+impl<'a> Encoder<'a> {
+    // the letter at a position, or a zero byte past either end
+    fn at(&self, i: usize) -> u8 {
+        *self.letters.get(i).unwrap_or(&0)
+    }
+
+    fn at_is(&self, i: usize, options: &[&str]) -> bool {
+        options.iter().any(|option| self.letters[i..].starts_with(option.as_bytes()))
+    }
+
+    fn push(&mut self, sounds: &str) {
+        self.push_either(sounds, sounds);
+    }
+
+    fn push_either(&mut self, primary: &str, alternate: &str) {
+        self.primary.push_str(primary);
+        self.alternate.push_str(alternate);
+    }
+
+    fn encode(&mut self) {
+        let letters = self.letters;
+        let len = letters.len();
+
+        // some initial letters are silent or sound like something else; a vowel is only written
+        // down if it's the first sound
+        let mut i = 0;
+        let mut first = 0;
+        if self.at_is(0, &["AE", "GN", "KN", "PN", "WR"]) {
+            i = 1;
+            first = 1;
+        } else if self.at(0) == b'X' {
+            self.push("S");
+            i = 1;
+        } else if self.at_is(0, &["WH"]) {
+            self.push("W");
+            i = 2;
+        }
+
+        while i < len {
+            let letter = letters[i];
+            let prev = if i == 0 { 0 } else { letters[i - 1] };
+            let next = self.at(i + 1);
+            // doubled letters sound like single ones, except for "cc" as in "accent"
+            if letter == prev && letter != b'C' {
+                i += 1;
+                continue;
+            }
+
+            // how many letters this step consumes
+            let mut step = 1;
+            match letter {
+                b'A' | b'E' | b'I' | b'O' | b'U' => if i == first {
+                    self.push("A");
+                },
+                b'B' => if !(prev == b'M' && i == len - 1) {
+                    self.push("P");
+                },
+                b'C' => if self.at_is(i, &["CIA"]) {
+                    self.push("X");
+                } else if next == b'H' {
+                    // "ch" as in "church", or as in "chorus"
+                    self.push_either("X", "K");
+                    step = 2;
+                } else if next == b'E' || next == b'I' || next == b'Y' {
+                    self.push("S");
+                } else {
+                    self.push("K");
+                },
+                b'D' => if next == b'G' && (self.at(i + 2) == b'E' || self.at(i + 2) == b'I' || self.at(i + 2) == b'Y') {
+                    self.push("J");
+                    step = 2;
+                } else {
+                    self.push("T");
+                },
+                b'G' => if next == b'H' {
+                    // "gh" is hard before a vowel, as in "ghost", and otherwise silent, as in "night"
+                    if i == 0 || is_vowel(self.at(i + 2)) {
+                        self.push("K");
+                    }
+                    step = 2;
+                } else if next == b'N' && (i + 2 == len || (self.at_is(i + 1, &["NED"]) && i + 4 == len)) {
+                    // silent, as in "sign" and "signed"
+                } else if next == b'E' || next == b'I' || next == b'Y' {
+                    // soft as in "gem", or hard as in "get"
+                    self.push_either("J", "K");
+                } else {
+                    self.push("K");
+                },
+                b'H' => if is_vowel(next) && !is_vowel(prev) {
+                    self.push("H");
+                },
+                b'K' => if prev != b'C' {
+                    self.push("K");
+                },
+                b'P' => if next == b'H' {
+                    self.push("F");
+                    step = 2;
+                } else {
+                    self.push("P");
+                },
+                b'Q' => self.push("K"),
+                b'S' => if self.at_is(i, &["SCH"]) {
+                    // "sch" as in "schnitzel", or as in "school"
+                    self.push_either("X", "SK");
+                    step = 3;
+                } else if next == b'H' {
+                    self.push("X");
+                    step = 2;
+                } else if self.at_is(i, &["SIO", "SIA"]) {
+                    self.push("X");
+                } else {
+                    self.push("S");
+                },
+                b'T' => if self.at_is(i, &["TIA", "TIO"]) {
+                    self.push("X");
+                } else if next == b'H' {
+                    self.push("0");
+                    step = 2;
+                } else if !self.at_is(i, &["TCH"]) {
+                    self.push("T");
+                },
+                b'V' => self.push("F"),
+                b'W' | b'Y' => if is_vowel(next) && next != b'Y' {
+                    self.push(if letter == b'W' { "W" } else { "Y" });
+                },
+                b'X' => self.push("KS"),
+                b'Z' => self.push("S"),
+                // F, J, L, M, N and R
+                _ => self.push(&(letter as char).to_string()),
+            }
+            i += step;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn phonetic_map_test() -> () {
+        // the map keys words by how they sound, and looks them up by another spelling's keys
+        let words = ["filadelfia", "philadelphia", "pittsburgh", "schenectady"];
+        let mut builder = PhoneticMapBuilder::memory();
+        for (id, word) in words.iter().enumerate() {
+            builder.insert(word, id as u32);
+        }
+        let map = PhoneticMap::from_bytes(builder.into_inner().unwrap()).unwrap();
+
+        assert_eq!(map.lookup("Fillydelfia"), vec![0, 1]);
+        // either of a word's keys finds it
+        assert_eq!(map.lookup("shenectady"), vec![3]);
+        assert_eq!(map.lookup("skenectady"), vec![3]);
+    }
+
+    #[test]
+    fn phonetic_keys_test() -> () {
+        assert_eq!(phonetic_keys("Philadelphia"), vec!["FLTLF"]);
+        assert_eq!(phonetic_keys("filadelfia"), vec!["FLTLF"]);
+        assert_eq!(phonetic_keys("Shenectady"), vec!["XNKTT"]);
+        assert_eq!(phonetic_keys("Schenectady"), vec!["XNKTT", "SKNKTT"]);
+        assert_eq!(phonetic_keys("Knight"), vec!["NT"]);
+        assert_eq!(phonetic_keys("Aeneas"), vec!["ANS"]);
+        // too short to be useful
+        assert_eq!(phonetic_keys("São"), Vec::<String>::new());
+        assert_eq!(phonetic_keys("Straße"), phonetic_keys("strasse"));
+        assert_eq!(phonetic_keys("москва"), Vec::<String>::new());
+        assert_eq!(phonetic_keys("4th"), Vec::<String>::new());
+    }
+}
@@ -0,0 +1,242 @@
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::is_combining_mark;
+
+// Romanization is done a character (or, for Greek, a digraph) at a time from these tables, which
+// loosely follow the BGN/PCGN systems. Each entry has the usual romanization and, where there's a
+// common competing one, an alternate; words get a key for each.
+
+// Russian, Ukrainian, Belarusian, Serbian and Macedonian letters
+static CYRILLIC: &[(char, &str, Option<&str>)] = &[
+    ('а', "a", None), ('б', "b", None), ('в', "v", None), ('г', "g", None), ('д', "d", None),
+    ('е', "e", None), ('ё', "e", Some("yo")), ('ж', "zh", None), ('з', "z", None), ('и', "i", None),
+    ('й', "y", Some("i")), ('к', "k", None), ('л', "l", None), ('м', "m", None), ('н', "n", None),
+    ('о', "o", None), ('п', "p", None), ('р', "r", None), ('с', "s", None), ('т', "t", None),
+    ('у', "u", None), ('ф', "f", None), ('х', "kh", Some("h")), ('ц', "ts", None), ('ч', "ch", None),
+    ('ш', "sh", None), ('щ', "shch", None), ('ъ', "", None), ('ы', "y", None), ('ь', "", None),
+    ('э', "e", None), ('ю', "yu", Some("iu")), ('я', "ya", Some("ia")),
+    ('і', "i", None), ('ї', "yi", None), ('є', "ye", None), ('ґ', "g", None), ('ў', "u", None),
+    ('ђ', "dj", None), ('ј', "j", None), ('љ', "lj", None), ('њ', "nj", None), ('ћ', "c", None),
+    ('џ', "dz", None), ('ѓ', "gj", None), ('ќ', "kj", None), ('ѕ', "dz", None),
+];
+
+// modern Greek, with accents and diaereses already removed; digraphs come first so they're
+// preferred over their letters
+static GREEK: &[(&str, &str, Option<&str>)] = &[
+    ("ου", "ou", None), ("αυ", "av", None), ("ευ", "ev", None), ("ηυ", "iv", None),
+    ("γγ", "ng", None), ("γξ", "nx", None), ("γχ", "nch", None),
+    ("α", "a", None), ("β", "v", None), ("γ", "g", None), ("δ", "d", None), ("ε", "e", None),
+    ("ζ", "z", None), ("η", "i", None), ("θ", "th", None), ("ι", "i", None), ("κ", "k", None),
+    ("λ", "l", None), ("μ", "m", None), ("ν", "n", None), ("ξ", "x", None), ("ο", "o", None),
+    ("π", "p", None), ("ρ", "r", None), ("σ", "s", None), ("ς", "s", None), ("τ", "t", None),
+    ("υ", "y", Some("i")), ("φ", "f", None), ("χ", "ch", Some("h")), ("ψ", "ps", None), ("ω", "o", None),
+];
+
+// Arabic (and the extra Persian and Urdu) letters; short vowels aren't written, so Arabic words
+// are only compared by their consonants (see `skeleton`), and the long vowels and hamza carriers
+// just need to be vowels here
+static ARABIC: &[(char, &str)] = &[
+    ('ء', ""), ('آ', "a"), ('أ', "a"), ('ؤ', "u"), ('إ', "i"), ('ئ', "i"), ('ا', "a"), ('ب', "b"),
+    ('ة', "a"), ('ت', "t"), ('ث', "th"), ('ج', "j"), ('ح', "h"), ('خ', "kh"), ('د', "d"),
+    ('ذ', "dh"), ('ر', "r"), ('ز', "z"), ('س', "s"), ('ش', "sh"), ('ص', "s"), ('ض', "d"),
+    ('ط', "t"), ('ظ', "z"), ('ع', ""), ('غ', "gh"), ('ف', "f"), ('ق', "q"), ('ك', "k"), ('ل', "l"),
+    ('م', "m"), ('ن', "n"), ('ه', "h"), ('و', "w"), ('ى', "a"), ('ي', "y"), ('ـ', ""),
+    ('پ', "p"), ('چ', "ch"), ('ژ', "zh"), ('گ', "g"), ('ک', "k"), ('ی', "y"),
+];
+
+// consonant-skeleton keys are marked so they can't collide with full romanizations
+const SKELETON_MARKER: char = '~';
+
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+enum Script {
+    Cyrillic,
+    Greek,
+    Arabic,
+}
+
+/// The Latin-alphabet keys under which a Cyrillic, Greek or Arabic word is indexed, so that a
+/// romanized query word can find it with `query_keys`: the word's romanization (and an alternate
+/// one, if any of its letters are commonly romanized more than one way), or, for Arabic, which is
+/// usually written without its short vowels, the consonants of its romanization, both with and
+/// without a leading definite article. Words in any other script, or in more than one, have no
+/// keys.
+pub fn transliteration_keys(word: &str) -> Vec<String> {
+    let lowercase: Vec<char> = word.nfc().flat_map(|c| c.to_lowercase()).collect();
+    let script = match detect_script(&lowercase) {
+        Some(script) => script,
+        None => return Vec::new(),
+    };
+
+    let mut keys: Vec<String> = Vec::new();
+    match script {
+        Script::Cyrillic | Script::Greek => {
+            for alternate in &[false, true] {
+                let romanized = if script == Script::Cyrillic {
+                    romanize_cyrillic(&lowercase, *alternate)
+                } else {
+                    romanize_greek(&lowercase, *alternate)
+                };
+                push_key(&mut keys, romanized);
+            }
+        },
+        Script::Arabic => {
+            let romanized = romanize_arabic(&lowercase);
+            if let Some(without_article) = romanized.strip_prefix("al") {
+                push_key(&mut keys, skeleton(without_article));
+            }
+            push_key(&mut keys, skeleton(&romanized));
+        },
+    }
+    keys
+}
+
+/// The keys to look a Latin-alphabet query word up by: the word itself, lowercased and without
+/// accents, and its consonant skeleton, for finding Arabic words. Words with anything but Latin
+/// letters and digits (apostrophes and hyphens aside) have none.
+pub fn query_keys(word: &str) -> Vec<String> {
+    let mut romanized = String::with_capacity(word.len());
+    for c in word.nfd().filter(|c| !is_combining_mark(*c)).flat_map(|c| c.to_lowercase()) {
+        match c {
+            'a'..='z' | '0'..='9' => romanized.push(c),
+            '\'' | '’' | '-' => (),
+            _ => return Vec::new(),
+        }
+    }
+
+    let mut keys: Vec<String> = Vec::new();
+    let skeleton = skeleton(&romanized);
+    push_key(&mut keys, romanized);
+    push_key(&mut keys, skeleton);
+    keys
+}
+
+fn push_key(keys: &mut Vec<String>, key: String) {
+    if key.trim_start_matches(SKELETON_MARKER).len() >= 2 && !keys.contains(&key) {
+        keys.push(key);
+    }
+}
+
+// which of our scripts the word's letters are all in, if any (digits are allowed in any of them)
+fn detect_script(word: &[char]) -> Option<Script> {
+    let mut script: Option<Script> = None;
+    for c in word {
+        let char_script = match *c {
+            '0'..='9' | '-' => continue,
+            '\u{0400}'..='\u{04FF}' => Script::Cyrillic,
+            '\u{0370}'..='\u{03FF}' | '\u{1F00}'..='\u{1FFF}' => Script::Greek,
+            '\u{0600}'..='\u{06FF}' => Script::Arabic,
+            c if is_combining_mark(c) => continue,
+            _ => return None,
+        };
+        if let Some(script) = script {
+            if script != char_script {
+                return None;
+            }
+        }
+        script = Some(char_script);
+    }
+    script
+}
+
+fn romanize_cyrillic(word: &[char], alternate: bool) -> String {
+    let mut romanized = String::with_capacity(word.len() * 2);
+    for c in word {
+        // look letters with diacritics up as they are first, since some (like "й") are letters in
+        // their own right, and then without their diacritics
+        let entry = CYRILLIC.iter().find(|entry| entry.0 == *c).or_else(|| {
+            let base = c.to_string().nfd().next()?;
+            CYRILLIC.iter().find(|entry| entry.0 == base)
+        });
+        match entry {
+            Some((_, _, Some(other))) if alternate => romanized.push_str(other),
+            Some((_, usual, _)) => romanized.push_str(usual),
+            None => if c.is_ascii_digit() {
+                romanized.push(*c);
+            },
+        }
+    }
+    romanized
+}
+
+fn romanize_greek(word: &[char], alternate: bool) -> String {
+    let letters: String = word.iter().collect::<String>().nfd().filter(|c| !is_combining_mark(*c)).collect();
+    let mut romanized = String::with_capacity(letters.len());
+    let mut rest: &str = &letters;
+    while let Some(c) = rest.chars().next() {
+        match GREEK.iter().find(|entry| rest.starts_with(entry.0)) {
+            Some((from, usual, other)) => {
+                romanized.push_str(match other {
+                    Some(other) if alternate => other,
+                    _ => usual,
+                });
+                rest = &rest[from.len()..];
+            },
+            None => {
+                if c.is_ascii_digit() {
+                    romanized.push(c);
+                }
+                rest = &rest[c.len_utf8()..];
+            },
+        }
+    }
+    romanized
+}
+
+fn romanize_arabic(word: &[char]) -> String {
+    let mut romanized = String::with_capacity(word.len() * 2);
+    for c in word {
+        if let Some((_, to)) = ARABIC.iter().find(|entry| entry.0 == *c) {
+            romanized.push_str(to);
+        } else if c.is_ascii_digit() {
+            romanized.push(*c);
+        }
+    }
+    romanized
+}
+
+// the consonants of a romanized word, without doubled letters, for comparing words whose vowels
+// (or whose doubling) might not have been written down; "w" and "y" go too, since a romanization
+// might spell them as vowels
+fn skeleton(romanized: &str) -> String {
+    let mut skeleton = String::with_capacity(romanized.len() + 1);
+    skeleton.push(SKELETON_MARKER);
+    let mut last: Option<char> = None;
+    for c in romanized.chars() {
+        if "aeiouwy".contains(c) {
+            last = None;
+        } else if Some(c) != last {
+            skeleton.push(c);
+            last = Some(c);
+        }
+    }
+    skeleton
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn transliteration_keys_test() -> () {
+        assert_eq!(transliteration_keys("Москва"), vec!["moskva"]);
+        assert_eq!(transliteration_keys("Хабаровск"), vec!["khabarovsk", "habarovsk"]);
+        assert_eq!(transliteration_keys("Ярославль"), vec!["yaroslavl", "iaroslavl"]);
+        assert_eq!(transliteration_keys("Київ"), vec!["kiyiv"]);
+        assert_eq!(transliteration_keys("Щёлково"), vec!["shchelkovo", "shchyolkovo"]);
+        assert_eq!(transliteration_keys("Αθήνα"), vec!["athina"]);
+        assert_eq!(transliteration_keys("Θεσσαλονίκη"), vec!["thessaloniki"]);
+        assert_eq!(transliteration_keys("Χανιά"), vec!["chania", "hania"]);
+        assert_eq!(transliteration_keys("دمشق"), vec!["~dmshq"]);
+        assert_eq!(transliteration_keys("القاهرة"), vec!["~qhr", "~lqhr"]);
+        assert_eq!(transliteration_keys("Paris"), Vec::<String>::new());
+        assert_eq!(transliteration_keys("Mосква"), Vec::<String>::new());
+    }
+
+    #[test]
+    fn query_keys_test() -> () {
+        assert_eq!(query_keys("Moskva"), vec!["moskva", "~mskv"]);
+        assert_eq!(query_keys("Dimashq"), vec!["dimashq", "~dmshq"]);
+        assert_eq!(query_keys("Makkah"), vec!["makkah", "~mkh"]);
+        assert_eq!(query_keys("al-Qāhira"), vec!["alqahira", "~lqhr"]);
+        assert_eq!(query_keys("Москва"), Vec::<String>::new());
+    }
+}
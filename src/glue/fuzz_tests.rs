@@ -25,8 +25,8 @@ lazy_static! {
     };
     static ref SET: FuzzyPhraseSet = {
         let mut builder = FuzzyPhraseSetBuilder::new(&DIR.path()).unwrap();
-        for phrase in PHRASES.iter() {
-            builder.insert_str(phrase).unwrap();
+        for (i, phrase) in PHRASES.iter().enumerate() {
+            builder.insert_str(phrase, i as u64).unwrap();
         }
         builder.finish().unwrap();
 
@@ -122,6 +122,8 @@ fn glue_fuzztest_windowed_multi_equivalent() {
                 emulated_windowed_match_result.push(FuzzyWindowResult {
                     phrase: result.phrase.clone(),
                     edit_distance: result.edit_distance,
+                    phrase_ids: result.phrase_ids.clone(),
+                    displays: result.displays.clone(),
                     start_position: variant_starts[i],
                     ends_in_prefix: false
                 });
@@ -192,6 +194,8 @@ fn glue_fuzztest_windowed_multi_equivalent_prefix() {
                 emulated_windowed_match_result.push(FuzzyWindowResult {
                     phrase: result.phrase.clone(),
                     edit_distance: result.edit_distance,
+                    phrase_ids: result.phrase_ids.clone(),
+                    displays: result.displays.clone(),
                     start_position: variant_starts[i],
                     ends_in_prefix: variant_eip[i]
                 });
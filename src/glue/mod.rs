@@ -1,66 +1,306 @@
-use std::collections::{BTreeMap, hash_map};
+use std::collections::{BTreeMap, BTreeSet, hash_map};
+use std::borrow::Cow;
 use std::path::{Path, PathBuf};
 use std::error::Error;
-use std::io::{Error as IoError, ErrorKind as IoErrorKind, BufReader, BufWriter};
+use std::io::{Error as IoError, ErrorKind as IoErrorKind, BufReader, BufWriter, Write};
 use std::fs;
 use std::iter;
 use std::cmp::Ord;
 use std::fmt::Debug;
 
 use serde_json;
-use fst::Streamer;
+use serde::{Deserialize, Serialize};
+use rmps::{Deserializer, Serializer};
+use fst::{IntoStreamer, Streamer};
+use itertools::Itertools;
 use rustc_hash::FxHashMap;
 
 use ::prefix::{PrefixSet, PrefixSetBuilder};
 use ::phrase::{PhraseSet, PhraseSetBuilder};
+pub use ::phrase::ContinuationOrder;
 use ::phrase::util::PhraseSetError;
 use ::phrase::query::{QueryPhrase, QueryWord};
-use ::fuzzy::{FuzzyMap, FuzzyMapBuilder};
+use ::fuzzy::{FuzzyMap, FuzzyMapBuilder, AliasMap, AliasMapBuilder};
+use ::fuzzy::phonetic::phonetic_keys;
+use ::fuzzy::transliteration::{transliteration_keys, query_keys};
 use regex;
 
 pub mod unicode_ranges;
 mod util;
+mod scoring;
+pub use self::scoring::{Scorer, DefaultScorer, MatchFeatures, ScoredResult};
+mod segmented;
+pub use self::segmented::SegmentedFuzzyPhraseSet;
+mod packed;
+pub use self::packed::{pack, unpack};
+mod verify;
+pub use self::verify::{verify, VerificationReport, IntegrityProblem};
+mod tokenizer;
+pub use self::tokenizer::{Tokenizer, TokenizerType, UnicodeWordTokenizer, SpaceTokenizer};
+mod normalization;
+pub use self::normalization::{NormalizationOptions, NormalizationForm};
+mod phrase_replacement;
+pub use self::phrase_replacement::PhraseReplacement;
+use self::phrase_replacement::{PhraseReplacementRules, Rewrite};
+mod scoped_replacement;
+pub use self::scoped_replacement::{ReplacementScope, ReplacementPosition};
+use self::scoped_replacement::{ScopedReplacementRule, WordContext, Neighbor};
+
+// phrases inserted with more than one ID store an index into a side list of IDs in the phrase
+// graph instead of the ID itself, and flag that they've done so using the high bit
+static MULTI_FLAG: u64 = 1 << 63;
+static MULTI_MASK: u64 = !(1 << 63);
+
+// a misspelled prefix this short is within an edit or two of the start of a large share of the
+// lexicon, so only look for fuzzy prefixes of partial words at least this long
+const MIN_FUZZY_PREFIX_LENGTH: usize = 4;
+
+#[derive(Serialize, Deserialize)]
+struct SerializablePhraseIdList(Vec<Vec<u64>>);
+
+// phrases that have been deleted since the set was built, as word ID sequences; they stay in the
+// graphs until the set is compacted, but queries skip them
+#[derive(Serialize, Deserialize)]
+struct SerializableTombstones(Vec<Vec<u32>>);
+
+// the display strings phrases were inserted with, as word ID sequences, the phrase ID each
+// display string goes with, and the strings, sorted by word IDs and then phrase IDs; only phrase
+// IDs that were given one are listed
+#[derive(Serialize, Deserialize)]
+struct SerializableDisplayStrings(Vec<(Vec<u32>, u64, String)>);
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 pub struct WordReplacement {
     pub from: String,
-    pub to: String
+    pub to: String,
+    /// Where the replacement applies; it applies everywhere by default.
+    #[serde(default, skip_serializing_if = "ReplacementScope::is_unscoped")]
+    pub scope: ReplacementScope,
+}
+
+impl WordReplacement {
+    /// A replacement that applies everywhere.
+    pub fn new(from: &str, to: &str) -> WordReplacement {
+        WordReplacement { from: from.to_owned(), to: to.to_owned(), scope: ReplacementScope::default() }
+    }
+}
+
+/// One tier of a length-dependent edit distance policy: words of at most `max_length` characters
+/// may be corrected by at most `edit_distance` edits.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct EditDistanceTier {
+    pub max_length: usize,
+    pub edit_distance: u8,
+}
+
+/// Decides how many edits each query word is allowed, before the per-query `max_word_dist` cap
+/// is applied.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub enum EditDistancePolicy {
+    /// Every word is allowed `max_word_dist` edits.
+    Uniform,
+    /// Each word is allowed the edit distance of the first tier whose `max_length` it fits in,
+    /// capped at `max_word_dist`; words longer than every tier are allowed `max_word_dist`. Tiers
+    /// must be sorted by `max_length`.
+    ByLength(Vec<EditDistanceTier>),
+}
+
+impl Default for EditDistancePolicy {
+    fn default() -> EditDistancePolicy {
+        EditDistancePolicy::Uniform
+    }
+}
+
+impl EditDistancePolicy {
+    pub fn edit_distance_for(&self, word: &str, max_word_dist: u8) -> u8 {
+        match self {
+            EditDistancePolicy::Uniform => max_word_dist,
+            EditDistancePolicy::ByLength(tiers) => {
+                let length = word.chars().count();
+                match tiers.iter().find(|tier| length <= tier.max_length) {
+                    Some(tier) => tier.edit_distance.min(max_word_dist),
+                    None => max_word_dist,
+                }
+            }
+        }
+    }
+
+    fn validate(&self, max_edit_distance: u8) -> Result<(), Box<Error>> {
+        if let EditDistancePolicy::ByLength(tiers) = self {
+            if tiers.windows(2).any(|pair| pair[0].max_length >= pair[1].max_length) {
+                return Err("edit distance tiers must be sorted by max_length".into());
+            }
+            if tiers.iter().any(|tier| tier.edit_distance > max_edit_distance) {
+                return Err("edit distance tiers can't exceed the index's max_edit_distance".into());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Settings that control how a `FuzzyPhraseSet` is built and queried. These are recorded in the
+/// index metadata at build time, and read back by `FuzzyPhraseSet::from_path`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct FuzzyPhraseSetBuilderOptions {
+    /// The largest per-word edit distance the index will support at query time. Larger values
+    /// make the fuzzy graph considerably bigger.
+    pub max_edit_distance: u8,
+    /// The names of the Unicode scripts whose words will be spelling-corrected; words containing
+    /// characters from any other script are only ever matched exactly.
+    pub fuzzy_enabled_scripts: Vec<String>,
+    /// Query words with fewer characters than this are only ever matched exactly.
+    pub min_fuzzy_word_length: usize,
+    /// How many edits query words are allowed, depending on their length.
+    pub edit_distance_policy: EditDistancePolicy,
+    /// How the `_str` methods split phrases and queries into words.
+    pub tokenizer: TokenizerType,
+    /// How words are normalized when they're inserted and when they're queried.
+    pub normalization: NormalizationOptions,
+    /// If set, the index also groups its Latin-script words by how they sound, and query words
+    /// are matched to lexicon words that sound like them at this many edits, whatever the real
+    /// number of edits between them; this catches misspellings made by ear, like "Filadelfia",
+    /// that are too far from the real word for the fuzzy graph to find. Like other fuzzy matches,
+    /// these only apply to words eligible for spelling correction, and only when the query allows
+    /// at least this many edits per word.
+    pub phonetic_edit_distance: Option<u8>,
+    /// If set, the index also records romanizations of its Cyrillic, Greek and Arabic words, and
+    /// Latin-alphabet query words are matched to the words they romanize, like "Moskva" to
+    /// "Москва", at this many edits. Again, this only applies when the query allows at least
+    /// this many edits per word.
+    pub transliteration_edit_distance: Option<u8>,
+    /// A language tag for the set's phrases, like "en" or "fr"; word replacements scoped to a
+    /// language only apply to sets with that language.
+    pub language: Option<String>,
+}
+
+impl Default for FuzzyPhraseSetBuilderOptions {
+    fn default() -> FuzzyPhraseSetBuilderOptions {
+        FuzzyPhraseSetBuilderOptions {
+            max_edit_distance: 1,
+            fuzzy_enabled_scripts: vec!["Latin".to_string(), "Greek".to_string(), "Cyrillic".to_string()],
+            min_fuzzy_word_length: 2,
+            edit_distance_policy: EditDistancePolicy::Uniform,
+            tokenizer: TokenizerType::default(),
+            normalization: NormalizationOptions::default(),
+            phonetic_edit_distance: None,
+            transliteration_edit_distance: None,
+            language: None,
+        }
+    }
 }
 
 #[derive(Default, Debug)]
 pub struct FuzzyPhraseSetBuilder {
-    phrases: Vec<Vec<u32>>,
+    // each phrase is stored alongside the caller-supplied ID it was inserted with
+    phrases: Vec<(Vec<u32>, u64)>,
+    // and optionally with a string to show for it, in the same temp IDs as the phrases
+    displays: Vec<(Vec<u32>, u64, String)>,
+    // phrases (and their display strings) taken from another set, which have had the
+    // replacements applied already; see insert_replaced
+    replaced_phrases: Vec<(Vec<u32>, u64)>,
+    replaced_displays: Vec<(Vec<u32>, u64, String)>,
     // use a btreemap for this one so we can read them out in order later
     // we'll only have one copy of each word, in the vector, so the inverse
     // map will map from a pointer to an int
     words_to_tmpids: BTreeMap<String, u32>,
     word_replacements: Vec<WordReplacement>,
-    directory: PathBuf,
+    phrase_replacements: Vec<PhraseReplacement>,
+    // absent for builders that only build in memory
+    directory: Option<PathBuf>,
+    options: FuzzyPhraseSetBuilderOptions,
+}
+
+/// The serialized components of a `FuzzyPhraseSet`, one buffer per file of the on-disk format,
+/// as produced by `FuzzyPhraseSetBuilder::finish_to_bytes` and read by `FuzzyPhraseSet::from_bytes`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FuzzyPhraseSetBytes {
+    pub prefix_fst: Vec<u8>,
+    pub phrase_fst: Vec<u8>,
+    pub phrase_msg: Vec<u8>,
+    pub fuzzy_fst: Vec<u8>,
+    pub fuzzy_msg: Vec<u8>,
+    pub metadata_json: Vec<u8>,
+    /// Only present if any phrases were inserted with display strings.
+    pub display_msg: Option<Vec<u8>>,
+    /// Only present if the set was built with a phonetic edit distance.
+    pub phonetic_fst: Option<Vec<u8>>,
+    /// Only present if the set was built with a transliteration edit distance.
+    pub transliteration_fst: Option<Vec<u8>>,
 }
 
-#[derive(Serialize, Deserialize, Debug, PartialEq, Eq)]
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
 struct FuzzyPhraseSetMetadata {
     index_type: String,
     format_version: u32,
     fuzzy_enabled_scripts: Vec<String>,
     max_edit_distance: u8,
-    word_replacements: Vec<WordReplacement>
+    #[serde(default = "legacy_min_fuzzy_word_length")]
+    min_fuzzy_word_length: usize,
+    #[serde(default)]
+    edit_distance_policy: EditDistancePolicy,
+    #[serde(default = "tokenizer::legacy_tokenizer")]
+    tokenizer: TokenizerType,
+    #[serde(default)]
+    normalization: NormalizationOptions,
+    #[serde(default)]
+    phonetic_edit_distance: Option<u8>,
+    #[serde(default)]
+    transliteration_edit_distance: Option<u8>,
+    #[serde(default)]
+    language: Option<String>,
+    word_replacements: Vec<WordReplacement>,
+    #[serde(default)]
+    phrase_replacements: Vec<PhraseReplacement>,
 }
 
 impl Default for FuzzyPhraseSetMetadata {
     fn default() -> FuzzyPhraseSetMetadata {
+        let options = FuzzyPhraseSetBuilderOptions::default();
         FuzzyPhraseSetMetadata {
             index_type: "fuzzy_phrase_set".to_string(),
-            format_version: 1,
-            fuzzy_enabled_scripts: vec!["Latin".to_string(), "Greek".to_string(), "Cyrillic".to_string()],
-            max_edit_distance: 1,
-            word_replacements: vec![]
+            format_version: 2,
+            fuzzy_enabled_scripts: options.fuzzy_enabled_scripts,
+            max_edit_distance: options.max_edit_distance,
+            min_fuzzy_word_length: options.min_fuzzy_word_length,
+            edit_distance_policy: options.edit_distance_policy,
+            tokenizer: options.tokenizer,
+            normalization: options.normalization,
+            phonetic_edit_distance: options.phonetic_edit_distance,
+            transliteration_edit_distance: options.transliteration_edit_distance,
+            language: options.language,
+            word_replacements: vec![],
+            phrase_replacements: vec![],
         }
     }
 }
 
+impl FuzzyPhraseSetMetadata {
+    // sets from before phrases had IDs (format version 1) can still be loaded; every phrase in
+    // their phrase graphs has ID 0, and they have no list of phrases with several IDs
+    fn is_supported(&self) -> bool {
+        let default = FuzzyPhraseSetMetadata::default();
+        self.index_type == default.index_type && self.format_version >= 1 && self.format_version <= default.format_version
+    }
+
+    fn has_phrase_id_list(&self) -> bool {
+        self.format_version >= 2
+    }
+}
+
+// sets whose metadata predates the minimum fuzzy word length setting only fuzzy-matched words of
+// at least two characters
+fn legacy_min_fuzzy_word_length() -> usize {
+    2
+}
+
 impl FuzzyPhraseSetBuilder {
     pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, Box<Error>> {
+        FuzzyPhraseSetBuilder::new_with_options(path, FuzzyPhraseSetBuilderOptions::default())
+    }
+
+    pub fn new_with_options<P: AsRef<Path>>(path: P, options: FuzzyPhraseSetBuilderOptions) -> Result<Self, Box<Error>> {
+        let mut builder = FuzzyPhraseSetBuilder::memory_with_options(options)?;
         let directory = path.as_ref().to_owned();
 
         if directory.exists() {
@@ -71,7 +311,22 @@ impl FuzzyPhraseSetBuilder {
             fs::create_dir(&directory)?;
         }
 
-        Ok(FuzzyPhraseSetBuilder { directory, ..Default::default() })
+        builder.directory = Some(directory);
+        Ok(builder)
+    }
+
+    /// A builder that doesn't touch the filesystem; finish it with `finish_to_bytes`.
+    pub fn memory() -> Result<Self, Box<Error>> {
+        FuzzyPhraseSetBuilder::memory_with_options(FuzzyPhraseSetBuilderOptions::default())
+    }
+
+    pub fn memory_with_options(options: FuzzyPhraseSetBuilderOptions) -> Result<Self, Box<Error>> {
+        // check the script list up front so we don't find out it's bad after all the inserts
+        util::get_script_regex(&options.fuzzy_enabled_scripts)?;
+        options.edit_distance_policy.validate(options.max_edit_distance)?;
+        options.normalization.validate()?;
+
+        Ok(FuzzyPhraseSetBuilder { options, ..Default::default() })
     }
 
     fn get_or_create_tmpid(&mut self, word: &str) -> u32 {
@@ -82,23 +337,64 @@ impl FuzzyPhraseSetBuilder {
 
     pub fn load_word_replacements(&mut self, word_replacements: Vec<WordReplacement>) -> () {
         for word_replacement in word_replacements {
+            // replacements apply to normalized words, so they have to be normalized themselves
+            let normalization = &self.options.normalization;
+            let normalize_all = |words: &[String]| -> Vec<String> {
+                words.iter().map(|word| normalization.normalize(word).into_owned()).collect()
+            };
+            let word_replacement = WordReplacement {
+                from: normalization.normalize(&word_replacement.from).into_owned(),
+                to: normalization.normalize(&word_replacement.to).into_owned(),
+                scope: ReplacementScope {
+                    preceded_by: normalize_all(&word_replacement.scope.preceded_by),
+                    followed_by: normalize_all(&word_replacement.scope.followed_by),
+                    ..word_replacement.scope
+                },
+            };
             self.get_or_create_tmpid(&word_replacement.from);
             self.get_or_create_tmpid(&word_replacement.to);
             self.word_replacements.push(word_replacement);
         }
     }
 
-    pub fn insert<T: AsRef<str>>(&mut self, phrase: &[T]) -> Result<(), Box<Error>> {
+    /// Add rules that rewrite runs of words in every phrase inserted, and in every query; see
+    /// `PhraseReplacement`. Their words are normalized like any others, and added to the lexicon.
+    pub fn load_phrase_replacements(&mut self, phrase_replacements: Vec<PhraseReplacement>) -> Result<(), Box<Error>> {
+        for phrase_replacement in phrase_replacements {
+            if phrase_replacement.from.is_empty() || phrase_replacement.to.is_empty() {
+                return Err(Box::new(IoError::new(IoErrorKind::InvalidInput, "Phrase replacements need at least one word on each side")));
+            }
+            let normalize = |words: &[String]| -> Vec<String> {
+                words.iter().map(|word| self.options.normalization.normalize(word).into_owned()).collect()
+            };
+            let phrase_replacement = PhraseReplacement {
+                from: normalize(&phrase_replacement.from),
+                to: normalize(&phrase_replacement.to),
+            };
+            for word in phrase_replacement.from.iter().chain(phrase_replacement.to.iter()) {
+                self.get_or_create_tmpid(word);
+            }
+            self.phrase_replacements.push(phrase_replacement);
+        }
+        Ok(())
+    }
+
+    pub fn insert<T: AsRef<str>>(&mut self, phrase: &[T], phrase_id: u64) -> Result<(), Box<Error>> {
         // the strategy here is to take a phrase, look at it word by word, and for any words we've
         // seen before, reuse their temp IDs, otherwise, add new words to our word map and assign them
         // new temp IDs (just autoincrementing in the order we see them) -- later once we've seen all
         // the words we'll renumber them lexicographically
         //
-        // and then we're going to add the actual phrase, represented number-wise, to our phrase list
+        // and then we're going to add the actual phrase, represented number-wise, to our phrase list,
+        // along with the ID the caller wants back when this phrase is matched
+
+        if phrase_id & MULTI_FLAG != 0 {
+            return Err(Box::new(IoError::new(IoErrorKind::InvalidInput, "Phrase IDs must be less than 2^63")));
+        }
 
         let mut tmpid_phrase: Vec<u32> = Vec::with_capacity(phrase.len());
         for word in phrase {
-            let word = word.as_ref();
+            let word = self.options.normalization.normalize(word.as_ref());
             // the fact that this allocation is necessary even if the string is already in the hashmap is a bummer
             // but absent https://github.com/rust-lang/rfcs/pull/1769 , avoiding it requires a huge amount of hoop-jumping
             let string_word = word.to_string();
@@ -106,43 +402,203 @@ impl FuzzyPhraseSetBuilder {
             tmpid_phrase.push(word_id.to_owned());
         }
 
-        self.phrases.push(tmpid_phrase);
+        self.phrases.push((tmpid_phrase, phrase_id));
         Ok(())
     }
 
-    // convenience method that splits the input string on the space character
-    // IT DOES NOT DO PROPER TOKENIZATION; if you need that, use a real tokenizer and call
-    // insert directly
-    pub fn insert_str(&mut self, phrase: &str) -> Result<(), Box<Error>> {
-        let phrase_v: Vec<&str> = phrase.split(' ').collect();
-        self.insert(&phrase_v)
+    // convenience method that splits the input string into words with the set's tokenizer
+    pub fn insert_str(&mut self, phrase: &str, phrase_id: u64) -> Result<(), Box<Error>> {
+        let phrase_v = self.options.tokenizer.tokenize(phrase);
+        self.insert(&phrase_v, phrase_id)
     }
 
-    pub fn finish(mut self) -> Result<(), Box<Error>> {
-        // in the future we could make some of this setable from the outside
-        let mut metadata = FuzzyPhraseSetMetadata::default();
+    /// Insert a phrase along with a string to show for it in match results in place of its
+    /// (normalized, replaced) words, such as the original text it was tokenized from. The display
+    /// string goes with `phrase_id`, so a phrase inserted under several IDs can have a different
+    /// one for each; if the same phrase and ID are given more than one, the first one is kept.
+    pub fn insert_with_display<T: AsRef<str>>(&mut self, phrase: &[T], phrase_id: u64, display: &str) -> Result<(), Box<Error>> {
+        self.insert(phrase, phrase_id)?;
+        let tmpid_phrase = self.phrases.last().expect("just inserted").0.clone();
+        self.displays.push((tmpid_phrase, phrase_id, display.to_owned()));
+        Ok(())
+    }
 
-        // we can go from name -> tmpid
-        // we need to go from tmpid -> id
-        // so build a mapping that does that
-        let mut tmpids_to_ids: Vec<u32> = vec![0; self.words_to_tmpids.len()];
+    // like insert_str, but keeps the input string as the phrase's display string
+    pub fn insert_str_with_display(&mut self, phrase: &str, phrase_id: u64) -> Result<(), Box<Error>> {
+        let phrase_v = self.options.tokenizer.tokenize(phrase);
+        self.insert_with_display(&phrase_v, phrase_id, phrase)
+    }
+
+    // insert a phrase whose words are as a set stores them, already normalized and replaced, so
+    // that the replacements don't apply to it a second time; the replacements can chain (e.g.,
+    // "mt" to "mount" and then "mount vernon" to "mt vernon"), so applying them again could
+    // change it
+    fn insert_replaced<T: AsRef<str>>(&mut self, phrase: &[T], phrase_id: u64, display: Option<&str>) -> Result<(), Box<Error>> {
+        if phrase_id & MULTI_FLAG != 0 {
+            return Err(Box::new(IoError::new(IoErrorKind::InvalidInput, "Phrase IDs must be less than 2^63")));
+        }
+        let tmpid_phrase: Vec<u32> = phrase.iter().map(|word| self.get_or_create_tmpid(word.as_ref())).collect();
+        if let Some(display) = display {
+            self.replaced_displays.push((tmpid_phrase.clone(), phrase_id, display.to_owned()));
+        }
+        self.replaced_phrases.push((tmpid_phrase, phrase_id));
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> Result<(), Box<Error>> {
+        let directory = self.directory.clone().ok_or_else(|| IoError::new(
+            IoErrorKind::InvalidInput,
+            "In-memory builders have no directory to write to; use finish_to_bytes"
+        ))?;
+        let mut metadata = self.metadata();
 
-        let prefix_writer = BufWriter::new(fs::File::create(self.directory.join(Path::new("prefix.fst")))?);
+        let prefix_writer = BufWriter::new(fs::File::create(directory.join(Path::new("prefix.fst")))?);
         let mut prefix_set_builder = PrefixSetBuilder::new(prefix_writer)?;
 
         let mut fuzzy_map_builder = FuzzyMapBuilder::new(
-            self.directory.join(Path::new("fuzzy")),
+            directory.join(Path::new("fuzzy")),
             metadata.max_edit_distance
         )?;
 
-        // this is a regex set to decide whether to index somehing for fuzzy matching
-        let allowed_scripts = &metadata.fuzzy_enabled_scripts.iter().map(
-            |s| unicode_ranges::get_script_by_name(s)
-        ).collect::<Option<Vec<_>>>().ok_or("unknown script")?;
-        let script_regex = regex::Regex::new(
-            &unicode_ranges::get_pattern_for_scripts(&allowed_scripts),
+        let mut phonetic_map_builder = match metadata.phonetic_edit_distance {
+            Some(_) => Some(AliasMapBuilder::new(BufWriter::new(fs::File::create(directory.join(Path::new("phonetic.fst")))?))),
+            None => None,
+        };
+        let mut transliteration_map_builder = match metadata.transliteration_edit_distance {
+            Some(_) => Some(AliasMapBuilder::new(BufWriter::new(fs::File::create(directory.join(Path::new("translit.fst")))?))),
+            None => None,
+        };
+
+        let tmpids_to_ids = self.build_lexicon(
+            &metadata,
+            &mut prefix_set_builder,
+            &mut fuzzy_map_builder,
+            phonetic_map_builder.as_mut(),
+            transliteration_map_builder.as_mut()
+        )?;
+
+        prefix_set_builder.finish()?;
+        fuzzy_map_builder.finish()?;
+        if let Some(phonetic_map_builder) = phonetic_map_builder {
+            phonetic_map_builder.finish()?;
+        }
+        if let Some(transliteration_map_builder) = transliteration_map_builder {
+            transliteration_map_builder.finish()?;
+        }
+
+        self.prepare_phrases(tmpids_to_ids)?;
+
+        let phrase_writer = BufWriter::new(fs::File::create(directory.join(Path::new("phrase.fst")))?);
+        let mut phrase_set_builder = PhraseSetBuilder::new(phrase_writer)?;
+        let phrase_id_list = self.build_phrases(&mut phrase_set_builder)?;
+        phrase_set_builder.finish()?;
+
+        let phrase_id_writer = BufWriter::new(fs::File::create(directory.join(Path::new("phrase.msg")))?);
+        phrase_id_list.serialize(&mut Serializer::new(phrase_id_writer))?;
+
+        if let Some(displays) = self.take_displays() {
+            let display_writer = BufWriter::new(fs::File::create(directory.join(Path::new("display.msg")))?);
+            displays.serialize(&mut Serializer::new(display_writer))?;
+        }
+
+        metadata.word_replacements = self.word_replacements;
+        metadata.phrase_replacements = self.phrase_replacements;
+
+        let metadata_writer = BufWriter::new(fs::File::create(directory.join(Path::new("metadata.json")))?);
+        serde_json::to_writer_pretty(metadata_writer, &metadata)?;
+
+        Ok(())
+    }
+
+    /// Build the set without writing anything to disk, and return its components, ready for
+    /// `FuzzyPhraseSet::from_bytes`.
+    pub fn finish_to_bytes(mut self) -> Result<FuzzyPhraseSetBytes, Box<Error>> {
+        let mut metadata = self.metadata();
+
+        let mut prefix_set_builder = PrefixSetBuilder::memory();
+        let mut fuzzy_map_builder = FuzzyMapBuilder::memory(metadata.max_edit_distance);
+        let mut phonetic_map_builder = metadata.phonetic_edit_distance.map(|_| AliasMapBuilder::memory());
+        let mut transliteration_map_builder = metadata.transliteration_edit_distance.map(|_| AliasMapBuilder::memory());
+
+        let tmpids_to_ids = self.build_lexicon(
+            &metadata,
+            &mut prefix_set_builder,
+            &mut fuzzy_map_builder,
+            phonetic_map_builder.as_mut(),
+            transliteration_map_builder.as_mut()
         )?;
 
+        let prefix_fst = prefix_set_builder.into_inner()?;
+        let (fuzzy_fst, fuzzy_msg) = fuzzy_map_builder.into_inner()?;
+        let phonetic_fst = match phonetic_map_builder {
+            Some(phonetic_map_builder) => Some(phonetic_map_builder.into_inner()?),
+            None => None,
+        };
+        let transliteration_fst = match transliteration_map_builder {
+            Some(transliteration_map_builder) => Some(transliteration_map_builder.into_inner()?),
+            None => None,
+        };
+
+        self.prepare_phrases(tmpids_to_ids)?;
+
+        let mut phrase_set_builder = PhraseSetBuilder::memory();
+        let phrase_id_list = self.build_phrases(&mut phrase_set_builder)?;
+        let phrase_fst = phrase_set_builder.into_inner()?;
+
+        let mut phrase_msg: Vec<u8> = Vec::new();
+        phrase_id_list.serialize(&mut Serializer::new(&mut phrase_msg))?;
+
+        let display_msg = match self.take_displays() {
+            Some(displays) => {
+                let mut display_msg: Vec<u8> = Vec::new();
+                displays.serialize(&mut Serializer::new(&mut display_msg))?;
+                Some(display_msg)
+            },
+            None => None,
+        };
+
+        metadata.word_replacements = self.word_replacements;
+        metadata.phrase_replacements = self.phrase_replacements;
+
+        let metadata_json = serde_json::to_vec_pretty(&metadata)?;
+
+        Ok(FuzzyPhraseSetBytes { prefix_fst, phrase_fst, phrase_msg, fuzzy_fst, fuzzy_msg, metadata_json, display_msg, phonetic_fst, transliteration_fst })
+    }
+
+    // finish and finish_to_bytes share the steps below, and differ only in where the builders
+    // write; the replacements are added to the metadata once the phrases no longer need them
+    fn metadata(&self) -> FuzzyPhraseSetMetadata {
+        FuzzyPhraseSetMetadata {
+            fuzzy_enabled_scripts: self.options.fuzzy_enabled_scripts.clone(),
+            max_edit_distance: self.options.max_edit_distance,
+            min_fuzzy_word_length: self.options.min_fuzzy_word_length,
+            edit_distance_policy: self.options.edit_distance_policy.clone(),
+            tokenizer: self.options.tokenizer,
+            normalization: self.options.normalization.clone(),
+            phonetic_edit_distance: self.options.phonetic_edit_distance,
+            transliteration_edit_distance: self.options.transliteration_edit_distance,
+            language: self.options.language.clone(),
+            ..FuzzyPhraseSetMetadata::default()
+        }
+    }
+
+    // fill the word graphs, and return the mapping from temporary word IDs to final ones
+    fn build_lexicon<P: Write, F: Write, A: Write>(
+        &self,
+        metadata: &FuzzyPhraseSetMetadata,
+        prefix_set_builder: &mut PrefixSetBuilder<P>,
+        fuzzy_map_builder: &mut FuzzyMapBuilder<F>,
+        mut phonetic_map_builder: Option<&mut AliasMapBuilder<A>>,
+        mut transliteration_map_builder: Option<&mut AliasMapBuilder<A>>
+    ) -> Result<Vec<u32>, Box<Error>> {
+        // we can go from name -> tmpid
+        // we need to go from tmpid -> id
+        // so build a mapping that does that
+        let mut tmpids_to_ids: Vec<u32> = vec![0; self.words_to_tmpids.len()];
+
+        // this is a regex set to decide whether to index somehing for fuzzy matching
+        let script_regex = util::get_script_regex(&metadata.fuzzy_enabled_scripts)?;
+
         // words_to_tmpids is a btreemap over word keys,
         // so when we iterate over it, we'll get back words sorted
         // we'll do three things with that:
@@ -158,77 +614,197 @@ impl FuzzyPhraseSetBuilder {
 
             if allowed {
                 fuzzy_map_builder.insert(word, id);
+                if let Some(ref mut phonetic_map_builder) = phonetic_map_builder {
+                    phonetic_map_builder.insert(&phonetic_keys(word), id);
+                }
+            }
+            if let Some(ref mut transliteration_map_builder) = transliteration_map_builder {
+                transliteration_map_builder.insert(&transliteration_keys(word), id);
             }
 
             tmpids_to_ids[*tmpid as usize] = id;
         }
 
-        prefix_set_builder.finish()?;
-        fuzzy_map_builder.finish()?;
+        Ok(tmpids_to_ids)
+    }
+
+    // renumber the phrases with their final word IDs, apply the replacements, and sort them for
+    // the phrase graph
+    fn prepare_phrases(&mut self, mut tmpids_to_ids: Vec<u32>) -> Result<(), Box<Error>> {
+        // phrases that were inserted already replaced only need their words' own real IDs
+        let mut replaced_phrases = ::std::mem::take(&mut self.replaced_phrases);
+        let mut replaced_displays = ::std::mem::take(&mut self.replaced_displays);
+        for word_idx in replaced_phrases.iter_mut().flat_map(|t| t.0.iter_mut()).chain(replaced_displays.iter_mut().flat_map(|t| t.0.iter_mut())) {
+            *word_idx = tmpids_to_ids[*word_idx as usize];
+        }
 
         // for token-replacement words, we want to map the temporary ID to the final ID of the
-        // replacement target, rather than of the replacement source, so number those again
+        // replacement target, rather than of the replacement source, so number those again;
+        // replacements scoped to some positions keep their own IDs, and are applied below
+        let language = self.options.language.as_deref();
         for replacement in &self.word_replacements {
-            tmpids_to_ids[self.words_to_tmpids[&replacement.from] as usize] = tmpids_to_ids[self.words_to_tmpids[&replacement.to] as usize];
+            if scoped_replacement::is_unconditional(replacement, language) {
+                tmpids_to_ids[self.words_to_tmpids[&replacement.from] as usize] = tmpids_to_ids[self.words_to_tmpids[&replacement.to] as usize];
+            }
         }
 
         // next, renumber all of the current phrases with real rather than temp IDs
-        for phrase in self.phrases.iter_mut() {
+        for (phrase, _phrase_id) in self.phrases.iter_mut() {
+            for word_idx in (*phrase).iter_mut() {
+                *word_idx = tmpids_to_ids[*word_idx as usize];
+            }
+        }
+        for (phrase, _phrase_id, _display) in self.displays.iter_mut() {
             for word_idx in (*phrase).iter_mut() {
                 *word_idx = tmpids_to_ids[*word_idx as usize];
             }
         }
 
-        self.phrases.sort();
-
-        let phrase_writer = BufWriter::new(fs::File::create(self.directory.join(Path::new("phrase.fst")))?);
-        let mut phrase_set_builder = PhraseSetBuilder::new(phrase_writer)?;
-
-        for phrase in self.phrases {
-            phrase_set_builder.insert(&phrase)?;
+        // and then rewrite them with the scoped word replacements and the phrase replacements,
+        // which work in the real IDs too
+        let (scoped_replacement_rules, phrase_replacement_rules) = {
+            let words_to_tmpids = &self.words_to_tmpids;
+            let id_of = |word: &str| words_to_tmpids.get(word).map(|tmpid| tmpids_to_ids[*tmpid as usize]);
+            (
+                scoped_replacement::resolve_rules(&self.word_replacements, language, id_of)?,
+                phrase_replacement::resolve_rules(&self.phrase_replacements, id_of)?,
+            )
+        };
+        if !scoped_replacement_rules.is_empty() {
+            for (phrase, _phrase_id) in self.phrases.iter_mut() {
+                *phrase = scoped_replacement::replace_words(phrase, &scoped_replacement_rules);
+            }
+            for (phrase, _phrase_id, _display) in self.displays.iter_mut() {
+                *phrase = scoped_replacement::replace_words(phrase, &scoped_replacement_rules);
+            }
+        }
+        if !phrase_replacement_rules.is_empty() {
+            for (phrase, _phrase_id) in self.phrases.iter_mut() {
+                *phrase = phrase_replacement::replace_phrase(phrase, &phrase_replacement_rules);
+            }
+            for (phrase, _phrase_id, _display) in self.displays.iter_mut() {
+                *phrase = phrase_replacement::replace_phrase(phrase, &phrase_replacement_rules);
+            }
         }
 
-        phrase_set_builder.finish()?;
+        self.phrases.append(&mut replaced_phrases);
+        self.displays.append(&mut replaced_displays);
+        self.phrases.sort();
+        Ok(())
+    }
 
-        for word_replacement in self.word_replacements {
-            metadata.word_replacements.push(word_replacement);
+    // fill the phrase graph, and return the side list of IDs for phrases that have several
+    fn build_phrases<W: Write>(&self, phrase_set_builder: &mut PhraseSetBuilder<W>) -> Result<SerializablePhraseIdList, Box<Error>> {
+        // the same phrase might have been inserted more than once (or two phrases might have
+        // become identical after token replacement); the phrase graph can only hold one value per
+        // phrase, so if there's more than one distinct ID, put them all in a side list and store
+        // a flagged index into that list instead, as the fuzzy map does for its variants
+        let mut phrase_id_list: Vec<Vec<u64>> = Vec::new();
+        for (phrase, group) in &self.phrases.iter().dedup().group_by(|t| &t.0) {
+            let ids = group.map(|t| t.1).collect::<Vec<_>>();
+            let value = if ids.len() == 1 {
+                ids[0]
+            } else {
+                phrase_id_list.push(ids);
+                (phrase_id_list.len() - 1) as u64 | MULTI_FLAG
+            };
+            phrase_set_builder.insert(phrase, value)?;
         }
+        Ok(SerializablePhraseIdList(phrase_id_list))
+    }
 
-        let metadata_writer = BufWriter::new(fs::File::create(self.directory.join(Path::new("metadata.json")))?);
-        serde_json::to_writer_pretty(metadata_writer, &metadata)?;
-
-        Ok(())
+    // the display strings, if there are any, sorted by phrase and phrase ID; the sort is stable,
+    // so deduping keeps the first display string each phrase ID was given
+    fn take_displays(&mut self) -> Option<SerializableDisplayStrings> {
+        if self.displays.is_empty() {
+            return None;
+        }
+        let mut displays = ::std::mem::take(&mut self.displays);
+        displays.sort_by(|a, b| (&a.0, a.1).cmp(&(&b.0, b.1)));
+        displays.dedup_by(|a, b| a.0 == b.0 && a.1 == b.1);
+        Some(SerializableDisplayStrings(displays))
     }
 }
 
 pub struct FuzzyPhraseSet {
     prefix_set: PrefixSet,
     phrase_set: PhraseSet,
+    phrase_id_list: Vec<Vec<u64>>,
     fuzzy_map: FuzzyMap,
     word_list: Vec<String>,
     word_replacement_map: BTreeMap<u32, u32>,
+    scoped_replacement_rules: Vec<ScopedReplacementRule>,
+    phrase_replacement_rules: PhraseReplacementRules,
     script_regex: regex::Regex,
     max_edit_distance: u8,
+    min_fuzzy_word_length: usize,
+    edit_distance_policy: EditDistancePolicy,
+    // if the set has an accent edit distance, a map from each accent-stripped form that differs
+    // from the word it came from to the IDs of the words it came from
+    accent_variants: Option<FxHashMap<String, Vec<u32>>>,
+    // only for sets with a phonetic or transliteration edit distance, respectively
+    phonetic_map: Option<AliasMap>,
+    transliteration_map: Option<AliasMap>,
+    tombstones: BTreeSet<Vec<u32>>,
+    displays: Vec<(Vec<u32>, u64, String)>,
+    // normalized query words to normalized words to look up instead; see load_query_replacements
+    query_replacements: BTreeMap<String, String>,
+    // absent for sets loaded from memory
+    directory: Option<PathBuf>,
+    metadata: FuzzyPhraseSetMetadata,
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize)]
 pub struct FuzzyMatchResult {
     pub edit_distance: u8,
     pub phrase: Vec<String>,
+    // the IDs the phrase was inserted with; empty if the match ends in a prefix, and so doesn't
+    // correspond to a single complete phrase
+    pub phrase_ids: Vec<u64>,
+    // the display strings the phrase was inserted with, each with the ID it goes with, in ID
+    // order; IDs that weren't given one are left out, and like the IDs, only complete phrases
+    // have them
+    pub displays: Vec<(u64, String)>,
 }
 
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord, Clone, Serialize, Deserialize)]
 pub struct FuzzyWindowResult {
     pub edit_distance: u8,
     pub phrase: Vec<String>,
+    pub phrase_ids: Vec<u64>,
+    pub displays: Vec<(u64, String)>,
     pub start_position: usize,
     pub ends_in_prefix: bool,
 }
 
+/// An iterator over the phrases in a `FuzzyPhraseSet`; see `FuzzyPhraseSet::phrases`.
+pub struct Phrases<'a> {
+    set: &'a FuzzyPhraseSet,
+    stream: fst::map::Stream<'a>,
+}
+
+impl<'a> Iterator for Phrases<'a> {
+    type Item = (Vec<&'a str>, Vec<u64>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((key, value)) = self.stream.next() {
+            let word_ids = ::phrase::util::key_to_word_ids(key);
+            if self.set.tombstones.contains(&word_ids) {
+                continue;
+            }
+            let phrase = word_ids.iter().map(|id| self.set.word_list[*id as usize].as_str()).collect();
+            return Some((phrase, self.set.resolve_phrase_ids(value)));
+        }
+        None
+    }
+}
+
 impl<'a, 'b> PartialEq<FuzzyMatchResult> for FuzzyWindowResult {
     fn eq(&self, other: &FuzzyMatchResult) -> bool {
         self.edit_distance == other.edit_distance &&
-        self.phrase == other.phrase
+        self.phrase == other.phrase &&
+        self.phrase_ids == other.phrase_ids &&
+        self.displays == other.displays
     }
 }
 
@@ -238,6 +814,7 @@ impl FuzzyPhraseSet {
         // the prefix graph and phrase graph are each single-file FSTs; the fuzzy graph is multiple files so we
         // pass in a their shared prefix to the fuzzy graph constructor
         // we also have a config file that has version info (with metadata about the index contents to come)
+        // with the mmap feature, the graphs are memory-mapped; without it, they're read into memory
         let directory = path.as_ref();
 
         if !directory.exists() || !directory.is_dir() {
@@ -246,25 +823,128 @@ impl FuzzyPhraseSet {
 
         let metadata_reader = BufReader::new(fs::File::open(directory.join(Path::new("metadata.json")))?);
         let metadata: FuzzyPhraseSetMetadata = serde_json::from_reader(metadata_reader)?;
-        let default = FuzzyPhraseSetMetadata::default();
-        if metadata.index_type != default.index_type || metadata.format_version != default.format_version {
-            return Err(Box::new(IoError::new(IoErrorKind::InvalidData, "Unexpected structure metadata")));
-        }
-
-        let allowed_scripts = &metadata.fuzzy_enabled_scripts.iter().map(
-            |s| unicode_ranges::get_script_by_name(s)
-        ).collect::<Option<Vec<_>>>().ok_or("unknown script")?;
-        let script_regex = regex::Regex::new(
-            &unicode_ranges::get_pattern_for_scripts(&allowed_scripts),
-        )?;
-
-        let max_edit_distance = metadata.max_edit_distance;
 
         let prefix_path = directory.join(Path::new("prefix.fst"));
         if !prefix_path.exists() {
             return Err(Box::new(IoError::new(IoErrorKind::NotFound, "Prefix FST does not exist")));
         }
+        #[cfg(feature = "mmap")]
         let prefix_set = unsafe { PrefixSet::from_path(&prefix_path) }?;
+        #[cfg(not(feature = "mmap"))]
+        let prefix_set = PrefixSet::from_reader(BufReader::new(fs::File::open(&prefix_path)?))?;
+
+        let phrase_path = directory.join(Path::new("phrase.fst"));
+        if !phrase_path.exists() {
+            return Err(Box::new(IoError::new(IoErrorKind::NotFound, "Phrase FST does not exist")));
+        }
+        #[cfg(feature = "mmap")]
+        let phrase_set = unsafe { PhraseSet::from_path(&phrase_path) }?;
+        #[cfg(not(feature = "mmap"))]
+        let phrase_set = PhraseSet::from_reader(BufReader::new(fs::File::open(&phrase_path)?))?;
+
+        let phrase_id_path = directory.join(Path::new("phrase.msg"));
+        let phrase_id_list = if phrase_id_path.exists() {
+            let phrase_id_reader = BufReader::new(fs::File::open(&phrase_id_path)?);
+            Deserialize::deserialize(&mut Deserializer::new(phrase_id_reader))?
+        } else if !metadata.has_phrase_id_list() {
+            SerializablePhraseIdList(Vec::new())
+        } else {
+            return Err(Box::new(IoError::new(IoErrorKind::NotFound, "Phrase ID list does not exist")));
+        };
+
+        let fuzzy_path = directory.join(Path::new("fuzzy"));
+        #[cfg(feature = "mmap")]
+        let fuzzy_map = unsafe { FuzzyMap::from_path(&fuzzy_path) }?;
+        #[cfg(not(feature = "mmap"))]
+        let fuzzy_map = FuzzyMap::from_readers(
+            BufReader::new(fs::File::open(fuzzy_path.with_extension("fst"))?),
+            BufReader::new(fs::File::open(fuzzy_path.with_extension("msg"))?)
+        )?;
+
+        // the tombstone list only exists once something has been deleted
+        let mut tombstones: BTreeSet<Vec<u32>> = BTreeSet::new();
+        let tombstone_path = directory.join(Path::new("deleted.msg"));
+        if tombstone_path.exists() {
+            let tombstone_reader = BufReader::new(fs::File::open(&tombstone_path)?);
+            let tombstone_list: SerializableTombstones = Deserialize::deserialize(&mut Deserializer::new(tombstone_reader))?;
+            tombstones.extend(tombstone_list.0);
+        }
+
+        let mut set = FuzzyPhraseSet::from_parts(metadata, prefix_set, phrase_set, phrase_id_list.0, fuzzy_map)?;
+        set.tombstones = tombstones;
+        let display_path = directory.join(Path::new("display.msg"));
+        if display_path.exists() {
+            set.load_displays(&fs::read(&display_path)?)?;
+        }
+        set.load_alias_maps(
+            read_alias_map(&directory.join(Path::new("phonetic.fst")))?,
+            read_alias_map(&directory.join(Path::new("translit.fst")))?
+        )?;
+        set.directory = Some(directory.to_owned());
+        Ok(set)
+    }
+
+    /// Load a set from buffers held in memory, such as the ones `FuzzyPhraseSetBuilder::finish_to_bytes`
+    /// returns. The set isn't associated with a directory, so deletions aren't persisted anywhere.
+    pub fn from_bytes(bytes: FuzzyPhraseSetBytes) -> Result<Self, Box<Error>> {
+        let metadata: FuzzyPhraseSetMetadata = serde_json::from_slice(&bytes.metadata_json)?;
+        let prefix_set = PrefixSet::from_bytes(bytes.prefix_fst)?;
+        let phrase_set = PhraseSet::from_bytes(bytes.phrase_fst)?;
+        let phrase_id_list: SerializablePhraseIdList = Deserialize::deserialize(&mut Deserializer::new(&bytes.phrase_msg[..]))?;
+        let fuzzy_map = FuzzyMap::from_bytes(bytes.fuzzy_fst, &bytes.fuzzy_msg)?;
+
+        let mut set = FuzzyPhraseSet::from_parts(metadata, prefix_set, phrase_set, phrase_id_list.0, fuzzy_map)?;
+        if let Some(display_msg) = bytes.display_msg {
+            set.load_displays(&display_msg)?;
+        }
+        let phonetic_map = match bytes.phonetic_fst {
+            Some(phonetic_fst) => Some(AliasMap::from_bytes(phonetic_fst)?),
+            None => None,
+        };
+        let transliteration_map = match bytes.transliteration_fst {
+            Some(transliteration_fst) => Some(AliasMap::from_bytes(transliteration_fst)?),
+            None => None,
+        };
+        set.load_alias_maps(phonetic_map, transliteration_map)?;
+        Ok(set)
+    }
+
+    fn load_displays(&mut self, display_bytes: &[u8]) -> Result<(), Box<Error>> {
+        let display_list: SerializableDisplayStrings = Deserialize::deserialize(&mut Deserializer::new(display_bytes))?;
+        self.displays = display_list.0;
+        Ok(())
+    }
+
+    fn load_alias_maps(&mut self, phonetic_map: Option<AliasMap>, transliteration_map: Option<AliasMap>) -> Result<(), Box<Error>> {
+        if self.metadata.phonetic_edit_distance.is_some() && phonetic_map.is_none() {
+            return Err(Box::new(IoError::new(IoErrorKind::NotFound, "Phonetic FST does not exist")));
+        }
+        if self.metadata.transliteration_edit_distance.is_some() && transliteration_map.is_none() {
+            return Err(Box::new(IoError::new(IoErrorKind::NotFound, "Transliteration FST does not exist")));
+        }
+        self.phonetic_map = phonetic_map;
+        self.transliteration_map = transliteration_map;
+        Ok(())
+    }
+
+    // everything that happens after the components are loaded, whichever way they were loaded
+    fn from_parts(
+        metadata: FuzzyPhraseSetMetadata,
+        prefix_set: PrefixSet,
+        phrase_set: PhraseSet,
+        phrase_id_list: Vec<Vec<u64>>,
+        fuzzy_map: FuzzyMap
+    ) -> Result<Self, Box<Error>> {
+        if !metadata.is_supported() {
+            return Err(Box::new(IoError::new(IoErrorKind::InvalidData, "Unexpected structure metadata")));
+        }
+
+        let script_regex = util::get_script_regex(&metadata.fuzzy_enabled_scripts)?;
+
+        let max_edit_distance = metadata.max_edit_distance;
+        let min_fuzzy_word_length = metadata.min_fuzzy_word_length;
+        metadata.edit_distance_policy.validate(max_edit_distance)?;
+        let edit_distance_policy = metadata.edit_distance_policy.clone();
 
         // the fuzzy graph needs to be able to go from ID to actual word
         // one idea was to look this up from the prefix graph, which can do backwards lookups
@@ -279,20 +959,15 @@ impl FuzzyPhraseSet {
             }
         }
 
-        let phrase_path = directory.join(Path::new("phrase.fst"));
-        if !phrase_path.exists() {
-            return Err(Box::new(IoError::new(IoErrorKind::NotFound, "Phrase FST does not exist")));
-        }
-        let phrase_set = unsafe { PhraseSet::from_path(&phrase_path) }?;
-
-        let fuzzy_path = directory.join(Path::new("fuzzy"));
-        let fuzzy_map = unsafe { FuzzyMap::from_path(&fuzzy_path) }?;
-
         // the word replacements in the metadata are string to string, but we want ID to ID for
         // the sake of speed, so use the prefix map to go from the former to the latter and put
-        // put them in a btree
+        // put them in a btree; the ones that are scoped to some positions are kept separately
+        let language = metadata.language.as_deref();
         let mut word_replacement_map: BTreeMap<u32, u32> = BTreeMap::new();
         for word_replacement in &metadata.word_replacements {
+            if !scoped_replacement::is_unconditional(word_replacement, language) {
+                continue;
+            }
             let from = prefix_set.lookup(&word_replacement.from).id()
                 .ok_or_else(|| format!("Substitution from-word {} not in lexicon", word_replacement.from))?
                 .value() as u32;
@@ -301,38 +976,323 @@ impl FuzzyPhraseSet {
                 .value() as u32;
             word_replacement_map.insert(from, to);
         }
+        let (scoped_replacement_rules, phrase_replacement_rules) = {
+            let id_of = |word: &str| {
+                let id = prefix_set.lookup(word).id()?.value() as u32;
+                Some(*word_replacement_map.get(&id).unwrap_or(&id))
+            };
+            (
+                scoped_replacement::resolve_rules(&metadata.word_replacements, language, id_of)?,
+                phrase_replacement::resolve_rules(&metadata.phrase_replacements, id_of)?,
+            )
+        };
+
+        metadata.normalization.validate()?;
+        let accent_variants = metadata.normalization.accent_edit_distance.map(|_| {
+            let mut accent_variants: FxHashMap<String, Vec<u32>> = FxHashMap::default();
+            for (id, word) in word_list.iter().enumerate() {
+                let stripped = normalization::remove_accents(word);
+                if stripped != *word {
+                    accent_variants.entry(stripped).or_default().push(id as u32);
+                }
+            }
+            accent_variants
+        });
 
         Ok(FuzzyPhraseSet {
-            prefix_set, phrase_set, fuzzy_map, word_list, word_replacement_map, script_regex, max_edit_distance
+            prefix_set, phrase_set, phrase_id_list, fuzzy_map, word_list, word_replacement_map, scoped_replacement_rules, phrase_replacement_rules, script_regex, max_edit_distance,
+            min_fuzzy_word_length, edit_distance_policy, accent_variants, phonetic_map: None, transliteration_map: None, tombstones: BTreeSet::new(), displays: Vec::new(), query_replacements: BTreeMap::new(), directory: None, metadata
         })
     }
 
-    pub fn can_fuzzy_match(&self, word: &str) -> bool {
-        util::can_fuzzy_match(word, &self.script_regex)
+    // the word IDs a phrase would be stored under, after word and phrase replacements, if all its
+    // words are in the lexicon
+    fn get_word_ids<T: AsRef<str>>(&self, phrase: &[T]) -> Option<Vec<u32>> {
+        let mut word_ids: Vec<u32> = Vec::with_capacity(phrase.len());
+        for word in phrase {
+            let id = self.prefix_set.lookup(self.query_word(word.as_ref()).as_ref()).id()?.value() as u32;
+            word_ids.push(*self.word_replacement_map.get(&id).unwrap_or(&id));
+        }
+        if !self.scoped_replacement_rules.is_empty() {
+            word_ids = scoped_replacement::replace_words(&word_ids, &self.scoped_replacement_rules);
+        }
+        if !self.phrase_replacement_rules.is_empty() {
+            word_ids = phrase_replacement::replace_phrase(&word_ids, &self.phrase_replacement_rules);
+        }
+        Some(word_ids)
     }
 
-    pub fn contains<T: AsRef<str>>(&self, phrase: &[T]) -> Result<bool, Box<Error>> {
-        // strategy: get each word's ID from the prefix graph (or return false if any are missing)
-        // and then look up that ID sequence in the phrase graph
-        let mut id_phrase: Vec<QueryWord> = Vec::with_capacity(phrase.len());
-        for word in phrase {
-            match self.prefix_set.lookup(word.as_ref()).id() {
-                Some(word_id) => {
-                    let id = word_id.value() as u32;
-                    let maybe_replaced = *self.word_replacement_map.get(&id).unwrap_or(&id);
-                    id_phrase.push(QueryWord::new_full(maybe_replaced, 0))
-                },
-                None => { return Ok(false) }
+    /// Mark a phrase as deleted, so that no query will match it anymore. The deletion is recorded
+    /// in the set's directory (if it has one) right away, but the phrase isn't physically removed
+    /// until the set is compacted. Returns false if the phrase wasn't in the set (or was already
+    /// deleted). If recording the deletion fails, the phrase isn't deleted.
+    pub fn delete<T: AsRef<str>>(&mut self, phrase: &[T]) -> Result<bool, Box<Error>> {
+        let word_ids = match self.get_word_ids(phrase) {
+            Some(word_ids) => word_ids,
+            None => return Ok(false),
+        };
+        {
+            let id_phrase: Vec<QueryWord> = word_ids.iter().map(|id| QueryWord::new_full(*id, 0)).collect();
+            if !self.phrase_set.contains(QueryPhrase::new(&id_phrase)?)? {
+                return Ok(false);
             }
         }
-        Ok(self.phrase_set.contains(QueryPhrase::new(&id_phrase)?)?)
+        if self.tombstones.contains(&word_ids) {
+            return Ok(false);
+        }
+
+        if let Some(ref directory) = self.directory {
+            let tombstones: Vec<Vec<u32>> = self.tombstones.iter().cloned().merge(iter::once(word_ids.clone())).collect();
+            write_tombstones(directory, SerializableTombstones(tombstones))?;
+        }
+        self.tombstones.insert(word_ids);
+        Ok(true)
     }
 
-    // convenience method that splits the input string on the space character
-    // IT DOES NOT DO PROPER TOKENIZATION; if you need that, use a real tokenizer and call
-    // contains directly
-    pub fn contains_str(&self, phrase: &str) -> Result<bool, Box<Error>> {
-        let phrase_v: Vec<&str> = phrase.split(' ').collect();
+    // convenience method that splits the input string into words with the set's tokenizer
+    pub fn delete_str(&mut self, phrase: &str) -> Result<bool, Box<Error>> {
+        let phrase_v = self.tokenize(phrase);
+        self.delete(&phrase_v)
+    }
+
+    /// The number of phrases that have been deleted but not yet compacted away.
+    pub fn deleted_len(&self) -> usize {
+        self.tombstones.len()
+    }
+
+    /// The lexicon, in word ID order (which is also alphabetical order).
+    pub fn words(&self) -> &[String] {
+        &self.word_list
+    }
+
+    /// Every phrase in the set that hasn't been deleted, in word ID order, along with the IDs it
+    /// was inserted with. Phrases are as stored, i.e., after word replacement.
+    pub fn phrases(&self) -> Phrases {
+        Phrases { set: self, stream: (&self.phrase_set).into_stream() }
+    }
+
+    /// Split `text` into words the way this set's `_str` methods do, with the tokenizer it was
+    /// built with.
+    pub fn tokenize<'a>(&self, text: &'a str) -> Vec<&'a str> {
+        self.metadata.tokenizer.tokenize(text)
+    }
+
+    /// The display strings a phrase was inserted with, each with the phrase ID it goes with, in
+    /// ID order. IDs the phrase was inserted under without one aren't listed.
+    pub fn displays<T: AsRef<str>>(&self, phrase: &[T]) -> Vec<(u64, &str)> {
+        match self.get_word_ids(phrase) {
+            Some(word_ids) => self.get_displays(&word_ids),
+            None => Vec::new(),
+        }
+    }
+
+    fn get_displays(&self, word_ids: &[u32]) -> Vec<(u64, &str)> {
+        // the list is sorted by phrase and then ID, so a phrase's entries are all together
+        let start = match self.displays.binary_search_by(|(ids, phrase_id, _)| (ids.as_slice(), *phrase_id).cmp(&(word_ids, 0))) {
+            Ok(i) | Err(i) => i,
+        };
+        self.displays[start..].iter()
+            .take_while(|(ids, _, _)| ids.as_slice() == word_ids)
+            .map(|(_, phrase_id, display)| (*phrase_id, display.as_str()))
+            .collect()
+    }
+
+    // the display strings for a match, which only complete phrases can have
+    fn get_phrase_displays(&self, phrase_p: &[QueryWord]) -> Vec<(u64, String)> {
+        if self.displays.is_empty() {
+            return Vec::new();
+        }
+        let word_ids: Option<Vec<u32>> = phrase_p.iter().map(|qw| match qw {
+            QueryWord::Full { id, .. } => Some(*id),
+            _ => None,
+        }).collect();
+        match word_ids {
+            Some(word_ids) => self.owned_displays(&word_ids),
+            None => Vec::new(),
+        }
+    }
+
+    fn owned_displays(&self, word_ids: &[u32]) -> Vec<(u64, String)> {
+        self.get_displays(word_ids).into_iter().map(|(phrase_id, display)| (phrase_id, display.to_owned())).collect()
+    }
+
+    /// Normalize a word the way this set normalizes phrases and queries.
+    pub fn normalize<'a>(&self, word: &'a str) -> Cow<'a, str> {
+        self.metadata.normalization.normalize(word)
+    }
+
+    // a query word as it's looked up: normalized, and then swapped for its query replacement, if
+    // it has one
+    fn query_word<'a>(&self, word: &'a str) -> Cow<'a, str> {
+        let normalized = self.normalize(word);
+        match self.query_replacements.get(normalized.as_ref()) {
+            Some(replacement) => Cow::Owned(replacement.clone()),
+            None => normalized,
+        }
+    }
+
+    /// Swap query words for others before they're looked up, e.g., to roll out a new list of
+    /// abbreviations without rebuilding the set. Unlike the word replacements the set was built
+    /// with, these only apply to queries, so neither word has to be in the lexicon: the `from`
+    /// word is simply looked up (and spelling-corrected) as the `to` word, and then goes through
+    /// the set's own replacements like any other. The last word of a prefix query, which might
+    /// not be finished, is looked up as itself as well. Both words are normalized first. Replacements
+    /// can only be scoped to a language, and are ignored unless it's the set's; loading one for a
+    /// word that already has one replaces it. They last as long as this `FuzzyPhraseSet`, and
+    /// aren't saved with it.
+    pub fn load_query_replacements(&mut self, word_replacements: Vec<WordReplacement>) -> Result<(), Box<Error>> {
+        if word_replacements.iter().any(|word_replacement| word_replacement.scope.has_conditions()) {
+            return Err(Box::new(IoError::new(IoErrorKind::InvalidInput, "Query replacements can only be scoped to a language")));
+        }
+        for word_replacement in word_replacements {
+            if !word_replacement.scope.applies_to_language(self.metadata.language.as_deref()) {
+                continue;
+            }
+            let from = self.normalize(&word_replacement.from).into_owned();
+            let to = self.normalize(&word_replacement.to).into_owned();
+            self.query_replacements.insert(from, to);
+        }
+        Ok(())
+    }
+
+    /// Stop applying any query replacements loaded with `load_query_replacements`.
+    pub fn clear_query_replacements(&mut self) -> () {
+        self.query_replacements.clear();
+    }
+
+    /// The options the set was built with.
+    pub fn options(&self) -> FuzzyPhraseSetBuilderOptions {
+        FuzzyPhraseSetBuilderOptions {
+            max_edit_distance: self.metadata.max_edit_distance,
+            fuzzy_enabled_scripts: self.metadata.fuzzy_enabled_scripts.clone(),
+            min_fuzzy_word_length: self.metadata.min_fuzzy_word_length,
+            edit_distance_policy: self.metadata.edit_distance_policy.clone(),
+            tokenizer: self.metadata.tokenizer,
+            normalization: self.metadata.normalization.clone(),
+            phonetic_edit_distance: self.metadata.phonetic_edit_distance,
+            transliteration_edit_distance: self.metadata.transliteration_edit_distance,
+            language: self.metadata.language.clone(),
+        }
+    }
+
+    pub fn word_replacements(&self) -> &[WordReplacement] {
+        &self.metadata.word_replacements
+    }
+
+    pub fn phrase_replacements(&self) -> &[PhraseReplacement] {
+        &self.metadata.phrase_replacements
+    }
+
+    // the ways the phrase replacements could apply to a query with these word possibilities
+    fn rewrite_possibilities(&self, word_possibilities: Vec<Vec<QueryWord>>) -> Vec<Rewrite> {
+        phrase_replacement::rewrites(word_possibilities, &self.phrase_replacement_rules)
+    }
+
+    // the neighbors of the `i`th word of a query, which decide the scoped replacements that apply
+    // to it; unless `certain`, a match might start or end at the word instead of including them,
+    // and if the query `ends_in_prefix`, its last word could be the start of any word, and could
+    // have any word after it
+    fn word_context<T: AsRef<str>>(&self, phrase: &[T], i: usize, edit_distance: u8, certain: bool, ends_in_prefix: bool) -> WordContext {
+        if self.scoped_replacement_rules.is_empty() {
+            return WordContext::default();
+        }
+        let neighbor = |j: Option<usize>| self.neighbor(phrase, j, edit_distance, certain, ends_in_prefix);
+        WordContext {
+            previous: neighbor(i.checked_sub(1)),
+            next: if ends_in_prefix && i + 1 == phrase.len() { Neighbor::Unknown } else { neighbor(Some(i + 1)) },
+        }
+    }
+
+    fn neighbor<T: AsRef<str>>(&self, phrase: &[T], i: Option<usize>, edit_distance: u8, certain: bool, ends_in_prefix: bool) -> Neighbor {
+        let (i, word) = match i.and_then(|i| phrase.get(i).map(|word| (i, self.query_word(word.as_ref())))) {
+            Some(found) => found,
+            None => return Neighbor::Nothing,
+        };
+        // a word that might be misspelled or incomplete could stand for some other word, so it's
+        // left unknown
+        let id = if (ends_in_prefix && i + 1 == phrase.len()) || self.should_fuzzy_match(&word, self.edit_distance_policy.edit_distance_for(&word, edit_distance)) {
+            None
+        } else {
+            self.prefix_set.lookup(&*word).id().map(|word_id| {
+                let id = word_id.value() as u32;
+                *self.word_replacement_map.get(&id).unwrap_or(&id)
+            })
+        };
+        if certain {
+            Neighbor::Word(id)
+        } else {
+            Neighbor::MaybeWord(id)
+        }
+    }
+
+    /// Write a copy of this set to `path` without any of its deleted phrases, or any words that
+    /// only they used. `path` must not be this set's own directory, which is still in use while
+    /// the copy is written.
+    pub fn compact<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<Error>> {
+        rebuild(&[self], path)
+    }
+
+    // whether a match still corresponds to at least one phrase that hasn't been deleted; a match
+    // that's only the beginning of a phrase (`as_prefix`) qualifies if any of its completions do
+    fn is_live(&self, phrase_p: &[QueryWord], as_prefix: bool) -> Result<bool, Box<Error>> {
+        if self.tombstones.is_empty() {
+            return Ok(true);
+        }
+        let query_phrase = QueryPhrase::new(&phrase_p)?;
+        if !as_prefix && !query_phrase.has_prefix {
+            let word_ids: Vec<u32> = phrase_p.iter().map(|qw| match qw {
+                QueryWord::Full { id, .. } => *id,
+                _ => panic!("prefixes not allowed"),
+            }).collect();
+            return Ok(!self.tombstones.contains(&word_ids));
+        }
+        let completions = self.phrase_set.completions_filtered(query_phrase, 1, &|word_ids| self.is_live_phrase(word_ids))?;
+        Ok(!completions.is_empty())
+    }
+
+    // the phrase graph filter that skips deleted phrases
+    fn is_live_phrase(&self, word_ids: &[u32]) -> bool {
+        self.tombstones.is_empty() || !self.tombstones.contains(word_ids)
+    }
+
+    pub fn can_fuzzy_match(&self, word: &str) -> bool {
+        util::can_fuzzy_match(word, &self.script_regex)
+    }
+
+    // check if we actually want to fuzzy-match, if the word is made of the right kind of
+    // characters and if it's long enough
+    #[inline(always)]
+    fn should_fuzzy_match(&self, word: &str, edit_distance: u8) -> bool {
+        edit_distance > 0 &&
+            self.can_fuzzy_match(word) &&
+            word.chars().nth(self.min_fuzzy_word_length.saturating_sub(1)).is_some()
+    }
+
+    // a partial word is held to a stricter standard than a whole one before we look for
+    // misspelled prefixes: it has to be longer, and the matched words have to start with the same
+    // character as what was typed
+    fn should_fuzzy_match_prefix(&self, word: &str, edit_distance: u8) -> bool {
+        self.should_fuzzy_match(word, edit_distance) &&
+            word.chars().nth(MIN_FUZZY_PREFIX_LENGTH - 1).is_some()
+    }
+
+    pub fn contains<T: AsRef<str>>(&self, phrase: &[T]) -> Result<bool, Box<Error>> {
+        // strategy: get each word's ID from the prefix graph (or return false if any are missing)
+        // and then look up that ID sequence in the phrase graph, unless it's been deleted
+        let word_ids = match self.get_word_ids(phrase) {
+            Some(word_ids) => word_ids,
+            None => return Ok(false),
+        };
+        if self.tombstones.contains(&word_ids) {
+            return Ok(false);
+        }
+        let id_phrase: Vec<QueryWord> = word_ids.iter().map(|id| QueryWord::new_full(*id, 0)).collect();
+        Ok(self.phrase_set.contains(QueryPhrase::new(&id_phrase)?)?)
+    }
+
+    // convenience method that splits the input string into words with the set's tokenizer
+    pub fn contains_str(&self, phrase: &str) -> Result<bool, Box<Error>> {
+        let phrase_v = self.tokenize(phrase);
         self.contains(&phrase_v)
     }
 
@@ -348,131 +1308,352 @@ impl FuzzyPhraseSet {
         }
 
         let last_idx = phrase.len() - 1;
-        for word in phrase[..last_idx].iter() {
-            match self.prefix_set.lookup(word.as_ref()).id() {
+        for (i, word) in phrase[..last_idx].iter().enumerate() {
+            match self.prefix_set.lookup(self.query_word(word.as_ref()).as_ref()).id() {
                 Some(word_id) => {
                     let id = word_id.value() as u32;
                     let maybe_replaced = *self.word_replacement_map.get(&id).unwrap_or(&id);
-                    word_possibilities.push(vec![QueryWord::new_full(maybe_replaced, 0)])
+                    let mut variants = vec![QueryWord::new_full(maybe_replaced, 0)];
+                    if !self.scoped_replacement_rules.is_empty() {
+                        let context = self.word_context(phrase, i, 0, true, true);
+                        scoped_replacement::replace_variants(&mut variants, &context, &self.scoped_replacement_rules);
+                    }
+                    word_possibilities.push(variants)
                 },
                 None => { return Ok(false) }
             }
         }
-        match self.get_terminal_word_possibilities(phrase[last_idx].as_ref(), 0)? {
+        match self.get_terminal_word_possibilities(phrase[last_idx].as_ref(), 0, self.word_context(phrase, last_idx, 0, true, true))? {
             Some(possibilities) => word_possibilities.push(possibilities),
             None => return Ok(false),
         }
 
-        let phrase_matches = self.phrase_set.match_combinations_as_prefixes(&word_possibilities, 0)?;
-        Ok(phrase_matches.len() > 0)
+        for rewrite in self.rewrite_possibilities(word_possibilities) {
+            let phrase_matches = self.phrase_set.match_combinations_as_prefixes(&rewrite.word_possibilities, 0)?;
+            for phrase_p in &phrase_matches {
+                if self.is_live(phrase_p, true)? {
+                    return Ok(true);
+                }
+            }
+        }
+        Ok(false)
     }
 
-    // convenience method that splits the input string on the space character
-    // IT DOES NOT DO PROPER TOKENIZATION; if you need that, use a real tokenizer and call
-    // contains_prefix directly
+    // convenience method that splits the input string into words with the set's tokenizer
     pub fn contains_prefix_str(&self, phrase: &str) -> Result<bool, Box<Error>> {
-        let phrase_v: Vec<&str> = phrase.split(' ').collect();
+        let phrase_v = self.tokenize(phrase);
         self.contains_prefix(&phrase_v)
     }
 
+    fn get_phrase_ids(&self, phrase_p: &[QueryWord]) -> Result<Vec<u64>, Box<Error>> {
+        // matches that end in a prefix might correspond to many phrases, so they don't get IDs;
+        // otherwise, look up the value stored alongside the phrase in the phrase graph (there
+        // won't be one for full-word matches that are only the beginning of a longer phrase)
+        // and expand it if it points into the multi-ID list
+        let query_phrase = QueryPhrase::new(&phrase_p)?;
+        if query_phrase.has_prefix {
+            return Ok(Vec::new());
+        }
+        match self.phrase_set.get(query_phrase)? {
+            None => Ok(Vec::new()),
+            Some(value) => Ok(self.resolve_phrase_ids(value)),
+        }
+    }
+
+    fn resolve_phrase_ids(&self, value: u64) -> Vec<u64> {
+        if value & MULTI_FLAG != 0 {
+            self.phrase_id_list[(value & MULTI_MASK) as usize].clone()
+        } else {
+            vec![value]
+        }
+    }
+
+    // `typed` is the portion of the query that lines up with the match, word for word
+    fn get_match_features<T: AsRef<str>>(&self, phrase_p: &[QueryWord], typed: &[T]) -> MatchFeatures {
+        let (ends_in_prefix, prefix_completion_length) = match phrase_p.last() {
+            Some(QueryWord::Prefix { id_range, .. }) => {
+                let typed_length = typed.last().map_or(0, |t| t.as_ref().chars().count());
+                let completion_length = self.word_list[id_range.0 as usize].chars().count();
+                (true, completion_length.saturating_sub(typed_length))
+            },
+            _ => (false, 0),
+        };
+        MatchFeatures {
+            word_edit_distances: phrase_p.iter().map(|qw| qw.edit_distance()).collect(),
+            ends_in_prefix,
+            prefix_completion_length,
+        }
+    }
+
     #[inline(always)]
-    fn get_nonterminal_word_possibilities(&self, word: &str, edit_distance: u8) -> Result<Option<Vec<QueryWord>>, Box<Error>> {
-        // check if we actually want to fuzzy-match, if the word is made of the right kind of characters
-        // and if it's more than one char long
-        if edit_distance > 0 && self.can_fuzzy_match(word) && word.chars().nth(1).is_some() {
+    fn get_nonterminal_word_possibilities(&self, word: &str, edit_distance: u8, context: WordContext) -> Result<Option<Vec<QueryWord>>, Box<Error>> {
+        let normalized = self.query_word(word);
+        let word: &str = &normalized;
+        let edit_distance = self.edit_distance_policy.edit_distance_for(word, edit_distance);
+        let mut variants: Vec<QueryWord> = Vec::new();
+        if self.should_fuzzy_match(word, edit_distance) {
             let fuzzy_results = self.fuzzy_map.lookup(&word, edit_distance, |id| &self.word_list[id as usize])?;
-            if fuzzy_results.len() == 0 {
-                Ok(None)
-            } else {
-                let mut variants: Vec<QueryWord> = Vec::with_capacity(fuzzy_results.len());
-                for result in fuzzy_results {
-                    let maybe_replaced = *self.word_replacement_map.get(&result.id).unwrap_or(&result.id);
-                    let already = variants.iter().any(|&x| match x {
-                        QueryWord::Full { id, .. } => id == maybe_replaced,
-                        _ => false
-                    });
-                    if !already {
-                        variants.push(QueryWord::new_full(maybe_replaced, result.edit_distance));
-                    }
+            for result in fuzzy_results {
+                let maybe_replaced = *self.word_replacement_map.get(&result.id).unwrap_or(&result.id);
+                let already = variants.iter().any(|&x| match x {
+                    QueryWord::Full { id, .. } => id == maybe_replaced,
+                    _ => false
+                });
+                if !already {
+                    variants.push(QueryWord::new_full(maybe_replaced, result.edit_distance));
                 }
-                Ok(Some(variants))
             }
+        } else if let Some(word_id) = self.prefix_set.lookup(&word).id() {
+            let id = word_id.value() as u32;
+            let maybe_replaced = *self.word_replacement_map.get(&id).unwrap_or(&id);
+            variants.push(QueryWord::new_full(maybe_replaced, 0));
+        }
+        self.push_accent_variants(word, edit_distance, &mut variants);
+        self.push_phonetic_variants(word, edit_distance, &mut variants);
+        self.push_transliteration_variants(word, edit_distance, &mut variants);
+        if !self.scoped_replacement_rules.is_empty() {
+            scoped_replacement::replace_variants(&mut variants, &context, &self.scoped_replacement_rules);
+        }
+
+        if variants.len() == 0 {
+            Ok(None)
         } else {
-            match self.prefix_set.lookup(&word).id() {
-                Some(word_id) => {
-                    let id = word_id.value() as u32;
-                    let maybe_replaced = *self.word_replacement_map.get(&id).unwrap_or(&id);
-                    Ok(Some(vec![QueryWord::new_full(maybe_replaced, 0)])) },
-                None => { Ok(None) }
-            }
+            Ok(Some(variants))
         }
     }
 
     #[inline(always)]
-    fn get_terminal_word_possibilities(&self, word: &str, edit_distance: u8) -> Result<Option<Vec<QueryWord>>, Box<Error>> {
+    fn get_terminal_word_possibilities(&self, word: &str, edit_distance: u8, context: WordContext) -> Result<Option<Vec<QueryWord>>, Box<Error>> {
         // last word: try both prefix and, if eligible, fuzzy lookup, and return nothing if both fail
+        let normalized = self.normalize(word);
         let mut last_variants: Vec<QueryWord> = Vec::new();
 
+        // the last word might not be finished, so a query replacement for it doesn't stand in for
+        // it: "str" could be on its way to "strand" as well as short for "street"
+        if let Some(replacement) = self.query_replacements.get(normalized.as_ref()) {
+            self.push_terminal_variants(replacement, edit_distance, &mut last_variants)?;
+            self.push_terminal_variants(&normalized, edit_distance, &mut last_variants)?;
+            last_variants.sort_by_key(|x| x.edit_distance());
+        } else {
+            self.push_terminal_variants(&normalized, edit_distance, &mut last_variants)?;
+        }
+
+        if !self.scoped_replacement_rules.is_empty() {
+            scoped_replacement::replace_variants(&mut last_variants, &context, &self.scoped_replacement_rules);
+        }
+        if last_variants.len() > 0 {
+            Ok(Some(last_variants))
+        } else {
+            Ok(None)
+        }
+    }
+
+    // the prefix, fuzzy, and other variants of a (normalized) last word, leaving out any words
+    // that are among the variants already
+    fn push_terminal_variants(&self, word: &str, edit_distance: u8, last_variants: &mut Vec<QueryWord>) -> Result<(), Box<Error>> {
+        let edit_distance = self.edit_distance_policy.edit_distance_for(word, edit_distance);
+
         let lookup = self.prefix_set.lookup(word);
         if let Some((word_id_start, word_id_end)) = lookup.range() {
             let found_range = (word_id_start.value() as u32, word_id_end.value() as u32);
-            let num_terminations = (found_range.1 - found_range.0 + 1) as usize;
-            let replacements: Vec<u32> = self.word_replacement_map
-                .range(found_range.0..=found_range.1)
-                // don't bother emitting a replacement if it would be covered by the prefix anyway
-                .filter_map(|(_key, &target)|
-                    if target < found_range.0 || target > found_range.1 {
-                        Some(target)
-                    } else {
-                        None
-                    }
-                ).collect();
+            let taken = self.taken_ids(found_range, last_variants, &[]);
+            self.push_prefix_variants_around(found_range, taken, lookup.has_continuations(), 0, last_variants);
+        }
 
-            // if everything within our range will get token-replaced, don't emit the unreplaced word
-            if num_terminations != replacements.len() {
-                // if there's just one word and it's final, emit a full_word
-                if lookup.has_continuations() {
-                    last_variants.push(QueryWord::new_prefix((found_range.0, found_range.1)));
-                } else {
-                    last_variants.push(QueryWord::new_full(found_range.0, 0));
-                }
-            }
-            for replacement in replacements {
-                let already = last_variants.iter().any(|&x| match x {
-                    QueryWord::Full { id, .. } => id == replacement,
-                    _ => false
-                });
-                if !already {
-                    last_variants.push(QueryWord::new_full(replacement, 0));
+        if self.should_fuzzy_match(word, edit_distance) {
+            let last_fuzzy_results = self.fuzzy_map.lookup(word, edit_distance, |id| &self.word_list[id as usize])?;
+
+            // the word might be both incomplete and misspelled, so also look for prefixes within
+            // the edit distance; the zero-distance range is the exact lookup we already did
+            let mut fuzzy_prefixes = if self.should_fuzzy_match_prefix(word, edit_distance) {
+                self.prefix_set.fuzzy_lookup(word, edit_distance)
+            } else {
+                Vec::new()
+            };
+            fuzzy_prefixes.sort_by_key(|result| result.edit_distance);
+            let first_char = word.chars().next();
+            for result in fuzzy_prefixes {
+                // at this query length no range sits directly below the root, so the words in
+                // each one share a first character
+                if result.edit_distance == 0 ||
+                    self.word_list[result.id_range.0.value() as usize].chars().next() != first_char {
+                    continue;
                 }
+                // carve out any words we've already emitted more cheaply (i.e., replacement targets),
+                // and whole-word typos that are no more expensive, so that a range covering only
+                // those doesn't stand in for the word itself
+                let found_range = (result.id_range.0.value() as u32, result.id_range.1.value() as u32);
+                let typos: Vec<u32> = last_fuzzy_results.iter()
+                    .filter(|x| x.edit_distance <= result.edit_distance)
+                    .map(|x| x.id)
+                    .collect();
+                let taken = self.taken_ids(found_range, last_variants, &typos);
+                self.push_prefix_variants_around(found_range, taken, result.has_continuations, result.edit_distance, last_variants);
             }
-        }
 
-        // check if we actually want to fuzzy-match, if the word is made of the right kind of characters
-        // and if it's more than one char long
-        if edit_distance > 0 && self.can_fuzzy_match(word) && word.chars().nth(1).is_some() {
-            let last_fuzzy_results = self.fuzzy_map.lookup(word, edit_distance, |id| &self.word_list[id as usize])?;
             for result in last_fuzzy_results {
                 let maybe_replaced = *self.word_replacement_map.get(&result.id).unwrap_or(&result.id);
                 // skip adding this entry if it's in an already-identified range, or is a token
                 // replacement result; otherwise insert it into the set and push it to the output list
-                let already = last_variants.iter().any(|&x| match x {
-                    QueryWord::Full { id, .. } => id == maybe_replaced,
-                    QueryWord::Prefix { id_range, .. } => maybe_replaced >= id_range.0 && maybe_replaced <= id_range.1 
-                });
+                let already = last_variants.iter().any(|x| x.contains_id(maybe_replaced));
                 if !already {
                     last_variants.push(QueryWord::new_full(maybe_replaced, result.edit_distance));
                 }
             }
+
+            // the phrase graph searches stop at the first variant that's over budget, so cheaper
+            // variants need to come first
+            last_variants.sort_by_key(|x| x.edit_distance());
         }
-        if last_variants.len() > 0 {
-            Ok(Some(last_variants))
-        } else {
-            Ok(None)
+        self.push_accent_variants(word, edit_distance, last_variants);
+        self.push_phonetic_variants(word, edit_distance, last_variants);
+        self.push_transliteration_variants(word, edit_distance, last_variants);
+        Ok(())
+    }
+
+    // with an accent edit distance, words that differ from the (normalized) query word only in
+    // their accents are charged that distance rather than their real one; this adds any that
+    // aren't among the variants already, and makes any that are cheaper if need be
+    fn push_accent_variants(&self, word: &str, edit_distance: u8, variants: &mut Vec<QueryWord>) {
+        let (accent_variants, accent_edit_distance) = match (&self.accent_variants, self.metadata.normalization.accent_edit_distance) {
+            (Some(accent_variants), Some(accent_edit_distance)) if accent_edit_distance <= edit_distance => {
+                (accent_variants, accent_edit_distance)
+            },
+            _ => return,
+        };
+
+        let stripped = normalization::remove_accents(word);
+        let mut ids: Vec<u32> = accent_variants.get(&stripped).cloned().unwrap_or_default();
+        if stripped != word {
+            if let Some(word_id) = self.prefix_set.lookup(&stripped).id() {
+                ids.push(word_id.value() as u32);
+            }
+        }
+
+        self.push_variants_at(ids, accent_edit_distance, variants);
+    }
+
+    // with a phonetic edit distance, words that sound like the (normalized) query word are
+    // charged that distance, in the same way
+    fn push_phonetic_variants(&self, word: &str, edit_distance: u8, variants: &mut Vec<QueryWord>) {
+        let (phonetic_map, phonetic_edit_distance) = match (&self.phonetic_map, self.metadata.phonetic_edit_distance) {
+            (Some(phonetic_map), Some(phonetic_edit_distance)) if phonetic_edit_distance <= edit_distance => {
+                (phonetic_map, phonetic_edit_distance)
+            },
+            _ => return,
+        };
+        if !self.should_fuzzy_match(word, edit_distance) {
+            return;
+        }
+        self.push_variants_at(phonetic_map.lookup(&phonetic_keys(word)), phonetic_edit_distance, variants);
+    }
+
+    // with a transliteration edit distance, non-Latin words that a (Latin) query word is a
+    // romanization of are charged that distance, in the same way
+    fn push_transliteration_variants(&self, word: &str, edit_distance: u8, variants: &mut Vec<QueryWord>) {
+        let (transliteration_map, transliteration_edit_distance) = match (&self.transliteration_map, self.metadata.transliteration_edit_distance) {
+            (Some(transliteration_map), Some(transliteration_edit_distance)) if transliteration_edit_distance <= edit_distance => {
+                (transliteration_map, transliteration_edit_distance)
+            },
+            _ => return,
+        };
+        self.push_variants_at(transliteration_map.lookup(&query_keys(word)), transliteration_edit_distance, variants);
+    }
+
+    // add full-word variants for the given words at the given distance, or lower the distance of
+    // any that are already there at a higher one
+    fn push_variants_at(&self, ids: Vec<u32>, variant_edit_distance: u8, variants: &mut Vec<QueryWord>) {
+        let mut changed = false;
+        for id in ids {
+            let maybe_replaced = *self.word_replacement_map.get(&id).unwrap_or(&id);
+            match variants.iter_mut().find(|x| x.contains_id(maybe_replaced)) {
+                Some(QueryWord::Full { ref mut edit_distance, .. }) => if *edit_distance > variant_edit_distance {
+                    *edit_distance = variant_edit_distance;
+                    changed = true;
+                },
+                // already covered by a prefix
+                Some(_) => (),
+                None => {
+                    variants.push(QueryWord::new_full(maybe_replaced, variant_edit_distance));
+                    changed = true;
+                },
+            }
+        }
+        if changed {
+            variants.sort_by_key(|x| x.edit_distance());
+        }
+    }
+
+    // the runs of words in a range that the variants cover already, or that are among the given
+    // others, in order
+    fn taken_ids(&self, found_range: (u32, u32), last_variants: &[QueryWord], others: &[u32]) -> Vec<(u32, u32)> {
+        let mut taken: Vec<(u32, u32)> = last_variants.iter().map(|x| match *x {
+            QueryWord::Full { id, .. } => (id, id),
+            QueryWord::Prefix { id_range, .. } => id_range,
+        }).chain(others.iter().map(|id| (*id, *id)))
+            .filter(|run| run.1 >= found_range.0 && run.0 <= found_range.1)
+            .collect();
+        taken.sort();
+        taken
+    }
+
+    // push the variants for the parts of a range that are left once the taken runs of words (in
+    // order) are cut out of it
+    fn push_prefix_variants_around(&self, found_range: (u32, u32), taken: Vec<(u32, u32)>, has_continuations: bool, edit_distance: u8, last_variants: &mut Vec<QueryWord>) {
+        let mut cursor = found_range.0;
+        for (start, end) in taken.into_iter().chain(Some((found_range.1 + 1, found_range.1 + 1))) {
+            if start > cursor {
+                self.push_prefix_variants((cursor, start - 1), has_continuations, edit_distance, last_variants);
+            }
+            cursor = cursor.max(end + 1);
+        }
+    }
+
+    fn push_prefix_variants(&self, found_range: (u32, u32), has_continuations: bool, edit_distance: u8, last_variants: &mut Vec<QueryWord>) {
+        let num_terminations = (found_range.1 - found_range.0 + 1) as usize;
+        let replacements: Vec<u32> = self.word_replacement_map
+            .range(found_range.0..=found_range.1)
+            // don't bother emitting a replacement if it would be covered by the prefix anyway
+            .filter_map(|(_key, &target)|
+                if target < found_range.0 || target > found_range.1 {
+                    Some(target)
+                } else {
+                    None
+                }
+            ).collect();
+
+        // if everything within our range will get token-replaced, don't emit the unreplaced word
+        if num_terminations != replacements.len() {
+            // if there's just one word and it's final, emit a full_word
+            if has_continuations {
+                last_variants.push(QueryWord::new_fuzzy_prefix(found_range, edit_distance));
+            } else {
+                last_variants.push(QueryWord::new_full(found_range.0, edit_distance));
+            }
+        }
+        for replacement in replacements {
+            let already = last_variants.iter().any(|x| x.contains_id(replacement));
+            if !already {
+                last_variants.push(QueryWord::new_full(replacement, edit_distance));
+            }
         }
     }
 
     pub fn fuzzy_match<T: AsRef<str>>(&self, phrase: &[T], max_word_dist: u8, max_phrase_dist: u8) -> Result<Vec<FuzzyMatchResult>, Box<Error>> {
+        Ok(self.fuzzy_match_with_features(phrase, max_word_dist, max_phrase_dist, None)?.into_iter().map(|(r, _)| r).collect())
+    }
+
+    /// Like `fuzzy_match`, but returns at most `limit` results, lowest edit distance first, and
+    /// stops searching once it has them.
+    pub fn fuzzy_match_limited<T: AsRef<str>>(&self, phrase: &[T], max_word_dist: u8, max_phrase_dist: u8, limit: usize) -> Result<Vec<FuzzyMatchResult>, Box<Error>> {
+        Ok(self.fuzzy_match_with_features(phrase, max_word_dist, max_phrase_dist, Some(limit))?.into_iter().map(|(r, _)| r).collect())
+    }
+
+    pub fn fuzzy_match_scored<T: AsRef<str>, S: Scorer>(&self, phrase: &[T], max_word_dist: u8, max_phrase_dist: u8, scorer: &S, sorted: bool) -> Result<Vec<ScoredResult<FuzzyMatchResult>>, Box<Error>> {
+        Ok(scoring::score_results(self.fuzzy_match_with_features(phrase, max_word_dist, max_phrase_dist, None)?, scorer, sorted))
+    }
+
+    fn fuzzy_match_with_features<T: AsRef<str>>(&self, phrase: &[T], max_word_dist: u8, max_phrase_dist: u8, limit: Option<usize>) -> Result<Vec<(FuzzyMatchResult, MatchFeatures)>, Box<Error>> {
         // strategy: look up each word in the fuzzy graph
         // and then construct a vector of vectors representing all the word variants that could reside in each slot
         // in the phrase, and then recursively enumerate every combination of variants and look them each up in the phrase graph
@@ -490,38 +1671,189 @@ impl FuzzyPhraseSet {
         };
 
         // the map is executed lazily, so we can early-bail without correcting everything
-        for matches in phrase.iter().map(|word| self.get_nonterminal_word_possibilities(word.as_ref(), edit_distance)) {
+        for matches in phrase.iter().enumerate().map(|(i, word)| {
+            self.get_nonterminal_word_possibilities(word.as_ref(), edit_distance, self.word_context(phrase, i, edit_distance, true, false))
+        }) {
             match matches? {
                 Some(possibilities) => word_possibilities.push(possibilities),
                 None => return Ok(Vec::new()),
             }
         }
 
-        let phrase_matches = self.phrase_set.match_combinations(&word_possibilities, max_phrase_dist)?;
+        let rewrites = self.rewrite_possibilities(word_possibilities);
+        let mut results: Vec<(FuzzyMatchResult, MatchFeatures)> = Vec::new();
+        for rewrite in &rewrites {
+            let phrase_matches = match limit {
+                Some(limit) => self.phrase_set.match_combinations_limited_filtered(&rewrite.word_possibilities, max_phrase_dist, limit, &|word_ids| self.is_live_phrase(word_ids))?,
+                None => self.phrase_set.match_combinations(&rewrite.word_possibilities, max_phrase_dist)?,
+            };
+
+            for phrase_p in &phrase_matches {
+                if !self.is_live(phrase_p, false)? {
+                    continue;
+                }
+                results.push((FuzzyMatchResult {
+                    phrase: phrase_p.iter().map(|qw| match qw {
+                        QueryWord::Full { id, .. } => self.word_list[*id as usize].clone(),
+                        _ => panic!("prefixes not allowed"),
+                    }).collect::<Vec<String>>(),
+                    edit_distance: phrase_p.iter().map(|qw| match qw {
+                        QueryWord::Full { edit_distance, .. } => *edit_distance,
+                        _ => panic!("prefixes not allowed"),
+                    }).sum(),
+                    phrase_ids: self.get_phrase_ids(phrase_p)?,
+                    displays: self.get_phrase_displays(phrase_p),
+                }, self.get_match_features(phrase_p, phrase)));
+            }
+        }
+        if rewrites.len() > 1 {
+            phrase_replacement::dedup_matches(&mut results, |r| (r.0.edit_distance, r.0.phrase.clone()));
+        }
+        if let Some(limit) = limit {
+            results.truncate(limit);
+        }
+
+        Ok(results)
+    }
+
+    pub fn fuzzy_match_str(&self, phrase: &str, max_word_dist: u8, max_phrase_dist: u8) -> Result<Vec<FuzzyMatchResult>, Box<Error>> {
+        let phrase_v = self.tokenize(phrase);
+        self.fuzzy_match(&phrase_v, max_word_dist, max_phrase_dist)
+    }
+
+    pub fn fuzzy_match_prefix<T: AsRef<str>>(&self, phrase: &[T], max_word_dist: u8, max_phrase_dist: u8) -> Result<Vec<FuzzyMatchResult>, Box<Error>> {
+        Ok(self.fuzzy_match_prefix_with_features(phrase, max_word_dist, max_phrase_dist, None)?.into_iter().map(|(r, _)| r).collect())
+    }
+
+    /// Like `fuzzy_match_prefix`, but returns at most `limit` results, lowest edit distance first,
+    /// and stops searching once it has them.
+    pub fn fuzzy_match_prefix_limited<T: AsRef<str>>(&self, phrase: &[T], max_word_dist: u8, max_phrase_dist: u8, limit: usize) -> Result<Vec<FuzzyMatchResult>, Box<Error>> {
+        Ok(self.fuzzy_match_prefix_with_features(phrase, max_word_dist, max_phrase_dist, Some(limit))?.into_iter().map(|(r, _)| r).collect())
+    }
+
+    /// Like `fuzzy_match_prefix`, but rather than echoing back a partially-typed last word,
+    /// expands each match into the complete phrases it could be the beginning of, returning at
+    /// most `limit` of them, lowest edit distance first.
+    pub fn fuzzy_match_prefix_completions<T: AsRef<str>>(&self, phrase: &[T], max_word_dist: u8, max_phrase_dist: u8, limit: usize) -> Result<Vec<FuzzyMatchResult>, Box<Error>> {
+        // every match has at least one completion, so we never need more than `limit` matches
+        let phrase_matches = self.fuzzy_match_prefix_combinations(phrase, max_word_dist, max_phrase_dist, Some(limit))?;
 
         let mut results: Vec<FuzzyMatchResult> = Vec::new();
         for phrase_p in &phrase_matches {
-            results.push(FuzzyMatchResult {
-                phrase: phrase_p.iter().map(|qw| match qw {
+            let query_phrase = QueryPhrase::new(phrase_p)?;
+            let edit_distance = query_phrase.total_edit_distance();
+            for (word_ids, value) in self.phrase_set.completions_filtered(query_phrase, limit - results.len(), &|word_ids| self.is_live_phrase(word_ids))? {
+                if results.len() >= limit {
+                    break;
+                }
+                let phrase: Vec<String> = word_ids.iter().map(|id| self.word_list[*id as usize].clone()).collect();
+                // matches found through different phrase replacements can share completions
+                if results.iter().any(|r| r.phrase == phrase) {
+                    continue;
+                }
+                results.push(FuzzyMatchResult {
+                    phrase,
+                    edit_distance,
+                    phrase_ids: self.resolve_phrase_ids(value),
+                    displays: self.owned_displays(&word_ids),
+                });
+            }
+            if results.len() >= limit {
+                break;
+            }
+        }
+
+        Ok(results)
+    }
+
+    /// Suggest the words that could come next after a phrase whose words are all complete, e.g.
+    /// "100 main" might suggest "100 main street" and "100 main avenue". Each result is a match
+    /// for the phrase with one more word on the end, and carries phrase IDs if that makes a
+    /// complete phrase. Returns at most `limit` results, lowest edit distance first, ordered within
+    /// each match according to `order`.
+    pub fn fuzzy_match_continuations<T: AsRef<str>>(&self, phrase: &[T], max_word_dist: u8, max_phrase_dist: u8, limit: usize, order: ContinuationOrder) -> Result<Vec<FuzzyMatchResult>, Box<Error>> {
+        let mut word_possibilities: Vec<Vec<QueryWord>> = Vec::with_capacity(phrase.len());
+
+        if phrase.len() == 0 {
+            return Ok(Vec::new());
+        }
+
+        let edit_distance = if max_word_dist > self.max_edit_distance {
+            return Err(Box::new(PhraseSetError::new(format!(
+                "The maximum configured edit distance for this index is {}; {} requested",
+                self.max_edit_distance,
+                max_word_dist
+            ).as_str())));
+        } else {
+            max_word_dist
+        };
+
+        // the last word is complete, but there's another one after it
+        let last_idx = phrase.len() - 1;
+        for matches in phrase.iter().enumerate().map(|(i, word)| {
+            let context = self.word_context(phrase, i, edit_distance, true, false);
+            let context = if i == last_idx { WordContext { next: Neighbor::Unknown, ..context } } else { context };
+            self.get_nonterminal_word_possibilities(word.as_ref(), edit_distance, context)
+        }) {
+            match matches? {
+                Some(possibilities) => word_possibilities.push(possibilities),
+                None => return Ok(Vec::new()),
+            }
+        }
+
+        let rewrites = self.rewrite_possibilities(word_possibilities);
+        let mut results: Vec<FuzzyMatchResult> = Vec::new();
+        for rewrite in &rewrites {
+            // search best-first so the cheapest matches get their continuations in first; unlike
+            // with completions, we can't cap the number of matches, since some of them might be
+            // complete phrases with nothing after them
+            let phrase_matches = self.phrase_set.match_combinations_as_prefixes_limited(&rewrite.word_possibilities, max_phrase_dist, usize::max_value())?;
+
+            // each rewrite gets up to `limit` results of its own
+            let mut rewrite_results: Vec<FuzzyMatchResult> = Vec::new();
+            for phrase_p in &phrase_matches {
+                let query_phrase = QueryPhrase::new(phrase_p)?;
+                let edit_distance = query_phrase.total_edit_distance();
+                let matched: Vec<String> = phrase_p.iter().map(|qw| match qw {
                     QueryWord::Full { id, .. } => self.word_list[*id as usize].clone(),
                     _ => panic!("prefixes not allowed"),
-                }).collect::<Vec<String>>(),
-                edit_distance: phrase_p.iter().map(|qw| match qw {
-                    QueryWord::Full { edit_distance, .. } => *edit_distance,
-                    _ => panic!("prefixes not allowed"),
-                }).sum(),
-            });
+                }).collect();
+                // the next word only counts if some phrase that goes through it hasn't been deleted
+                for (word_id, value) in self.phrase_set.continuations_filtered(query_phrase, limit - rewrite_results.len(), order, &|word_ids| self.is_live_phrase(word_ids))? {
+                    if rewrite_results.len() >= limit {
+                        break;
+                    }
+                    let mut continued_p = phrase_p.clone();
+                    continued_p.push(QueryWord::new_full(word_id, 0));
+                    let mut phrase = matched.clone();
+                    phrase.push(self.word_list[word_id as usize].clone());
+                    let (phrase_ids, displays) = match value {
+                        Some(value) => {
+                            (self.resolve_phrase_ids(value), self.get_phrase_displays(&continued_p))
+                        },
+                        _ => (Vec::new(), Vec::new()),
+                    };
+                    rewrite_results.push(FuzzyMatchResult { phrase, edit_distance, phrase_ids, displays });
+                }
+                if rewrite_results.len() >= limit {
+                    break;
+                }
+            }
+            results.extend(rewrite_results);
+        }
+        if rewrites.len() > 1 {
+            phrase_replacement::dedup_matches(&mut results, |r| (r.edit_distance, r.phrase.clone()));
+            results.truncate(limit);
         }
 
         Ok(results)
     }
 
-    pub fn fuzzy_match_str(&self, phrase: &str, max_word_dist: u8, max_phrase_dist: u8) -> Result<Vec<FuzzyMatchResult>, Box<Error>> {
-        let phrase_v: Vec<&str> = phrase.split(' ').collect();
-        self.fuzzy_match(&phrase_v, max_word_dist, max_phrase_dist)
+    pub fn fuzzy_match_prefix_scored<T: AsRef<str>, S: Scorer>(&self, phrase: &[T], max_word_dist: u8, max_phrase_dist: u8, scorer: &S, sorted: bool) -> Result<Vec<ScoredResult<FuzzyMatchResult>>, Box<Error>> {
+        Ok(scoring::score_results(self.fuzzy_match_prefix_with_features(phrase, max_word_dist, max_phrase_dist, None)?, scorer, sorted))
     }
 
-    pub fn fuzzy_match_prefix<T: AsRef<str>>(&self, phrase: &[T], max_word_dist: u8, max_phrase_dist: u8) -> Result<Vec<FuzzyMatchResult>, Box<Error>> {
+    fn fuzzy_match_prefix_combinations<T: AsRef<str>>(&self, phrase: &[T], max_word_dist: u8, max_phrase_dist: u8, limit: Option<usize>) -> Result<Vec<Vec<QueryWord>>, Box<Error>> {
         // strategy: look up each word in the fuzzy graph, and also look up the last one in the prefix graph
         // and then construct a vector of vectors representing all the word variants that could reside in each slot
         // in the phrase, and then recursively enumerate every combination of variants and look them each up in the phrase graph
@@ -545,42 +1877,91 @@ impl FuzzyPhraseSet {
         // all words but the last one: fuzzy-lookup if eligible, or exact-match if not,
         // and return nothing if those fail
         let last_idx = phrase.len() - 1;
-        for matches in phrase[..last_idx].iter().map(|word| self.get_nonterminal_word_possibilities(word.as_ref(), edit_distance)) {
+        for matches in phrase[..last_idx].iter().enumerate().map(|(i, word)| {
+            self.get_nonterminal_word_possibilities(word.as_ref(), edit_distance, self.word_context(phrase, i, edit_distance, true, true))
+        }) {
             match matches? {
                 Some(possibilities) => word_possibilities.push(possibilities),
                 None => return Ok(Vec::new()),
             }
         }
-        match self.get_terminal_word_possibilities(phrase[last_idx].as_ref(), edit_distance)? {
+        match self.get_terminal_word_possibilities(phrase[last_idx].as_ref(), edit_distance, self.word_context(phrase, last_idx, edit_distance, true, true))? {
             Some(possibilities) => word_possibilities.push(possibilities),
             None => return Ok(Vec::new()),
         }
 
-        let phrase_matches = self.phrase_set.match_combinations_as_prefixes(&word_possibilities, max_phrase_dist)?;
+        let rewrites = self.rewrite_possibilities(word_possibilities);
+        let mut live_matches: Vec<Vec<QueryWord>> = Vec::new();
+        for rewrite in &rewrites {
+            let phrase_matches = match limit {
+                Some(limit) => self.phrase_set.match_combinations_as_prefixes_limited_filtered(&rewrite.word_possibilities, max_phrase_dist, limit, &|word_ids| self.is_live_phrase(word_ids))?,
+                None => self.phrase_set.match_combinations_as_prefixes(&rewrite.word_possibilities, max_phrase_dist)?,
+            };
+            for phrase_p in phrase_matches {
+                if self.is_live(&phrase_p, true)? {
+                    live_matches.push(phrase_p);
+                }
+            }
+        }
+        if rewrites.len() > 1 {
+            phrase_replacement::dedup_matches(&mut live_matches, |phrase_p| {
+                let ids: Vec<(u32, u32)> = phrase_p.iter().map(|qw| match qw {
+                    QueryWord::Full { id, .. } => (*id, *id),
+                    QueryWord::Prefix { id_range, .. } => *id_range,
+                }).collect();
+                (phrase_p.iter().map(|qw| qw.edit_distance()).sum(), ids)
+            });
+        }
+        if let Some(limit) = limit {
+            live_matches.truncate(limit);
+        }
+        Ok(live_matches)
+    }
 
-        let mut results: Vec<FuzzyMatchResult> = Vec::new();
+    fn fuzzy_match_prefix_with_features<T: AsRef<str>>(&self, phrase: &[T], max_word_dist: u8, max_phrase_dist: u8, limit: Option<usize>) -> Result<Vec<(FuzzyMatchResult, MatchFeatures)>, Box<Error>> {
+        let phrase_matches = self.fuzzy_match_prefix_combinations(phrase, max_word_dist, max_phrase_dist, limit)?;
+
+        let mut results: Vec<(FuzzyMatchResult, MatchFeatures)> = Vec::new();
         for phrase_p in &phrase_matches {
-            results.push(FuzzyMatchResult {
-                phrase: phrase_p.iter().enumerate().map(|(i, qw)| match qw {
+            results.push((FuzzyMatchResult {
+                // only the last word can be a prefix, and phrase replacements only ever put in full
+                // words, so a prefix is always the last word of the query
+                phrase: phrase_p.iter().map(|qw| match qw {
                     QueryWord::Full { id, .. } => self.word_list[*id as usize].clone(),
-                    QueryWord::Prefix { .. } => phrase[i].as_ref().to_owned(),
+                    QueryWord::Prefix { .. } => phrase[phrase.len() - 1].as_ref().to_owned(),
                 }).collect::<Vec<String>>(),
                 edit_distance: phrase_p.iter().map(|qw| match qw {
                     QueryWord::Full { edit_distance, .. } => *edit_distance,
-                    QueryWord::Prefix { .. } => 0u8,
+                    QueryWord::Prefix { edit_distance, .. } => *edit_distance,
                 }).sum(),
-            })
+                phrase_ids: self.get_phrase_ids(phrase_p)?,
+                displays: self.get_phrase_displays(phrase_p),
+            }, self.get_match_features(phrase_p, phrase)))
         }
 
         Ok(results)
     }
 
     pub fn fuzzy_match_prefix_str(&self, phrase: &str, max_word_dist: u8, max_phrase_dist: u8) -> Result<Vec<FuzzyMatchResult>, Box<Error>> {
-        let phrase_v: Vec<&str> = phrase.split(' ').collect();
+        let phrase_v = self.tokenize(phrase);
         self.fuzzy_match_prefix(&phrase_v, max_word_dist, max_phrase_dist)
     }
 
     pub fn fuzzy_match_windows<T: AsRef<str>>(&self, phrase: &[T], max_word_dist: u8, max_phrase_dist: u8, ends_in_prefix: bool) -> Result<Vec<FuzzyWindowResult>, Box<Error>> {
+        Ok(self.fuzzy_match_windows_with_features(phrase, max_word_dist, max_phrase_dist, ends_in_prefix, None)?.into_iter().map(|(r, _)| r).collect())
+    }
+
+    /// Like `fuzzy_match_windows`, but returns at most `limit` results, lowest edit distance
+    /// first. Each window stops searching once it has `limit` results of its own.
+    pub fn fuzzy_match_windows_limited<T: AsRef<str>>(&self, phrase: &[T], max_word_dist: u8, max_phrase_dist: u8, ends_in_prefix: bool, limit: usize) -> Result<Vec<FuzzyWindowResult>, Box<Error>> {
+        Ok(self.fuzzy_match_windows_with_features(phrase, max_word_dist, max_phrase_dist, ends_in_prefix, Some(limit))?.into_iter().map(|(r, _)| r).collect())
+    }
+
+    pub fn fuzzy_match_windows_scored<T: AsRef<str>, S: Scorer>(&self, phrase: &[T], max_word_dist: u8, max_phrase_dist: u8, ends_in_prefix: bool, scorer: &S, sorted: bool) -> Result<Vec<ScoredResult<FuzzyWindowResult>>, Box<Error>> {
+        Ok(scoring::score_results(self.fuzzy_match_windows_with_features(phrase, max_word_dist, max_phrase_dist, ends_in_prefix, None)?, scorer, sorted))
+    }
+
+    fn fuzzy_match_windows_with_features<T: AsRef<str>>(&self, phrase: &[T], max_word_dist: u8, max_phrase_dist: u8, ends_in_prefix: bool, limit: Option<usize>) -> Result<Vec<(FuzzyWindowResult, MatchFeatures)>, Box<Error>> {
         // this is a little different than the regular fuzzy match in that we're considering
         // multiple possible substrings we'll start by trying to fuzzy-match all the words, but
         // some of those will likely fail -- rather than early-returning like in regular fuzzy
@@ -631,20 +2012,22 @@ impl FuzzyPhraseSet {
             max_word_dist
         };
 
-        // this block creates an iterator of possible fuzzy matches for each word in phrase
+        // this block creates an iterator of possible fuzzy matches for each word in phrase; windows
+        // can start and end anywhere, so any word's neighbors might not be part of its match
+        let context = |i: usize| self.word_context(phrase, i, edit_distance, false, ends_in_prefix);
         let seq: Box<Iterator<Item=Result<Option<Vec<QueryWord>>, Box<Error>>>> = if ends_in_prefix {
             // if the phrase ends in a prefix
             let last_idx = phrase.len() - 1;
-            let i = phrase[..last_idx].iter().map(
+            let i = phrase[..last_idx].iter().enumerate().map(
                 // call this function on every word except the last one
-                |word| self.get_nonterminal_word_possibilities(word.as_ref(), edit_distance)
+                move |(i, word)| self.get_nonterminal_word_possibilities(word.as_ref(), edit_distance, context(i))
             ).chain(iter::once(last_idx).map(
                 // call this function on the last word (the prefix)
-                |idx| self.get_terminal_word_possibilities(phrase[idx].as_ref(), edit_distance))
+                move |idx| self.get_terminal_word_possibilities(phrase[idx].as_ref(), edit_distance, context(idx)))
             );
             Box::new(i)
         } else {
-            let i = phrase.iter().map(|word| self.get_nonterminal_word_possibilities(word.as_ref(), edit_distance));
+            let i = phrase.iter().enumerate().map(move |(i, word)| self.get_nonterminal_word_possibilities(word.as_ref(), edit_distance, context(i)));
             Box::new(i)
         };
 
@@ -699,35 +2082,90 @@ impl FuzzyPhraseSet {
         // Continuing with the example above: by iterating over multiple start positions within
         // each chunk, we'll end up considering "C" and "H" in addition to the combinations that
         // start in the initial positions ("A C", "B C", "F H", "G H").
-        let mut results: Vec<FuzzyWindowResult> = Vec::new();
-        for chunk in subqueries.iter() {
-            for i in 0..chunk.word_possibilities.len() {
-                let mut phrase_matches = self.phrase_set.match_combinations_as_windows(
-                    &chunk.word_possibilities[i..],
-                    max_phrase_dist,
-                    chunk.ends_in_prefix
-                )?;
-                for (phrase_p, sq_ends_in_prefix) in &phrase_matches {
-                    results.push(FuzzyWindowResult {
-                        phrase: phrase_p.iter().enumerate().map(|(j, qw)| match qw {
-                            QueryWord::Full { id, .. } => self.word_list[*id as usize].clone(),
-                            QueryWord::Prefix { .. } => phrase[chunk.start_position + i + j].as_ref().to_owned(),
-                        }).collect::<Vec<String>>(),
-                        edit_distance: phrase_p.iter().map(|qw| match qw {
-                            QueryWord::Full { edit_distance, .. } => *edit_distance,
-                            QueryWord::Prefix { .. } => 0u8,
-                        }).sum(),
-                        start_position: chunk.start_position + i,
-                        ends_in_prefix: *sq_ends_in_prefix,
-                    })
+        //
+        // Phrase replacements can turn a chunk's words into more than one sequence of slots, so
+        // each of those gets searched too. A replacement's slots stand for all of the words it
+        // replaced, so windows can't start or end partway through them, and their positions are
+        // mapped back to the query's.
+        let mut results: Vec<(FuzzyWindowResult, MatchFeatures)> = Vec::new();
+        let mut rewritten = false;
+        for chunk in subqueries {
+            let chunk_start = chunk.start_position;
+            let chunk_ends_in_prefix = chunk.ends_in_prefix;
+            let rewrites = self.rewrite_possibilities(chunk.word_possibilities);
+            rewritten = rewritten || rewrites.len() > 1;
+            for rewrite in &rewrites {
+                for i in 0..rewrite.word_possibilities.len() {
+                    if !rewrite.starts_span(i) {
+                        continue;
+                    }
+                    let phrase_matches = match limit {
+                        Some(limit) => self.phrase_set.match_combinations_as_windows_limited_filtered(
+                            &rewrite.word_possibilities[i..],
+                            max_phrase_dist,
+                            chunk_ends_in_prefix,
+                            limit,
+                            &|word_ids| self.is_live_phrase(word_ids)
+                        )?,
+                        None => self.phrase_set.match_combinations_as_windows(
+                            &rewrite.word_possibilities[i..],
+                            max_phrase_dist,
+                            chunk_ends_in_prefix
+                        )?,
+                    };
+                    for (phrase_p, sq_ends_in_prefix) in &phrase_matches {
+                        let last = i + phrase_p.len() - 1;
+                        if !rewrite.ends_span(last) || !self.is_live(phrase_p, *sq_ends_in_prefix)? {
+                            continue;
+                        }
+                        let start_position = chunk_start + rewrite.positions[i].start;
+                        let end_position = chunk_start + rewrite.positions[last].end;
+                        results.push((FuzzyWindowResult {
+                            phrase: phrase_p.iter().enumerate().map(|(j, qw)| match qw {
+                                QueryWord::Full { id, .. } => self.word_list[*id as usize].clone(),
+                                QueryWord::Prefix { .. } => phrase[chunk_start + rewrite.positions[i + j].start].as_ref().to_owned(),
+                            }).collect::<Vec<String>>(),
+                            edit_distance: phrase_p.iter().map(|qw| match qw {
+                                QueryWord::Full { edit_distance, .. } => *edit_distance,
+                                QueryWord::Prefix { edit_distance, .. } => *edit_distance,
+                            }).sum(),
+                            phrase_ids: self.get_phrase_ids(phrase_p)?,
+                            displays: self.get_phrase_displays(phrase_p),
+                            start_position,
+                            ends_in_prefix: *sq_ends_in_prefix,
+                        }, self.get_match_features(phrase_p, &phrase[start_position..end_position])))
+                    }
                 }
             }
         }
+        if rewritten {
+            phrase_replacement::dedup_matches(&mut results, |r| {
+                (r.0.edit_distance, (r.0.start_position, r.0.ends_in_prefix, r.0.phrase.clone()))
+            });
+        }
+
+        // each window was searched separately, so pick the best of all of them
+        if let Some(limit) = limit {
+            results.sort_by_key(|r| r.0.edit_distance);
+            results.truncate(limit);
+        }
 
         Ok(results)
     }
 
     pub fn fuzzy_match_multi<T: AsRef<str> + Ord + Debug, U: AsRef<[T]>>(&self, phrases: &[(U, bool)], max_word_dist: u8, max_phrase_dist: u8) -> Result<Vec<Vec<FuzzyMatchResult>>, Box<Error>> {
+        Ok(self.fuzzy_match_multi_with_features(phrases, max_word_dist, max_phrase_dist)?.into_iter().map(
+            |results| results.into_iter().map(|(r, _)| r).collect()
+        ).collect())
+    }
+
+    pub fn fuzzy_match_multi_scored<T: AsRef<str> + Ord + Debug, U: AsRef<[T]>, S: Scorer>(&self, phrases: &[(U, bool)], max_word_dist: u8, max_phrase_dist: u8, scorer: &S, sorted: bool) -> Result<Vec<Vec<ScoredResult<FuzzyMatchResult>>>, Box<Error>> {
+        Ok(self.fuzzy_match_multi_with_features(phrases, max_word_dist, max_phrase_dist)?.into_iter().map(
+            |results| scoring::score_results(results, scorer, sorted)
+        ).collect())
+    }
+
+    fn fuzzy_match_multi_with_features<T: AsRef<str> + Ord + Debug, U: AsRef<[T]>>(&self, phrases: &[(U, bool)], max_word_dist: u8, max_phrase_dist: u8) -> Result<Vec<Vec<(FuzzyMatchResult, MatchFeatures)>>, Box<Error>> {
 
         // This is roughly equivalent to `fuzzy_match_windows` in purpose, but operating under
         // the assumption that the caller will have wanted to make some changes to some of the
@@ -758,35 +2196,47 @@ impl FuzzyPhraseSet {
             max_word_dist
         };
 
-        // fuzzy-lookup all the words, but only once apiece (per prefix-y-ness type)
-        let mut all_words: FxHashMap<(&str, bool), Vec<QueryWord>> = FxHashMap::default();
+        // fuzzy-lookup all the words, but only once apiece (per prefix-y-ness type, and, with
+        // scoped replacements, per context); each phrase is searched from its start, but its
+        // matches might end before any of its words
+        let word_context = |phrase: &[T], i: usize, ends_in_prefix: bool| {
+            let context = self.word_context(phrase, i, edit_distance, true, ends_in_prefix);
+            match context.next {
+                Neighbor::Word(id) => WordContext { next: Neighbor::MaybeWord(id), ..context },
+                _ => context,
+            }
+        };
+        let mut all_words: FxHashMap<(&str, bool, WordContext), Vec<QueryWord>> = FxHashMap::default();
         let mut indexed_phrases: Vec<(&[T], bool, usize)> = Vec::new();
         for (i, (phrase, ends_in_prefix)) in phrases.iter().enumerate() {
             let phrase = phrase.as_ref();
             if *ends_in_prefix {
                 let last_idx = phrase.len() - 1;
-                for word in phrase[..last_idx].iter() {
+                for (j, word) in phrase[..last_idx].iter().enumerate() {
                     let word = word.as_ref();
-                    if let hash_map::Entry::Vacant(entry) = all_words.entry((word, false)) {
+                    let context = word_context(phrase, j, true);
+                    if let hash_map::Entry::Vacant(entry) = all_words.entry((word, false, context)) {
                         entry.insert(
-                            self.get_nonterminal_word_possibilities(word, edit_distance)?
+                            self.get_nonterminal_word_possibilities(word, edit_distance, context)?
                                 .unwrap_or_else(|| Vec::with_capacity(0))
                         );
                     }
                 }
                 let last_word = phrase[last_idx].as_ref();
-                if let hash_map::Entry::Vacant(entry) = all_words.entry((last_word, true)) {
+                let context = word_context(phrase, last_idx, true);
+                if let hash_map::Entry::Vacant(entry) = all_words.entry((last_word, true, context)) {
                     entry.insert(
-                        self.get_terminal_word_possibilities(last_word, edit_distance)?
+                        self.get_terminal_word_possibilities(last_word, edit_distance, context)?
                             .unwrap_or_else(|| Vec::with_capacity(0))
                     );
                 }
             } else {
-                for word in phrase.iter() {
+                for (j, word) in phrase.iter().enumerate() {
                     let word = word.as_ref();
-                    if let hash_map::Entry::Vacant(entry) = all_words.entry((word, false)) {
+                    let context = word_context(phrase, j, false);
+                    if let hash_map::Entry::Vacant(entry) = all_words.entry((word, false, context)) {
                         entry.insert(
-                            self.get_nonterminal_word_possibilities(word, edit_distance)?
+                            self.get_nonterminal_word_possibilities(word, edit_distance, context)?
                                 .unwrap_or_else(|| Vec::with_capacity(0))
                         );
                     }
@@ -840,7 +2290,7 @@ impl FuzzyPhraseSet {
 
         // Now we'll construct a vector of actual QueryWords for each longest phrase and
         // explore it, and then match it and its prefixes up to whatever we get back
-        let mut results: Vec<Vec<FuzzyMatchResult>> = vec![vec![]; phrases.len()];
+        let mut results: Vec<Vec<(FuzzyMatchResult, MatchFeatures)>> = vec![vec![]; phrases.len()];
         let mut word_possibilities: Vec<Vec<QueryWord>> = Vec::new();
         for (longest_idx, all_idxes) in collapsed.iter() {
             if phrases[*longest_idx].0.as_ref().len() == 0 {
@@ -851,25 +2301,20 @@ impl FuzzyPhraseSet {
 
             // Reuse the possibilities vector
             word_possibilities.clear();
-            let longest_phrase = &phrases[*longest_idx].0.as_ref();
+            let longest_phrase: &[T] = phrases[*longest_idx].0.as_ref();
             let ends_in_prefix = phrases[*longest_idx].1;
-            for word in longest_phrase[..(longest_phrase.len() - 1)].iter() {
+            let last_idx = longest_phrase.len() - 1;
+            for (j, word) in longest_phrase[..last_idx].iter().enumerate() {
                 word_possibilities.push(
-                    all_words.get(&(word.as_ref(), false))
+                    all_words.get(&(word.as_ref(), false, word_context(longest_phrase, j, ends_in_prefix)))
                         .ok_or("Can't find corrected word")?.clone()
                 );
             }
             word_possibilities.push(
-                all_words.get(&(longest_phrase[longest_phrase.len() - 1].as_ref(), ends_in_prefix))
+                all_words.get(&(longest_phrase[last_idx].as_ref(), ends_in_prefix, word_context(longest_phrase, last_idx, ends_in_prefix)))
                     .ok_or("Can't find corrected word")?.clone()
             );
 
-            let phrase_matches = self.phrase_set.match_combinations_as_windows(
-                &word_possibilities,
-                max_phrase_dist,
-                ends_in_prefix
-            )?;
-
             // Within this prefix cluster we have different things of different lengths and
             // prefix-y-nesses. Any results we get back of the same length and prefix-y-ness
             // should be ascribed to their matching entries in the cluster so they can be inserted
@@ -878,21 +2323,46 @@ impl FuzzyPhraseSet {
                 |&idx| ((phrases[idx].0.as_ref().len(), phrases[idx].1), idx)
             ).collect();
 
-            for (phrase_p, sq_ends_in_prefix) in &phrase_matches {
-                // We might have found results in our phrase graph traversal that we weren't
-                // actually look for -- we'll ignore those and only add results if they match
-                if let Some(&input_idx) = length_map.get(&(phrase_p.len(), *sq_ends_in_prefix)) {
-                    let input_phrase = phrases[input_idx].0.as_ref();
-                    results[input_idx].push(FuzzyMatchResult {
-                        phrase: phrase_p.iter().enumerate().map(|(i, qw)| match qw {
-                            QueryWord::Full { id, .. } => self.word_list[*id as usize].clone(),
-                            QueryWord::Prefix { .. } => input_phrase[i].as_ref().to_owned(),
-                        }).collect::<Vec<String>>(),
-                        edit_distance: phrase_p.iter().map(|qw| match qw {
-                            QueryWord::Full { edit_distance, .. } => *edit_distance,
-                            QueryWord::Prefix { .. } => 0u8,
-                        }).sum(),
-                    });
+            // with phrase replacements, a match's length in the query is however many query
+            // words its slots stand for, and it can't end partway through a replacement
+            let rewrites = self.rewrite_possibilities(word_possibilities.clone());
+            for rewrite in &rewrites {
+                let phrase_matches = self.phrase_set.match_combinations_as_windows(
+                    &rewrite.word_possibilities,
+                    max_phrase_dist,
+                    ends_in_prefix
+                )?;
+
+                for (phrase_p, sq_ends_in_prefix) in &phrase_matches {
+                    let last = phrase_p.len() - 1;
+                    if !rewrite.ends_span(last) {
+                        continue;
+                    }
+                    // We might have found results in our phrase graph traversal that we weren't
+                    // actually look for -- we'll ignore those and only add results if they match
+                    if let Some(&input_idx) = length_map.get(&(rewrite.positions[last].end, *sq_ends_in_prefix)) {
+                        if !self.is_live(phrase_p, *sq_ends_in_prefix)? {
+                            continue;
+                        }
+                        let input_phrase = phrases[input_idx].0.as_ref();
+                        results[input_idx].push((FuzzyMatchResult {
+                            phrase: phrase_p.iter().enumerate().map(|(i, qw)| match qw {
+                                QueryWord::Full { id, .. } => self.word_list[*id as usize].clone(),
+                                QueryWord::Prefix { .. } => input_phrase[rewrite.positions[i].start].as_ref().to_owned(),
+                            }).collect::<Vec<String>>(),
+                            edit_distance: phrase_p.iter().map(|qw| match qw {
+                                QueryWord::Full { edit_distance, .. } => *edit_distance,
+                                QueryWord::Prefix { edit_distance, .. } => *edit_distance,
+                            }).sum(),
+                            phrase_ids: self.get_phrase_ids(phrase_p)?,
+                            displays: self.get_phrase_displays(phrase_p),
+                        }, self.get_match_features(phrase_p, input_phrase)));
+                    }
+                }
+            }
+            if rewrites.len() > 1 {
+                for &idx in all_idxes {
+                    phrase_replacement::dedup_matches(&mut results[idx], |r| (r.0.edit_distance, r.0.phrase.clone()));
                 }
             }
         }
@@ -901,6 +2371,58 @@ impl FuzzyPhraseSet {
     }
 }
 
+// write the live phrases of one or more sets, which must have been built with the same options,
+// into a single new set at `path`; the lexicon is rebuilt from the words those phrases use (and
+// the word replacements), so words only deleted phrases used are dropped
+fn rebuild<P: AsRef<Path>>(sets: &[&FuzzyPhraseSet], path: P) -> Result<(), Box<Error>> {
+    let first = sets.first().ok_or("At least one set is required")?;
+    let mut builder = FuzzyPhraseSetBuilder::new_with_options(path, first.options())?;
+    builder.load_word_replacements(first.word_replacements().to_vec());
+    builder.load_phrase_replacements(first.phrase_replacements().to_vec())?;
+
+    // the phrases come out of each set already replaced, so they go back in as they are
+    for set in sets {
+        for (phrase, phrase_ids) in set.phrases() {
+            let word_ids: Vec<u32> = phrase.iter()
+                .map(|word| set.prefix_set.lookup(word).id().map(|word_id| word_id.value() as u32))
+                .collect::<Option<_>>()
+                .ok_or("Phrase word not in lexicon")?;
+            let displays = set.get_displays(&word_ids);
+            for phrase_id in phrase_ids {
+                let display = displays.iter().find(|(display_id, _)| *display_id == phrase_id).map(|(_, display)| *display);
+                builder.insert_replaced(&phrase, phrase_id, display)?;
+            }
+        }
+    }
+
+    builder.finish()
+}
+
+// replace the tombstone list in a set's directory; it's written to a temporary file first and
+// then moved into place, so a failed write leaves the old list as it was
+fn write_tombstones(directory: &Path, tombstones: SerializableTombstones) -> Result<(), Box<Error>> {
+    let tombstone_path = directory.join(Path::new("deleted.msg"));
+    let temp_path = directory.join(Path::new("deleted.msg.tmp"));
+    let mut tombstone_writer = BufWriter::new(fs::File::create(&temp_path)?);
+    tombstones.serialize(&mut Serializer::new(&mut tombstone_writer))?;
+    tombstone_writer.flush()?;
+    tombstone_writer.get_ref().sync_all()?;
+    fs::rename(&temp_path, &tombstone_path)?;
+    Ok(())
+}
+
+// the alias graph at `path`, if the set has one there
+fn read_alias_map(path: &Path) -> Result<Option<AliasMap>, Box<Error>> {
+    if !path.exists() {
+        return Ok(None);
+    }
+    #[cfg(feature = "mmap")]
+    let alias_map = unsafe { AliasMap::from_path(path) }?;
+    #[cfg(not(feature = "mmap"))]
+    let alias_map = AliasMap::from_reader(BufReader::new(fs::File::open(path)?))?;
+    Ok(Some(alias_map))
+}
+
 #[cfg(test)]
 mod basic_tests {
     extern crate tempfile;
@@ -908,14 +2430,24 @@ mod basic_tests {
 
     use super::*;
 
+    // the result for a whole phrase that was inserted once, without a display string
+    fn full_match(phrase: &[&str], phrase_id: u64, edit_distance: u8) -> FuzzyMatchResult {
+        FuzzyMatchResult {
+            phrase: phrase.iter().map(|w| w.to_string()).collect(),
+            phrase_ids: vec![phrase_id],
+            displays: vec![],
+            edit_distance,
+        }
+    }
+
     lazy_static! {
         static ref DIR: tempfile::TempDir = tempfile::tempdir().unwrap();
         static ref SET: FuzzyPhraseSet = {
             let mut builder = FuzzyPhraseSetBuilder::new(&DIR.path()).unwrap();
-            builder.insert_str("100 main street").unwrap();
-            builder.insert_str("200 main street").unwrap();
-            builder.insert_str("100 main ave").unwrap();
-            builder.insert_str("300 mlk blvd").unwrap();
+            builder.insert_str("100 main street", 0).unwrap();
+            builder.insert_str("200 main street", 1).unwrap();
+            builder.insert_str("100 main ave", 2).unwrap();
+            builder.insert_str("300 mlk blvd", 3).unwrap();
             builder.finish().unwrap();
 
             FuzzyPhraseSet::from_path(&DIR.path()).unwrap()
@@ -932,10 +2464,53 @@ mod basic_tests {
         contents.sort();
         assert_eq!(
             contents,
-            vec!["fuzzy.fst", "fuzzy.msg", "metadata.json", "phrase.fst", "prefix.fst"]
+            vec!["fuzzy.fst", "fuzzy.msg", "metadata.json", "phrase.fst", "phrase.msg", "prefix.fst"]
         );
     }
 
+    #[test]
+    fn glue_legacy_metadata() -> () {
+        // a set from before phrases had IDs: every phrase in the graph has a zero, there's no
+        // phrase ID list, and the metadata only has the settings there were then
+        let dir = tempfile::tempdir().unwrap();
+        let mut builder = FuzzyPhraseSetBuilder::new(&dir.path()).unwrap();
+        builder.load_word_replacements(vec![WordReplacement::new("street", "st")]);
+        builder.insert_str("100 main street", 0).unwrap();
+        builder.insert_str("300 mlk blvd", 0).unwrap();
+        builder.finish().unwrap();
+        fs::remove_file(dir.path().join("phrase.msg")).unwrap();
+        let v1_metadata = r#"{"index_type":"fuzzy_phrase_set","format_version":1,"fuzzy_enabled_scripts":["Latin","Greek","Cyrillic"],"max_edit_distance":1,"word_replacements":[{"from":"street","to":"st"}]}"#;
+        fs::write(dir.path().join("metadata.json"), v1_metadata).unwrap();
+
+        let set = FuzzyPhraseSet::from_path(&dir.path()).unwrap();
+        let options = set.options();
+        assert_eq!(options.min_fuzzy_word_length, 2);
+        assert_eq!(options.edit_distance_policy, EditDistancePolicy::Uniform);
+        assert_eq!(options.tokenizer, TokenizerType::Space);
+        assert_eq!(set.tokenize("100  main st."), vec!["100", "", "main", "st."]);
+        assert!(set.contains_str("100 main street").unwrap());
+        assert_eq!(set.fuzzy_match_str("300 mlk blvd", 1, 1).unwrap()[0].phrase_ids, vec![0]);
+        assert_eq!(set.fuzzy_match_str("300 mlk blvf", 1, 1).unwrap()[0].phrase, vec!["300", "mlk", "blvd"]);
+        assert!(set.contains_str("100 main st").unwrap());
+        assert!(verify(&dir.path()).unwrap().is_ok());
+
+        // compacting it writes a current one
+        let compacted = tempfile::tempdir().unwrap();
+        set.compact(&compacted.path().join("index")).unwrap();
+        assert!(compacted.path().join("index").join("phrase.msg").exists());
+        let compacted_set = FuzzyPhraseSet::from_path(&compacted.path().join("index")).unwrap();
+        assert_eq!(compacted_set.phrases().collect::<Vec<_>>(), set.phrases().collect::<Vec<_>>());
+
+        // a version 2 set still needs its phrase ID list, and later versions aren't supported
+        let v2_metadata = v1_metadata.replace(r#""format_version":1"#, r#""format_version":2"#);
+        fs::write(dir.path().join("metadata.json"), &v2_metadata).unwrap();
+        assert!(FuzzyPhraseSet::from_path(&dir.path()).is_err());
+        assert!(!verify(&dir.path()).unwrap().is_ok());
+        let v3_metadata = v1_metadata.replace(r#""format_version":1"#, r#""format_version":3"#);
+        fs::write(dir.path().join("metadata.json"), &v3_metadata).unwrap();
+        assert!(FuzzyPhraseSet::from_path(&dir.path()).is_err());
+    }
+
     // TODO:  test fpsb.insert <05-07-18, boblannon> //
     // TODO:  test fpsb.insert_str <05-07-18, boblannon> //
 
@@ -1016,33 +2591,955 @@ mod basic_tests {
         assert_eq!(
             SET.fuzzy_match(&["100", "man", "street"], 1, 1).unwrap(),
             vec![
-                FuzzyMatchResult { phrase: vec!["100".to_string(), "main".to_string(), "street".to_string()], edit_distance: 1 },
+                FuzzyMatchResult { phrase: vec!["100".to_string(), "main".to_string(), "street".to_string()], phrase_ids: vec![0], displays: vec![], edit_distance: 1 },
             ]
         );
 
         assert_eq!(
             SET.fuzzy_match(&["100", "man", "stret"], 1, 2).unwrap(),
             vec![
-                FuzzyMatchResult { phrase: vec!["100".to_string(), "main".to_string(), "street".to_string()], edit_distance: 2 },
+                FuzzyMatchResult { phrase: vec!["100".to_string(), "main".to_string(), "street".to_string()], phrase_ids: vec![0], displays: vec![], edit_distance: 2 },
             ]
         );
 
         assert!(SET.fuzzy_match(&["100", "man", "stret"], 2, 2).is_err());
     }
 
+    #[test]
+    fn glue_builder_options() -> () {
+        let dir = tempfile::tempdir().unwrap();
+        let options = FuzzyPhraseSetBuilderOptions {
+            max_edit_distance: 2,
+            fuzzy_enabled_scripts: vec!["Cyrillic".to_string()],
+            min_fuzzy_word_length: 4,
+            ..FuzzyPhraseSetBuilderOptions::default()
+        };
+        let mut builder = FuzzyPhraseSetBuilder::new_with_options(&dir.path(), options.clone()).unwrap();
+        builder.insert_str("улица ленина", 0).unwrap();
+        builder.insert_str("lenin street", 1).unwrap();
+        builder.insert_str("дом 1", 2).unwrap();
+        builder.finish().unwrap();
+
+        let metadata_reader = BufReader::new(fs::File::open(dir.path().join(Path::new("metadata.json"))).unwrap());
+        let metadata: FuzzyPhraseSetMetadata = serde_json::from_reader(metadata_reader).unwrap();
+        assert_eq!(metadata.max_edit_distance, 2);
+        assert_eq!(metadata.fuzzy_enabled_scripts, options.fuzzy_enabled_scripts);
+        assert_eq!(metadata.min_fuzzy_word_length, 4);
+
+        let set = FuzzyPhraseSet::from_path(&dir.path()).unwrap();
+        // two edits in one word are allowed now
+        assert_eq!(
+            set.fuzzy_match_str("улца лнина", 2, 3).unwrap(),
+            vec![
+                FuzzyMatchResult { phrase: vec!["улица".to_string(), "ленина".to_string()], phrase_ids: vec![0], displays: vec![], edit_distance: 2 },
+            ]
+        );
+        assert!(set.fuzzy_match_str("улца лнина", 3, 3).is_err());
+        // Latin isn't fuzzy-enabled in this index
+        assert_eq!(set.fuzzy_match_str("lenin stret", 2, 2).unwrap(), vec![]);
+        // and words shorter than four characters aren't corrected
+        assert_eq!(set.fuzzy_match_str("дим 1", 2, 2).unwrap(), vec![]);
+
+        assert!(FuzzyPhraseSetBuilder::new_with_options(
+            &dir.path(),
+            FuzzyPhraseSetBuilderOptions { fuzzy_enabled_scripts: vec!["Klingon".to_string()], ..FuzzyPhraseSetBuilderOptions::default() }
+        ).is_err());
+    }
+
+    #[test]
+    fn glue_tokenizer() -> () {
+        let dir = tempfile::tempdir().unwrap();
+        let mut builder = FuzzyPhraseSetBuilder::new(&dir.path()).unwrap();
+        builder.insert_str("100  main st.", 0).unwrap();
+        builder.insert_str("o'brien\u{00A0}avenue", 1).unwrap();
+        builder.finish().unwrap();
+
+        let set = FuzzyPhraseSet::from_path(&dir.path()).unwrap();
+        assert_eq!(set.options().tokenizer, TokenizerType::UnicodeWords);
+        assert_eq!(set.words(), &["100", "avenue", "main", "o'brien", "st"]);
+        assert!(set.contains_str("100 main st").unwrap());
+        assert!(set.contains_prefix_str(" o'brien, av").unwrap());
+        assert_eq!(
+            set.fuzzy_match_str("100\tmian, st", 1, 1).unwrap(),
+            vec![FuzzyMatchResult { phrase: vec!["100".to_string(), "main".to_string(), "st".to_string()], phrase_ids: vec![0], displays: vec![], edit_distance: 1 }]
+        );
+
+        // sets built before the tokenizer was recorded keep splitting on spaces
+        let metadata_path = dir.path().join(Path::new("metadata.json"));
+        let mut metadata: serde_json::Value = serde_json::from_slice(&fs::read(&metadata_path).unwrap()).unwrap();
+        metadata.as_object_mut().unwrap().remove("tokenizer");
+        fs::write(&metadata_path, serde_json::to_vec(&metadata).unwrap()).unwrap();
+        let set = FuzzyPhraseSet::from_path(&dir.path()).unwrap();
+        assert_eq!(set.options().tokenizer, TokenizerType::Space);
+        assert_eq!(set.tokenize("100  main st."), vec!["100", "", "main", "st."]);
+        assert!(!set.contains_str("100  main st").unwrap());
+    }
+
+    #[test]
+    fn glue_normalization() -> () {
+        let build = |normalization: NormalizationOptions| {
+            let mut builder = FuzzyPhraseSetBuilder::memory_with_options(
+                FuzzyPhraseSetBuilderOptions { normalization, ..FuzzyPhraseSetBuilderOptions::default() }
+            ).unwrap();
+            builder.load_word_replacements(vec![WordReplacement::new("Str", "STRASSE")]);
+            builder.insert_str("Unter den Linden Straße", 0).unwrap();
+            builder.insert_str("São Paulo", 1).unwrap();
+            builder.insert_str("Crème Brûlée", 2).unwrap();
+            builder.insert_str("Sao Tome", 3).unwrap();
+            FuzzyPhraseSet::from_bytes(builder.finish_to_bytes().unwrap()).unwrap()
+        };
+        // by default, words are stored exactly as they were inserted
+        let set = build(NormalizationOptions::default());
+        assert!(set.contains_str("Unter den Linden Straße").unwrap());
+        assert!(!set.contains_str("unter den linden strasse").unwrap());
+
+        let set = build(NormalizationOptions { form: Some(NormalizationForm::Nfc), case_fold: true, ..NormalizationOptions::default() });
+        assert!(set.words().contains(&"strasse".to_string()));
+        assert!(set.contains_str("UNTER DEN LINDEN STRASSE").unwrap());
+        assert!(set.contains_str("unter den linden str").unwrap());
+        assert!(set.contains_prefix_str("Unter Den Linden STRA").unwrap());
+        assert!(set.contains_str("Sa\u{0303}o Paulo").unwrap());
+        assert_eq!(set.fuzzy_match_str("SAO PAULO", 1, 1).unwrap(), vec![full_match(&["são", "paulo"], 1, 1)]);
+
+        let set = build(NormalizationOptions { case_fold: true, strip_accents: true, ..NormalizationOptions::default() });
+        assert_eq!(set.fuzzy_match_str("sao paulo", 0, 0).unwrap(), vec![full_match(&["sao", "paulo"], 1, 0)]);
+        assert!(set.contains_str("CRÈME BRULEE").unwrap());
+
+        // keeping accents, but not charging for them
+        let set = build(NormalizationOptions { case_fold: true, accent_edit_distance: Some(0), ..NormalizationOptions::default() });
+        assert_eq!(set.fuzzy_match_str("sao paulo", 0, 0).unwrap(), vec![full_match(&["são", "paulo"], 1, 0)]);
+        assert_eq!(set.fuzzy_match_str("são tome", 0, 0).unwrap(), vec![full_match(&["sao", "tome"], 3, 0)]);
+        // two accent differences are more than one edit allows, but still free
+        assert_eq!(set.fuzzy_match_str("creme brulee", 1, 1).unwrap(), vec![full_match(&["crème", "brûlée"], 2, 0)]);
+        assert_eq!(set.fuzzy_match_prefix_str("creme brulee", 1, 1).unwrap(), vec![full_match(&["crème", "brûlée"], 2, 0)]);
+        // accents can be mixed with real misspellings
+        assert_eq!(set.fuzzy_match_str("sao paolo", 1, 1).unwrap(), vec![full_match(&["são", "paulo"], 1, 1)]);
+        assert!(!set.contains_str("sao paulo").unwrap());
+
+        assert!(FuzzyPhraseSetBuilder::memory_with_options(FuzzyPhraseSetBuilderOptions {
+            normalization: NormalizationOptions { strip_accents: true, accent_edit_distance: Some(0), ..NormalizationOptions::default() },
+            ..FuzzyPhraseSetBuilderOptions::default()
+        }).is_err());
+    }
+
+    #[test]
+    fn glue_display() -> () {
+        let dir = tempfile::tempdir().unwrap();
+        let index = dir.path().join("index");
+        let packed = dir.path().join("index.fzp");
+        let compacted = dir.path().join("compacted");
+
+        let options = FuzzyPhraseSetBuilderOptions {
+            normalization: NormalizationOptions { case_fold: true, ..NormalizationOptions::default() },
+            ..FuzzyPhraseSetBuilderOptions::default()
+        };
+        let mut builder = FuzzyPhraseSetBuilder::new_with_options(&index, options).unwrap();
+        builder.load_word_replacements(vec![WordReplacement::new("street", "st")]);
+        builder.insert_str_with_display("100 Main Street", 0).unwrap();
+        // the same phrase after replacement, with a display string of its own
+        builder.insert_with_display(&["100", "main", "st"], 1, "100 MAIN ST").unwrap();
+        // the same phrase and ID again, so the first display string is kept
+        builder.insert_with_display(&["100", "main", "street"], 1, "100 Main St.").unwrap();
+        // and an ID without one
+        builder.insert_str("100 main st", 3).unwrap();
+        builder.insert_str("200 Main Street", 2).unwrap();
+        builder.finish().unwrap();
+        pack(&index, &packed).unwrap();
+
+        let displays = vec![(0, "100 Main Street".to_string()), (1, "100 MAIN ST".to_string())];
+        let expected = vec![FuzzyMatchResult {
+            phrase: vec!["100".to_string(), "main".to_string(), "st".to_string()],
+            phrase_ids: vec![0, 1, 3],
+            displays: displays.clone(),
+            edit_distance: 0,
+        }];
+        let set = FuzzyPhraseSet::from_path(&index).unwrap();
+        assert_eq!(set.displays(&["100", "MAIN", "street"]), vec![(0, "100 Main Street"), (1, "100 MAIN ST")]);
+        assert_eq!(set.displays(&["200", "main", "st"]), vec![]);
+        assert_eq!(set.fuzzy_match_str("100 main st", 1, 1).unwrap(), expected);
+        assert_eq!(set.fuzzy_match_str("200 main st", 1, 1).unwrap()[0].displays, vec![]);
+        // incomplete phrases don't have any
+        assert_eq!(set.fuzzy_match_prefix_str("100 main", 1, 1).unwrap()[0].displays, vec![]);
+        let windows = set.fuzzy_match_windows(&["at", "100", "main", "st"], 1, 1, false).unwrap();
+        assert_eq!(windows[0].displays, displays);
+        assert_eq!(set.fuzzy_match_multi(&[(vec!["100", "main", "st"], false)], 1, 1).unwrap(), vec![expected.clone()]);
+
+        let packed_set = FuzzyPhraseSet::from_packed_path(&packed).unwrap();
+        assert_eq!(packed_set.fuzzy_match_str("100 main street", 1, 1).unwrap(), expected);
+
+        set.compact(&compacted).unwrap();
+        let compacted_set = FuzzyPhraseSet::from_path(&compacted).unwrap();
+        assert_eq!(compacted_set.fuzzy_match_str("100 main st", 1, 1).unwrap(), expected);
+    }
+
+    #[test]
+    fn glue_alias_graph_files() -> () {
+        // the phonetic and transliteration graphs are both alias graphs, and are written, packed
+        // and checked the same way
+        let dir = tempfile::tempdir().unwrap();
+        let index = dir.path().join("index");
+        let packed = dir.path().join("index.fzp");
+
+        let options = FuzzyPhraseSetBuilderOptions {
+            phonetic_edit_distance: Some(1),
+            transliteration_edit_distance: Some(1),
+            ..FuzzyPhraseSetBuilderOptions::default()
+        };
+        let insert_all = |builder: &mut FuzzyPhraseSetBuilder| {
+            builder.insert_str("philadelphia pa", 0).unwrap();
+            builder.insert_str("улица москва", 1).unwrap();
+        };
+        let mut builder = FuzzyPhraseSetBuilder::memory_with_options(options.clone()).unwrap();
+        insert_all(&mut builder);
+        let bytes = builder.finish_to_bytes().unwrap();
+
+        // building in a directory writes the same graphs
+        let mut builder = FuzzyPhraseSetBuilder::new_with_options(&index, options).unwrap();
+        insert_all(&mut builder);
+        builder.finish().unwrap();
+        assert_eq!(fs::read(index.join("phonetic.fst")).unwrap(), bytes.phonetic_fst.unwrap());
+        assert_eq!(fs::read(index.join("translit.fst")).unwrap(), bytes.transliteration_fst.unwrap());
+
+        pack(&index, &packed).unwrap();
+        assert!(verify(&packed).unwrap().is_ok());
+        for set in &[FuzzyPhraseSet::from_path(&index).unwrap(), FuzzyPhraseSet::from_packed_path(&packed).unwrap()] {
+            assert_eq!(set.fuzzy_match_str("filadelfia pa", 1, 1).unwrap(), vec![full_match(&["philadelphia", "pa"], 0, 1)]);
+            assert_eq!(set.fuzzy_match_str("ulitsa moskva", 1, 2).unwrap(), vec![full_match(&["улица", "москва"], 1, 2)]);
+        }
+
+        // a set built with an alias graph can't be loaded without it
+        for name in &["phonetic.fst", "translit.fst"] {
+            let graph = fs::read(index.join(name)).unwrap();
+            fs::remove_file(index.join(name)).unwrap();
+            assert!(FuzzyPhraseSet::from_path(&index).is_err());
+            assert_eq!(verify(&index).unwrap().problems, vec![IntegrityProblem::MissingSection(name.to_string())]);
+            fs::write(index.join(name), graph).unwrap();
+        }
+    }
+
+    #[test]
+    fn glue_phonetic() -> () {
+        let options = |phonetic_edit_distance: Option<u8>| {
+            FuzzyPhraseSetBuilderOptions { phonetic_edit_distance, ..FuzzyPhraseSetBuilderOptions::default() }
+        };
+        let insert_all = |builder: &mut FuzzyPhraseSetBuilder| {
+            builder.insert_str("philadelphia pa", 0).unwrap();
+            builder.insert_str("schenectady ny", 1).unwrap();
+            builder.insert_str("pittsburgh pa", 2).unwrap();
+        };
+        let build = |phonetic_edit_distance: Option<u8>| {
+            let mut builder = FuzzyPhraseSetBuilder::memory_with_options(options(phonetic_edit_distance)).unwrap();
+            insert_all(&mut builder);
+            builder.finish_to_bytes().unwrap()
+        };
+
+        let set = FuzzyPhraseSet::from_bytes(build(None)).unwrap();
+        assert_eq!(set.fuzzy_match_str("filadelfia pa", 1, 1).unwrap(), vec![]);
+
+        let bytes = build(Some(1));
+        assert!(bytes.phonetic_fst.is_some());
+        let set = FuzzyPhraseSet::from_bytes(bytes).unwrap();
+        assert_eq!(set.fuzzy_match_str("filadelfia pa", 1, 1).unwrap(), vec![full_match(&["philadelphia", "pa"], 0, 1)]);
+        assert_eq!(set.fuzzy_match_str("shenectady ny", 1, 1).unwrap(), vec![full_match(&["schenectady", "ny"], 1, 1)]);
+        assert_eq!(set.fuzzy_match_prefix_str("filadelfia", 1, 1).unwrap()[0].phrase, vec!["philadelphia".to_string()]);
+        // a real misspelling is still cheaper than a phonetic match
+        assert_eq!(set.fuzzy_match_str("pittsburg pa", 1, 1).unwrap(), vec![full_match(&["pittsburgh", "pa"], 2, 1)]);
+        // the query has to allow as many edits as a phonetic match costs
+        assert_eq!(set.fuzzy_match_str("filadelfia pa", 0, 0).unwrap(), vec![]);
+        assert_eq!(set.fuzzy_match_windows(&["in", "filadelfia", "pa"], 1, 1, false).unwrap()[0].phrase_ids, vec![0]);
+    }
+
+    #[test]
+    fn glue_transliteration() -> () {
+        let options = |transliteration_edit_distance: Option<u8>| {
+            FuzzyPhraseSetBuilderOptions { transliteration_edit_distance, ..FuzzyPhraseSetBuilderOptions::default() }
+        };
+        let insert_all = |builder: &mut FuzzyPhraseSetBuilder| {
+            builder.insert_str("улица москва", 0).unwrap();
+            builder.insert_str("οδός αθήνα", 1).unwrap();
+            builder.insert_str("شارع دمشق", 2).unwrap();
+            builder.insert_str("moskva river", 3).unwrap();
+        };
+        let build = |transliteration_edit_distance: Option<u8>| {
+            let mut builder = FuzzyPhraseSetBuilder::memory_with_options(options(transliteration_edit_distance)).unwrap();
+            insert_all(&mut builder);
+            builder.finish_to_bytes().unwrap()
+        };
+
+        let set = FuzzyPhraseSet::from_bytes(build(None)).unwrap();
+        assert_eq!(set.fuzzy_match_str("ulitsa moskva", 1, 2).unwrap(), vec![]);
+
+        let bytes = build(Some(1));
+        assert!(bytes.transliteration_fst.is_some());
+        let set = FuzzyPhraseSet::from_bytes(bytes).unwrap();
+        assert_eq!(set.fuzzy_match_str("ulitsa moskva", 1, 2).unwrap(), vec![full_match(&["улица", "москва"], 0, 2)]);
+        assert_eq!(set.fuzzy_match_str("odos athina", 1, 2).unwrap(), vec![full_match(&["οδός", "αθήνα"], 1, 2)]);
+        // Arabic words match by their consonants, however the query spells the vowels
+        assert_eq!(set.fuzzy_match_str("shari dimashq", 1, 2).unwrap(), vec![full_match(&["شارع", "دمشق"], 2, 2)]);
+        // the Latin word itself is still an exact match
+        assert_eq!(set.fuzzy_match_str("moskva river", 1, 2).unwrap(), vec![full_match(&["moskva", "river"], 3, 0)]);
+        // the query has to allow as many edits as a transliteration costs
+        assert_eq!(set.fuzzy_match_str("ulitsa moskva", 0, 2).unwrap(), vec![]);
+        assert_eq!(set.fuzzy_match_windows(&["in", "ulitsa", "moskva"], 1, 2, false).unwrap()[0].phrase_ids, vec![0]);
+    }
+
+    #[test]
+    fn glue_phrase_replacements() -> () {
+        let dir = tempfile::tempdir().unwrap();
+        let index = dir.path().join("index");
+        let compacted = dir.path().join("compacted");
+
+        let mut builder = FuzzyPhraseSetBuilder::new(&index).unwrap();
+        builder.load_word_replacements(vec![WordReplacement::new("street", "st")]);
+        let phrase_replacement = |from: &str, to: &str| PhraseReplacement {
+            from: from.split(' ').map(|w| w.to_string()).collect(),
+            to: to.split(' ').map(|w| w.to_string()).collect(),
+        };
+        builder.load_phrase_replacements(vec![
+            phrase_replacement("saint louis", "st louis"),
+            phrase_replacement("martin luther king", "mlk"),
+            phrase_replacement("n", "north"),
+        ]).unwrap();
+        assert!(builder.load_phrase_replacements(vec![PhraseReplacement { from: vec![], to: vec!["x".to_string()] }]).is_err());
+        builder.insert_str_with_display("saint louis", 0).unwrap();
+        builder.insert_str("martin luther king blvd", 1).unwrap();
+        builder.insert_str("100 n main street", 2).unwrap();
+        builder.finish().unwrap();
+
+        let set = FuzzyPhraseSet::from_path(&index).unwrap();
+        // phrases are stored rewritten, and either spelling finds them
+        assert!(set.contains_str("saint louis").unwrap());
+        assert!(set.contains_str("st louis").unwrap());
+        assert!(set.contains_str("mlk blvd").unwrap());
+        assert!(set.contains_str("100 north main st").unwrap());
+        assert!(set.contains_prefix_str("martin luther king bl").unwrap());
+        assert_eq!(set.displays(&["st", "louis"]), vec![(0, "saint louis")]);
+        assert_eq!(
+            set.fuzzy_match_str("martin luther king blvd", 1, 1).unwrap(),
+            vec![full_match(&["mlk", "blvd"], 1, 0)]
+        );
+        assert_eq!(
+            set.fuzzy_match_str("100 n main st", 1, 1).unwrap(),
+            vec![full_match(&["100", "north", "main", "st"], 2, 0)]
+        );
+        // the words a rule replaces can be misspelled, at the usual cost
+        assert_eq!(
+            set.fuzzy_match_str("martin lutherr king blvd", 1, 1).unwrap(),
+            vec![full_match(&["mlk", "blvd"], 1, 1)]
+        );
+        assert_eq!(set.fuzzy_match_prefix_str("martin luther king bl", 1, 1).unwrap()[0].phrase, vec!["mlk", "bl"]);
+
+        // windows report where a replaced match was in the query, and can't split a replacement
+        let windows = set.fuzzy_match_windows(&["go", "to", "martin", "luther", "king", "blvd", "now"], 1, 1, false).unwrap();
+        assert_eq!(windows.len(), 1);
+        assert_eq!(windows[0].phrase, vec!["mlk", "blvd"]);
+        assert_eq!(windows[0].start_position, 2);
+        assert_eq!(set.fuzzy_match_windows(&["luther", "king", "blvd"], 1, 1, false).unwrap(), Vec::<FuzzyWindowResult>::new());
+        assert_eq!(set.fuzzy_match_windows(&["mlk", "blvd", "saint", "louis"], 1, 1, false).unwrap().len(), 2);
+
+        assert_eq!(
+            set.fuzzy_match_multi(&[(vec!["martin", "luther", "king", "blvd"], false), (vec!["saint", "louis"], false)], 1, 1).unwrap(),
+            vec![vec![full_match(&["mlk", "blvd"], 1, 0)], vec![FuzzyMatchResult { displays: vec![(0, "saint louis".to_string())], ..full_match(&["st", "louis"], 0, 0) }]]
+        );
+
+        set.compact(&compacted).unwrap();
+        let compacted_set = FuzzyPhraseSet::from_path(&compacted).unwrap();
+        assert_eq!(compacted_set.phrase_replacements(), set.phrase_replacements());
+        assert!(compacted_set.contains_str("martin luther king blvd").unwrap());
+        assert_eq!(compacted_set.phrases().collect::<Vec<_>>(), set.phrases().collect::<Vec<_>>());
+        assert_eq!(compacted_set.displays(&["st", "louis"]), vec![(0, "saint louis")]);
+    }
+
+    #[test]
+    fn glue_phrase_replacement_chains_compact() -> () {
+        let dir = tempfile::tempdir().unwrap();
+        let index = dir.path().join("index");
+        let compacted = dir.path().join("compacted");
+
+        // each rule's output is the other's input, so replacing a stored phrase again would
+        // change it
+        let mut builder = FuzzyPhraseSetBuilder::new(&index).unwrap();
+        let phrase_replacement = |from: &str, to: &str| PhraseReplacement {
+            from: from.split(' ').map(|w| w.to_string()).collect(),
+            to: to.split(' ').map(|w| w.to_string()).collect(),
+        };
+        builder.load_phrase_replacements(vec![
+            phrase_replacement("mt", "mount"),
+            phrase_replacement("mount vernon", "mt vernon"),
+        ]).unwrap();
+        builder.insert_str_with_display("mt vernon", 0).unwrap();
+        builder.insert_str("mt hood", 1).unwrap();
+        builder.finish().unwrap();
+
+        let set = FuzzyPhraseSet::from_path(&index).unwrap();
+        let phrases: Vec<(Vec<&str>, Vec<u64>)> = set.phrases().collect();
+        assert_eq!(phrases, vec![(vec!["mount", "hood"], vec![1]), (vec!["mount", "vernon"], vec![0])]);
+
+        set.compact(&compacted).unwrap();
+        let compacted_set = FuzzyPhraseSet::from_path(&compacted).unwrap();
+        assert_eq!(compacted_set.phrases().collect::<Vec<_>>(), phrases);
+        assert_eq!(compacted_set.displays(&["mt", "vernon"]), vec![(0, "mt vernon")]);
+        assert!(compacted_set.contains_str("mt vernon").unwrap());
+        assert!(compacted_set.contains_str("mt hood").unwrap());
+    }
+
+    #[test]
+    fn glue_scoped_replacements() -> () {
+        let dir = tempfile::tempdir().unwrap();
+        let index = dir.path().join("index");
+        let compacted = dir.path().join("compacted");
+
+        let options = FuzzyPhraseSetBuilderOptions {
+            language: Some("en".to_string()),
+            normalization: NormalizationOptions { case_fold: true, ..NormalizationOptions::default() },
+            ..FuzzyPhraseSetBuilderOptions::default()
+        };
+        let mut builder = FuzzyPhraseSetBuilder::new_with_options(&index, options).unwrap();
+        let scoped = |from: &str, to: &str, scope: ReplacementScope| WordReplacement { scope, ..WordReplacement::new(from, to) };
+        let replacements = vec![
+            scoped("st", "saint", ReplacementScope { position: Some(ReplacementPosition::Start), ..ReplacementScope::default() }),
+            scoped("st", "street", ReplacementScope { position: Some(ReplacementPosition::End), ..ReplacementScope::default() }),
+            scoped("ft", "fort", ReplacementScope { followed_by: vec!["Worth".to_string()], ..ReplacementScope::default() }),
+            scoped("str", "strasse", ReplacementScope { language: Some("de".to_string()), ..ReplacementScope::default() }),
+        ];
+        builder.load_word_replacements(replacements);
+        builder.insert_str("st louis", 0).unwrap();
+        builder.insert_str("main st", 1).unwrap();
+        builder.insert_str("ft worth", 2).unwrap();
+        builder.insert_str("10 ft", 3).unwrap();
+        builder.insert_str("main str", 4).unwrap();
+        builder.finish().unwrap();
+
+        let set = FuzzyPhraseSet::from_path(&index).unwrap();
+        // "st" is "saint" at the start of a phrase and "street" at the end
+        assert!(set.contains_str("saint louis").unwrap());
+        assert!(set.contains_str("st louis").unwrap());
+        assert!(set.contains_str("main street").unwrap());
+        assert!(set.contains_str("main st").unwrap());
+        assert!(!set.contains_str("street louis").unwrap());
+        assert!(!set.contains_str("main saint").unwrap());
+        assert_eq!(set.fuzzy_match_str("st louis", 0, 0).unwrap(), vec![full_match(&["saint", "louis"], 0, 0)]);
+        assert!(set.fuzzy_match_str("main st", 1, 1).unwrap().contains(&full_match(&["main", "street"], 1, 0)));
+
+        // "ft" is only "fort" before "worth", even if that's misspelled
+        assert_eq!(set.fuzzy_match_str("ft worth", 0, 0).unwrap(), vec![full_match(&["fort", "worth"], 2, 0)]);
+        assert_eq!(set.fuzzy_match_str("fort worth", 0, 0).unwrap(), vec![full_match(&["fort", "worth"], 2, 0)]);
+        assert_eq!(set.fuzzy_match_str("ft worht", 1, 1).unwrap(), vec![full_match(&["fort", "worth"], 2, 1)]);
+        assert_eq!(set.fuzzy_match_str("10 ft", 0, 0).unwrap(), vec![full_match(&["10", "ft"], 3, 0)]);
+
+        // replacements for another language don't apply
+        assert_eq!(set.fuzzy_match_str("main str", 0, 0).unwrap(), vec![full_match(&["main", "str"], 4, 0)]);
+        assert!(!set.contains_str("main strasse").unwrap());
+
+        // with a prefix, the last word could be followed by anything
+        assert!(set.contains_prefix_str("st lou").unwrap());
+        assert!(set.contains_prefix_str("main st").unwrap());
+        assert_eq!(set.fuzzy_match_prefix_str("st lo", 0, 0).unwrap()[0].phrase, vec!["saint", "lo"]);
+
+        // windows and multi-searches can start and end partway through the query
+        let windows = set.fuzzy_match_windows(&["visit", "st", "louis", "today"], 0, 0, false).unwrap();
+        assert_eq!(windows.len(), 1);
+        assert_eq!(windows[0].phrase, vec!["saint", "louis"]);
+        assert_eq!(windows[0].start_position, 1);
+        let windows = set.fuzzy_match_windows(&["park", "on", "main", "st"], 0, 0, false).unwrap();
+        assert_eq!(windows.len(), 1);
+        assert_eq!(windows[0].phrase, vec!["main", "street"]);
+        assert_eq!(
+            set.fuzzy_match_multi(&[(vec!["st", "louis"], false), (vec!["main", "st"], false)], 0, 0).unwrap(),
+            vec![vec![full_match(&["saint", "louis"], 0, 0)], vec![full_match(&["main", "street"], 1, 0)]]
+        );
+
+        // the scopes (with their words normalized) and the language are kept in the metadata,
+        // and survive compaction
+        assert_eq!(set.options().language, Some("en".to_string()));
+        assert_eq!(set.word_replacements()[2].scope.followed_by, vec!["worth".to_string()]);
+        set.compact(&compacted).unwrap();
+        let compacted_set = FuzzyPhraseSet::from_path(&compacted).unwrap();
+        assert_eq!(compacted_set.word_replacements(), set.word_replacements());
+        assert!(compacted_set.contains_str("st louis").unwrap());
+        assert!(compacted_set.contains_str("main str").unwrap());
+    }
+
+    #[test]
+    fn glue_query_replacements() -> () {
+        let dir = tempfile::tempdir().unwrap();
+        let mut builder = FuzzyPhraseSetBuilder::new(&dir.path()).unwrap();
+        builder.load_word_replacements(vec![WordReplacement::new("street", "st")]);
+        builder.insert_str("100 main street", 0).unwrap();
+        builder.insert_str("fort worth", 1).unwrap();
+        builder.finish().unwrap();
+
+        let mut set = FuzzyPhraseSet::from_path(&dir.path()).unwrap();
+        assert!(!set.contains_str("100 main str").unwrap());
+
+        // neither "str" nor "ft" is in the lexicon, and "street" goes on to be replaced by "st"
+        set.load_query_replacements(vec![
+            WordReplacement::new("str", "street"),
+            WordReplacement::new("ft", "fort"),
+            WordReplacement { scope: ReplacementScope { language: Some("de".to_string()), ..ReplacementScope::default() }, ..WordReplacement::new("fort", "ft") },
+        ]).unwrap();
+        assert!(set.contains_str("100 main str").unwrap());
+        assert!(set.contains_prefix_str("ft wo").unwrap());
+        assert_eq!(set.fuzzy_match_str("100 main str", 1, 1).unwrap(), vec![full_match(&["100", "main", "st"], 0, 0)]);
+        assert_eq!(set.fuzzy_match_str("ft wroth", 1, 1).unwrap(), vec![full_match(&["fort", "worth"], 1, 1)]);
+        assert_eq!(set.fuzzy_match_str("fort worth", 1, 1).unwrap(), vec![full_match(&["fort", "worth"], 1, 0)]);
+        let windows = set.fuzzy_match_windows(&["go", "to", "ft", "worth"], 1, 1, false).unwrap();
+        assert_eq!(windows.len(), 1);
+        assert_eq!(windows[0].phrase, vec!["fort", "worth"]);
+        assert_eq!(windows[0].start_position, 2);
+
+        let position = ReplacementScope { position: Some(ReplacementPosition::End), ..ReplacementScope::default() };
+        assert!(set.load_query_replacements(vec![WordReplacement { scope: position, ..WordReplacement::new("n", "north") }]).is_err());
+
+        set.clear_query_replacements();
+        assert!(!set.contains_str("100 main str").unwrap());
+    }
+
+    #[test]
+    fn glue_query_replacement_prefix() -> () {
+        let dir = tempfile::tempdir().unwrap();
+        let mut builder = FuzzyPhraseSetBuilder::new(&dir.path()).unwrap();
+        builder.insert_str("100 main strand", 0).unwrap();
+        builder.insert_str("100 main street", 1).unwrap();
+        builder.finish().unwrap();
+
+        let mut set = FuzzyPhraseSet::from_path(&dir.path()).unwrap();
+        set.load_query_replacements(vec![WordReplacement::new("str", "street")]).unwrap();
+
+        // a finished "str" is "street", but an unfinished one could still become "strand"
+        let as_strings = |words: &[&str]| words.iter().map(|w| w.to_string()).collect::<Vec<String>>();
+        assert_eq!(set.fuzzy_match_prefix_str("100 main str", 0, 0).unwrap(), vec![
+            FuzzyMatchResult { phrase: as_strings(&["100", "main", "street"]), phrase_ids: vec![1], displays: vec![], edit_distance: 0 },
+            FuzzyMatchResult { phrase: as_strings(&["100", "main", "str"]), phrase_ids: vec![], displays: vec![], edit_distance: 0 },
+        ]);
+        let mut completions: Vec<Vec<String>> = set.fuzzy_match_prefix_completions(&["100", "main", "str"], 0, 0, 10).unwrap()
+            .into_iter().map(|r| r.phrase).collect();
+        completions.sort();
+        assert_eq!(completions, vec![as_strings(&["100", "main", "strand"]), as_strings(&["100", "main", "street"])]);
+        assert!(set.contains_prefix_str("100 main stra").unwrap());
+    }
+
+    #[test]
+    fn glue_edit_distance_policy() -> () {
+        let dir = tempfile::tempdir().unwrap();
+        let options = FuzzyPhraseSetBuilderOptions {
+            max_edit_distance: 2,
+            edit_distance_policy: EditDistancePolicy::ByLength(vec![
+                EditDistanceTier { max_length: 3, edit_distance: 0 },
+                EditDistanceTier { max_length: 7, edit_distance: 1 },
+            ]),
+            ..FuzzyPhraseSetBuilderOptions::default()
+        };
+        let mut builder = FuzzyPhraseSetBuilder::new_with_options(&dir.path(), options).unwrap();
+        builder.insert_str("fox hill boulevard", 0).unwrap();
+        builder.insert_str("fox hills road", 1).unwrap();
+        builder.finish().unwrap();
+
+        let set = FuzzyPhraseSet::from_path(&dir.path()).unwrap();
+
+        // three-letter words get no edits, even if the query allows them
+        assert_eq!(set.fuzzy_match_str("fix hill boulevard", 2, 2).unwrap(), vec![]);
+        assert_eq!(set.fuzzy_match_prefix_str("fix hill boul", 2, 2).unwrap(), vec![]);
+        // medium-length words get one edit...
+        assert_eq!(
+            set.fuzzy_match_str("fox hlil boulevard", 2, 2).unwrap(),
+            vec![FuzzyMatchResult { phrase: vec!["fox".to_string(), "hill".to_string(), "boulevard".to_string()], phrase_ids: vec![0], displays: vec![], edit_distance: 1 }]
+        );
+        assert_eq!(set.fuzzy_match_str("fox hl boulevard", 2, 2).unwrap(), vec![]);
+        // ...and long words get whatever the query allows
+        assert_eq!(
+            set.fuzzy_match_windows(&["fox", "hill", "boulevdr"], 2, 2, false).unwrap(),
+            vec![FuzzyWindowResult { phrase: vec!["fox".to_string(), "hill".to_string(), "boulevard".to_string()], phrase_ids: vec![0], displays: vec![], edit_distance: 2, start_position: 0, ends_in_prefix: false }]
+        );
+        assert_eq!(set.fuzzy_match_windows(&["fox", "hill", "boulevdr"], 1, 1, false).unwrap(), Vec::<FuzzyWindowResult>::new());
+        assert_eq!(
+            set.fuzzy_match_multi(&[(vec!["fox", "hill", "boulevdr"], false), (vec!["fix", "hills"], true)], 2, 2).unwrap(),
+            vec![
+                vec![FuzzyMatchResult { phrase: vec!["fox".to_string(), "hill".to_string(), "boulevard".to_string()], phrase_ids: vec![0], displays: vec![], edit_distance: 2 }],
+                vec![],
+            ]
+        );
+
+        // tiers have to be in order and within the index's maximum
+        assert!(FuzzyPhraseSetBuilder::new_with_options(&dir.path(), FuzzyPhraseSetBuilderOptions {
+            edit_distance_policy: EditDistancePolicy::ByLength(vec![
+                EditDistanceTier { max_length: 7, edit_distance: 1 },
+                EditDistanceTier { max_length: 3, edit_distance: 0 },
+            ]),
+            ..FuzzyPhraseSetBuilderOptions::default()
+        }).is_err());
+        assert!(FuzzyPhraseSetBuilder::new_with_options(&dir.path(), FuzzyPhraseSetBuilderOptions {
+            edit_distance_policy: EditDistancePolicy::ByLength(vec![
+                EditDistanceTier { max_length: 3, edit_distance: 2 },
+            ]),
+            ..FuzzyPhraseSetBuilderOptions::default()
+        }).is_err());
+    }
+
+    #[test]
+    fn glue_duplicate_phrase_ids() -> () {
+        let dir = tempfile::tempdir().unwrap();
+        let mut builder = FuzzyPhraseSetBuilder::new(&dir.path()).unwrap();
+        builder.insert_str("main street", 5).unwrap();
+        builder.insert_str("main street", 2).unwrap();
+        builder.insert_str("main street", 5).unwrap();
+        builder.insert_str("main avenue", 3).unwrap();
+        assert!(builder.insert_str("main road", 1 << 63).is_err());
+        builder.finish().unwrap();
+
+        let set = FuzzyPhraseSet::from_path(&dir.path()).unwrap();
+        assert_eq!(
+            set.fuzzy_match_str("main street", 1, 1).unwrap(),
+            vec![
+                FuzzyMatchResult { phrase: vec!["main".to_string(), "street".to_string()], phrase_ids: vec![2, 5], displays: vec![], edit_distance: 0 },
+            ]
+        );
+        assert_eq!(
+            set.fuzzy_match_windows(&["main", "avenu"], 1, 1, false).unwrap(),
+            vec![
+                FuzzyWindowResult { phrase: vec!["main".to_string(), "avenue".to_string()], phrase_ids: vec![3], displays: vec![], edit_distance: 1, start_position: 0, ends_in_prefix: false },
+            ]
+        );
+    }
+
+    #[test]
+    fn glue_fuzzy_prefix_misspelled() -> () {
+        let dir = tempfile::tempdir().unwrap();
+        let mut builder = FuzzyPhraseSetBuilder::new(&dir.path()).unwrap();
+        builder.insert_str("100 main street", 0).unwrap();
+        builder.insert_str("100 main stravenue", 1).unwrap();
+        builder.insert_str("100 main stop", 2).unwrap();
+        builder.finish().unwrap();
+
+        let set = FuzzyPhraseSet::from_path(&dir.path()).unwrap();
+
+        // "strre" is an incomplete, misspelled "street"; like the correctly spelled "stre", it's
+        // still a prefix, even though only one word starts with "stre"
+        assert_eq!(
+            set.fuzzy_match_prefix_str("100 main strre", 1, 1).unwrap(),
+            vec![
+                FuzzyMatchResult { phrase: vec!["100".to_string(), "main".to_string(), "strre".to_string()], phrase_ids: vec![], displays: vec![], edit_distance: 1 },
+            ]
+        );
+        // a misspelled prefix that could still go several ways comes back as typed
+        assert_eq!(
+            set.fuzzy_match_prefix_str("100 main sttr", 1, 1).unwrap(),
+            vec![
+                FuzzyMatchResult { phrase: vec!["100".to_string(), "main".to_string(), "sttr".to_string()], phrase_ids: vec![], displays: vec![], edit_distance: 1 },
+            ]
+        );
+        // but shorter ones are too ambiguous to look for, as are ones with a different first letter
+        assert_eq!(set.fuzzy_match_prefix_str("100 main sta", 1, 1).unwrap(), vec![]);
+        assert_eq!(set.fuzzy_match_prefix_str("100 main ptre", 1, 1).unwrap(), vec![]);
+        // misspelled prefixes count against the phrase budget
+        assert_eq!(set.fuzzy_match_prefix_str("100 mian strre", 1, 1).unwrap(), vec![]);
+        assert_eq!(
+            set.fuzzy_match_windows(&["100", "main", "strre"], 1, 1, true).unwrap(),
+            vec![
+                FuzzyWindowResult { phrase: vec!["100".to_string(), "main".to_string(), "strre".to_string()], phrase_ids: vec![], displays: vec![], edit_distance: 1, start_position: 0, ends_in_prefix: true },
+            ]
+        );
+        assert_eq!(
+            set.fuzzy_match_multi(&[(vec!["100", "main", "strre"], true), (vec!["100", "main", "strre"], false)], 1, 1).unwrap(),
+            vec![
+                vec![FuzzyMatchResult { phrase: vec!["100".to_string(), "main".to_string(), "strre".to_string()], phrase_ids: vec![], displays: vec![], edit_distance: 1 }],
+                vec![],
+            ]
+        );
+    }
+
+    #[test]
+    fn glue_scored_results() -> () {
+        let dir = tempfile::tempdir().unwrap();
+        let mut builder = FuzzyPhraseSetBuilder::new(&dir.path()).unwrap();
+        builder.insert_str("main streat", 0).unwrap();
+        builder.insert_str("mainz street", 1).unwrap();
+        builder.insert_str("mainz stravenue", 2).unwrap();
+        builder.insert_str("mainz", 3).unwrap();
+        builder.finish().unwrap();
+
+        let set = FuzzyPhraseSet::from_path(&dir.path()).unwrap();
+        let scorer = DefaultScorer::default();
+
+        // both are one edit away, but the one with the correction in the first word ranks lower
+        let sorted = set.fuzzy_match_scored(&["mainz", "streat"], 1, 1, &scorer, true).unwrap();
+        assert_eq!(
+            sorted.iter().map(|r| r.result.phrase_ids.clone()).collect::<Vec<_>>(),
+            vec![vec![1], vec![0]]
+        );
+        assert_eq!(sorted[0].features.corrected_positions(), vec![1]);
+        assert_eq!(sorted[1].features.corrected_positions(), vec![0]);
+        assert!(sorted[0].score > sorted[1].score);
+        assert_eq!(
+            sorted.iter().map(|r| r.result.clone()).collect::<Vec<_>>(),
+            set.fuzzy_match(&["mainz", "streat"], 1, 1).unwrap()
+        );
+
+        // prefixes know how much is left to type
+        let prefixed = set.fuzzy_match_prefix_scored(&["mainz", "str"], 1, 1, &scorer, true).unwrap();
+        assert_eq!(
+            prefixed.iter().map(|r| r.result.phrase.clone()).collect::<Vec<_>>(),
+            vec![vec!["mainz".to_string(), "str".to_string()], vec!["main".to_string(), "str".to_string()]]
+        );
+        assert!(prefixed[0].features.ends_in_prefix);
+        assert_eq!(prefixed[0].features.prefix_completion_length, 6);
+
+        // windows come back shortest-first from the phrase graph, but the longer one scores higher
+        let unsorted = set.fuzzy_match_windows_scored(&["the", "mainz", "street"], 1, 1, false, &scorer, false).unwrap();
+        assert_eq!(
+            unsorted.iter().map(|r| (r.result.phrase_ids.clone(), r.result.start_position)).collect::<Vec<_>>(),
+            vec![(vec![3], 1), (vec![1], 1)]
+        );
+        let windows = set.fuzzy_match_windows_scored(&["the", "mainz", "street"], 1, 1, false, &scorer, true).unwrap();
+        assert_eq!(
+            windows.iter().map(|r| (r.result.phrase_ids.clone(), r.result.start_position)).collect::<Vec<_>>(),
+            vec![(vec![1], 1), (vec![3], 1)]
+        );
+
+        let multi = set.fuzzy_match_multi_scored(&[(vec!["mainz", "streat"], false)], 1, 1, &scorer, true).unwrap();
+        assert_eq!(multi[0], sorted);
+    }
+
+    #[test]
+    fn glue_limited_results() -> () {
+        let dir = tempfile::tempdir().unwrap();
+        let mut builder = FuzzyPhraseSetBuilder::new(&dir.path()).unwrap();
+        builder.insert_str("100 main street", 0).unwrap();
+        builder.insert_str("100 maine street", 1).unwrap();
+        builder.insert_str("100 main stravenue", 2).unwrap();
+        builder.insert_str("100 mains street", 3).unwrap();
+        builder.finish().unwrap();
+
+        let set = FuzzyPhraseSet::from_path(&dir.path()).unwrap();
+
+        // the exact match comes first, even though it isn't first in the phrase graph
+        assert_eq!(
+            set.fuzzy_match_limited(&["100", "mains", "street"], 1, 1, 1).unwrap(),
+            vec![
+                FuzzyMatchResult { phrase: vec!["100".to_string(), "mains".to_string(), "street".to_string()], phrase_ids: vec![3], displays: vec![], edit_distance: 0 },
+            ]
+        );
+        assert_eq!(set.fuzzy_match_limited(&["100", "mains", "street"], 1, 1, 10).unwrap().len(), 3);
+        assert_eq!(
+            set.fuzzy_match_prefix_limited(&["100", "maine", "str"], 1, 1, 1).unwrap(),
+            vec![
+                FuzzyMatchResult { phrase: vec!["100".to_string(), "maine".to_string(), "str".to_string()], phrase_ids: vec![], displays: vec![], edit_distance: 0 },
+            ]
+        );
+        let windows = set.fuzzy_match_windows_limited(&["at", "100", "mains", "st"], 1, 1, true, 2).unwrap();
+        assert_eq!(windows.iter().map(|r| r.edit_distance).collect::<Vec<_>>(), vec![0, 1]);
+        assert_eq!(windows[0].phrase, vec!["100".to_string(), "mains".to_string(), "st".to_string()]);
+    }
+
+    #[test]
+    fn glue_prefix_completions() -> () {
+        assert_eq!(
+            SET.fuzzy_match_prefix_completions(&["100", "main", "s"], 1, 1, 10).unwrap(),
+            vec![
+                FuzzyMatchResult { phrase: vec!["100".to_string(), "main".to_string(), "street".to_string()], phrase_ids: vec![0], displays: vec![], edit_distance: 0 },
+            ]
+        );
+        assert_eq!(
+            SET.fuzzy_match_prefix_completions(&["100", "main"], 1, 1, 10).unwrap(),
+            vec![
+                FuzzyMatchResult { phrase: vec!["100".to_string(), "main".to_string(), "ave".to_string()], phrase_ids: vec![2], displays: vec![], edit_distance: 0 },
+                FuzzyMatchResult { phrase: vec!["100".to_string(), "main".to_string(), "street".to_string()], phrase_ids: vec![0], displays: vec![], edit_distance: 0 },
+            ]
+        );
+        assert_eq!(
+            SET.fuzzy_match_prefix_completions(&["100", "main"], 1, 1, 1).unwrap(),
+            vec![
+                FuzzyMatchResult { phrase: vec!["100".to_string(), "main".to_string(), "ave".to_string()], phrase_ids: vec![2], displays: vec![], edit_distance: 0 },
+            ]
+        );
+        // completions of corrected matches come after the exact ones
+        assert_eq!(
+            SET.fuzzy_match_prefix_completions(&["200", "man"], 1, 1, 10).unwrap(),
+            vec![
+                FuzzyMatchResult { phrase: vec!["200".to_string(), "main".to_string(), "street".to_string()], phrase_ids: vec![1], displays: vec![], edit_distance: 1 },
+            ]
+        );
+        assert_eq!(SET.fuzzy_match_prefix_completions(&["400", "main"], 1, 1, 10).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn glue_continuations() -> () {
+        let dir = tempfile::tempdir().unwrap();
+        let mut builder = FuzzyPhraseSetBuilder::new(&dir.path()).unwrap();
+        builder.insert_str("100 main street", 0).unwrap();
+        builder.insert_str("100 main street north", 1).unwrap();
+        builder.insert_str("100 main street south", 2).unwrap();
+        builder.insert_str("100 main avenue", 3).unwrap();
+        builder.insert_str("100 maine road", 4).unwrap();
+        builder.finish().unwrap();
+
+        let set = FuzzyPhraseSet::from_path(&dir.path()).unwrap();
+
+        let words = |results: Vec<FuzzyMatchResult>| results.into_iter().map(
+            |r| (r.phrase.join(" "), r.phrase_ids, r.edit_distance)
+        ).collect::<Vec<_>>();
+
+        assert_eq!(
+            words(set.fuzzy_match_continuations(&["100", "main"], 1, 1, 10, ContinuationOrder::Lexicographic).unwrap()),
+            vec![
+                ("100 main avenue".to_string(), vec![3], 0),
+                ("100 main street".to_string(), vec![0], 0),
+                ("100 maine road".to_string(), vec![4], 1),
+            ]
+        );
+        assert_eq!(
+            words(set.fuzzy_match_continuations(&["100", "main"], 1, 1, 1, ContinuationOrder::MostPhrases).unwrap()),
+            vec![("100 main street".to_string(), vec![0], 0)]
+        );
+        // "100 main street" is a phrase in its own right, but there are still words after it
+        assert_eq!(
+            words(set.fuzzy_match_continuations(&["100", "main", "street"], 0, 0, 10, ContinuationOrder::Lexicographic).unwrap()),
+            vec![
+                ("100 main street north".to_string(), vec![1], 0),
+                ("100 main street south".to_string(), vec![2], 0),
+            ]
+        );
+        assert_eq!(set.fuzzy_match_continuations(&["100", "main", "avenue"], 1, 1, 10, ContinuationOrder::Lexicographic).unwrap(), vec![]);
+    }
+
+    #[test]
+    fn glue_contents() -> () {
+        assert_eq!(SET.words(), &["100", "200", "300", "ave", "blvd", "main", "mlk", "street"]);
+        assert_eq!(
+            SET.phrases().map(|(phrase, ids)| (phrase.join(" "), ids)).collect::<Vec<_>>(),
+            vec![
+                ("100 main ave".to_string(), vec![2]),
+                ("100 main street".to_string(), vec![0]),
+                ("200 main street".to_string(), vec![1]),
+                ("300 mlk blvd".to_string(), vec![3]),
+            ]
+        );
+        assert_eq!(SET.options(), FuzzyPhraseSetBuilderOptions::default());
+        assert_eq!(SET.word_replacements(), &[]);
+    }
+
+    #[test]
+    fn glue_in_memory() -> () {
+        let insert_all = |builder: &mut FuzzyPhraseSetBuilder| {
+            builder.insert_str("100 main street", 0).unwrap();
+            builder.insert_str("200 main street", 1).unwrap();
+            builder.insert_str("100 main ave", 2).unwrap();
+            builder.insert_str("300 mlk blvd", 3).unwrap();
+        };
+        let mut builder = FuzzyPhraseSetBuilder::memory().unwrap();
+        insert_all(&mut builder);
+        let bytes = builder.finish_to_bytes().unwrap();
+
+        // building in a directory streams out the same files
+        let dir = tempfile::tempdir().unwrap();
+        let mut builder = FuzzyPhraseSetBuilder::new(&dir.path()).unwrap();
+        insert_all(&mut builder);
+        builder.finish().unwrap();
+        assert_eq!(fs::read(dir.path().join("prefix.fst")).unwrap(), bytes.prefix_fst);
+        assert_eq!(fs::read(dir.path().join("phrase.fst")).unwrap(), bytes.phrase_fst);
+        assert_eq!(fs::read(dir.path().join("phrase.msg")).unwrap(), bytes.phrase_msg);
+        assert_eq!(fs::read(dir.path().join("fuzzy.fst")).unwrap(), bytes.fuzzy_fst);
+        assert_eq!(fs::read(dir.path().join("fuzzy.msg")).unwrap(), bytes.fuzzy_msg);
+        assert_eq!(fs::read(dir.path().join("metadata.json")).unwrap(), bytes.metadata_json);
+
+        let mut set = FuzzyPhraseSet::from_bytes(bytes).unwrap();
+
+        assert!(set.contains_str("100 main street").unwrap());
+        assert!(set.contains_prefix_str("300 mlk b").unwrap());
+        assert_eq!(set.fuzzy_match_str("100 man street", 1, 1).unwrap(), SET.fuzzy_match_str("100 man street", 1, 1).unwrap());
+        assert_eq!(set.fuzzy_match_prefix_str("200 man s", 1, 1).unwrap(), SET.fuzzy_match_prefix_str("200 man s", 1, 1).unwrap());
+
+        // deletions still work, but only last as long as the set does
+        assert!(set.delete_str("300 mlk blvd").unwrap());
+        assert!(!set.contains_str("300 mlk blvd").unwrap());
+
+        // there's nowhere for an in-memory builder to write to
+        assert!(FuzzyPhraseSetBuilder::memory().unwrap().finish().is_err());
+    }
+
+    #[test]
+    fn glue_tombstones() -> () {
+        let dir = tempfile::tempdir().unwrap();
+        let compacted = tempfile::tempdir().unwrap();
+        let mut builder = FuzzyPhraseSetBuilder::new(&dir.path()).unwrap();
+        builder.insert_str("100 main street", 0).unwrap();
+        builder.insert_str("200 main street", 1).unwrap();
+        builder.insert_str("100 main ave", 2).unwrap();
+        builder.insert_str("300 mlk blvd", 3).unwrap();
+        builder.insert_str("100 main streets", 4).unwrap();
+        builder.insert_str("500 elm row", 5).unwrap();
+        builder.finish().unwrap();
+
+        let mut set = FuzzyPhraseSet::from_path(&dir.path()).unwrap();
+        assert!(set.delete_str("100 main street").unwrap());
+        assert!(set.delete_str("300 mlk blvd").unwrap());
+        // already deleted, or never there
+        assert!(!set.delete_str("100 main street").unwrap());
+        assert!(!set.delete_str("100 main").unwrap());
+        assert!(!set.delete_str("400 main street").unwrap());
+        assert_eq!(set.deleted_len(), 2);
+
+        // a deletion that can't be recorded doesn't happen, so it can be tried again
+        fs::create_dir(dir.path().join("deleted.msg.tmp")).unwrap();
+        assert!(set.delete_str("500 elm row").is_err());
+        assert_eq!(set.deleted_len(), 2);
+        assert!(FuzzyPhraseSet::from_path(&dir.path()).unwrap().contains_str("500 elm row").unwrap());
+        fs::remove_dir(dir.path().join("deleted.msg.tmp")).unwrap();
+        assert!(set.delete_str("500 elm row").unwrap());
+        assert!(!FuzzyPhraseSet::from_path(&dir.path()).unwrap().contains_str("500 elm row").unwrap());
+        assert!(!dir.path().join("deleted.msg.tmp").exists());
+        assert_eq!(set.deleted_len(), 3);
+
+        // deletions are recorded on disk as they happen
+        let set = FuzzyPhraseSet::from_path(&dir.path()).unwrap();
+        assert!(!set.contains_str("100 main street").unwrap());
+        assert!(set.contains_str("200 main street").unwrap());
+        assert!(set.contains_prefix_str("100 main s").unwrap());
+        assert!(!set.contains_prefix_str("300").unwrap());
+        assert!(!set.contains_prefix_str("300 mlk b").unwrap());
+        assert!(set.contains_prefix_str("100 main").unwrap());
+
+        let phrases = |results: Vec<FuzzyMatchResult>| results.into_iter().map(|r| r.phrase.join(" ")).collect::<Vec<_>>();
+        assert_eq!(phrases(set.fuzzy_match_str("100 main street", 1, 1).unwrap()), vec!["100 main streets"]);
+        // the deleted exact match would have been the best result
+        assert_eq!(phrases(set.fuzzy_match_limited(&["100", "main", "street"], 1, 1, 1).unwrap()), vec!["100 main streets"]);
+        assert_eq!(phrases(set.fuzzy_match_prefix_str("300 mlk b", 1, 1).unwrap()), Vec::<String>::new());
+        assert_eq!(
+            phrases(set.fuzzy_match_prefix_completions(&["100", "main"], 1, 1, 10).unwrap()),
+            vec!["100 main ave", "100 main streets"]
+        );
+        assert_eq!(
+            phrases(set.fuzzy_match_continuations(&["100", "main"], 0, 0, 10, ContinuationOrder::Lexicographic).unwrap()),
+            vec!["100 main ave", "100 main streets"]
+        );
+        assert_eq!(set.fuzzy_match_windows(&["at", "300", "mlk", "blvd"], 1, 1, false).unwrap().len(), 0);
+        assert_eq!(
+            set.fuzzy_match_multi(&[(vec!["100", "main", "street"], false), (vec!["100", "main", "av"], true)], 1, 1).unwrap()
+                .into_iter().map(phrases).collect::<Vec<_>>(),
+            vec![vec!["100 main streets"], vec!["100 main av"]]
+        );
+
+        set.compact(&compacted.path()).unwrap();
+        let compacted_set = FuzzyPhraseSet::from_path(&compacted.path()).unwrap();
+        assert_eq!(compacted_set.deleted_len(), 0);
+        assert!(!compacted_set.contains_str("100 main street").unwrap());
+        assert!(compacted_set.contains_str("100 main ave").unwrap());
+        assert_eq!(compacted_set.word_list, vec!["100", "200", "ave", "main", "street", "streets"]);
+    }
+
     #[test]
     fn glue_fuzzy_match_prefix() -> () {
         assert_eq!(
             SET.fuzzy_match_prefix(&["100", "man"], 1, 1).unwrap(),
             vec![
-                FuzzyMatchResult { phrase: vec!["100".to_string(), "main".to_string()], edit_distance: 1 },
+                FuzzyMatchResult { phrase: vec!["100".to_string(), "main".to_string()], phrase_ids: vec![], displays: vec![], edit_distance: 1 },
             ]
         );
 
         assert_eq!(
             SET.fuzzy_match_prefix(&["100", "man", "str"], 1, 1).unwrap(),
             vec![
-                FuzzyMatchResult { phrase: vec!["100".to_string(), "main".to_string(), "str".to_string()], edit_distance: 1 },
+                FuzzyMatchResult { phrase: vec!["100".to_string(), "main".to_string(), "str".to_string()], phrase_ids: vec![], displays: vec![], edit_distance: 1 },
             ]
         );
 
@@ -1054,15 +3551,15 @@ mod basic_tests {
         assert_eq!(
             SET.fuzzy_match_windows(&["100", "main", "street", "washington", "300"], 1, 1, true).unwrap(),
             vec![
-                FuzzyWindowResult { phrase: vec!["100".to_string(), "main".to_string(), "street".to_string()], edit_distance: 0, start_position: 0, ends_in_prefix: false },
-                FuzzyWindowResult { phrase: vec!["300".to_string()], edit_distance: 0, start_position: 4, ends_in_prefix: true }
+                FuzzyWindowResult { phrase: vec!["100".to_string(), "main".to_string(), "street".to_string()], phrase_ids: vec![0], displays: vec![], edit_distance: 0, start_position: 0, ends_in_prefix: false },
+                FuzzyWindowResult { phrase: vec!["300".to_string()], phrase_ids: vec![], displays: vec![], edit_distance: 0, start_position: 4, ends_in_prefix: true }
             ]
         );
 
         assert_eq!(
             SET.fuzzy_match_windows(&["100", "main", "street", "washington", "300"], 1, 1, false).unwrap(),
             vec![
-                FuzzyWindowResult { phrase: vec!["100".to_string(), "main".to_string(), "street".to_string()], edit_distance: 0, start_position: 0, ends_in_prefix: false },
+                FuzzyWindowResult { phrase: vec!["100".to_string(), "main".to_string(), "street".to_string()], phrase_ids: vec![0], displays: vec![], edit_distance: 0, start_position: 0, ends_in_prefix: false },
             ]
         );
     }
@@ -1081,10 +3578,10 @@ mod basic_tests {
             vec![
                 vec![],
                 vec![],
-                vec![FuzzyMatchResult { phrase: vec!["100".to_string(), "main".to_string(), "street".to_string()], edit_distance: 0 }],
+                vec![FuzzyMatchResult { phrase: vec!["100".to_string(), "main".to_string(), "street".to_string()], phrase_ids: vec![0], displays: vec![], edit_distance: 0 }],
                 vec![],
                 vec![],
-                vec![FuzzyMatchResult { phrase: vec!["300".to_string(), "mlk".to_string(), "blvd".to_string()], edit_distance: 0 }]
+                vec![FuzzyMatchResult { phrase: vec!["300".to_string(), "mlk".to_string(), "blvd".to_string()], phrase_ids: vec![3], displays: vec![], edit_distance: 0 }]
             ]
         );
     }
@@ -1093,13 +3590,13 @@ mod basic_tests {
         static ref DIRECTORY: tempfile::TempDir = tempfile::tempdir().unwrap();
         static ref TEST_SET: FuzzyPhraseSet = {
             let mut builder = FuzzyPhraseSetBuilder::new(&DIRECTORY.path()).unwrap();
-            builder.insert_str("100 main street").unwrap();
-            builder.insert_str("100 main st").unwrap();
-            builder.insert_str("St Elizabeth").unwrap();
-            builder.insert_str("100 st washington").unwrap();
-            builder.insert_str("washington st").unwrap();
-            builder.insert_str("100 d st").unwrap();
-            builder.insert_str("100 e st").unwrap();
+            builder.insert_str("100 main street", 0).unwrap();
+            builder.insert_str("100 main st", 1).unwrap();
+            builder.insert_str("St Elizabeth", 2).unwrap();
+            builder.insert_str("100 st washington", 3).unwrap();
+            builder.insert_str("washington st", 4).unwrap();
+            builder.insert_str("100 d st", 5).unwrap();
+            builder.insert_str("100 e st", 6).unwrap();
             builder.finish().unwrap();
 
             FuzzyPhraseSet::from_path(&DIRECTORY.path()).unwrap()
@@ -1112,7 +3609,7 @@ mod basic_tests {
         //address present in the data, hence should match
         assert_eq!(
             TEST_SET.fuzzy_match_windows(&["100", "main", "street"], 1, 1, true).unwrap(),
-            vec![FuzzyWindowResult { phrase: vec!["100".to_string(), "main".to_string(), "street".to_string()], edit_distance: 0, start_position: 0, ends_in_prefix: true }]
+            vec![FuzzyWindowResult { phrase: vec!["100".to_string(), "main".to_string(), "street".to_string()], phrase_ids: vec![0], displays: vec![], edit_distance: 0, start_position: 0, ends_in_prefix: true }]
         );
         //address not present in the data, hence should not match
         assert_eq!(
@@ -1123,22 +3620,22 @@ mod basic_tests {
         assert_eq!(
             TEST_SET.fuzzy_match_windows(&["100", "main", "st"], 1, 1, false).unwrap(),
             vec![
-                FuzzyWindowResult { phrase: vec!["100".to_string(), "main".to_string(), "st".to_string()], edit_distance: 0, start_position: 0, ends_in_prefix: false }
+                FuzzyWindowResult { phrase: vec!["100".to_string(), "main".to_string(), "st".to_string()], phrase_ids: vec![1], displays: vec![], edit_distance: 0, start_position: 0, ends_in_prefix: false }
             ]
         );
         //address contains words in another address
         assert_eq!(
             TEST_SET.fuzzy_match_windows(&["100", "st", "washington"], 1, 1, false).unwrap(),
             vec![
-                FuzzyWindowResult { phrase: vec!["100".to_string(), "st".to_string(), "washington".to_string()], edit_distance: 0, start_position: 0, ends_in_prefix: false }
+                FuzzyWindowResult { phrase: vec!["100".to_string(), "st".to_string(), "washington".to_string()], phrase_ids: vec![3], displays: vec![], edit_distance: 0, start_position: 0, ends_in_prefix: false }
             ]
         );
         //autocomplete is applied only to the last term
         assert_eq!(
             TEST_SET.fuzzy_match_windows(&["100", "main", "st"], 1, 1, true).unwrap(),
             vec![
-                FuzzyWindowResult { phrase: vec!["100".to_string(), "main".to_string(), "st".to_string()], edit_distance: 0, start_position: 0, ends_in_prefix: true },
-                FuzzyWindowResult { phrase: vec!["St".to_string()], edit_distance: 1, start_position: 2, ends_in_prefix: true }
+                FuzzyWindowResult { phrase: vec!["100".to_string(), "main".to_string(), "st".to_string()], phrase_ids: vec![], displays: vec![], edit_distance: 0, start_position: 0, ends_in_prefix: true },
+                FuzzyWindowResult { phrase: vec!["St".to_string()], phrase_ids: vec![], displays: vec![], edit_distance: 1, start_position: 2, ends_in_prefix: true }
             ]
         );
     }
@@ -1165,8 +3662,8 @@ mod basic_tests {
         assert_eq!(
             TEST_SET.fuzzy_match_windows(&["100", "d", "st"], 1, 1, true).unwrap(),
             vec![
-                FuzzyWindowResult { phrase: vec!["100".to_string(), "d".to_string(), "st".to_string()], edit_distance: 0, start_position: 0, ends_in_prefix: true },
-                FuzzyWindowResult { phrase: vec!["St".to_string()], edit_distance: 1, start_position: 2, ends_in_prefix: true }
+                FuzzyWindowResult { phrase: vec!["100".to_string(), "d".to_string(), "st".to_string()], phrase_ids: vec![], displays: vec![], edit_distance: 0, start_position: 0, ends_in_prefix: true },
+                FuzzyWindowResult { phrase: vec!["St".to_string()], phrase_ids: vec![], displays: vec![], edit_distance: 1, start_position: 2, ends_in_prefix: true }
             ]
         );
 
@@ -1174,7 +3671,7 @@ mod basic_tests {
         assert_eq!(
             TEST_SET.fuzzy_match_windows(&["100", "e"], 1, 1, true).unwrap(),
             vec![
-                FuzzyWindowResult { phrase: vec!["100".to_string(), "e".to_string()], edit_distance: 0, start_position: 0, ends_in_prefix: true },
+                FuzzyWindowResult { phrase: vec!["100".to_string(), "e".to_string()], phrase_ids: vec![], displays: vec![], edit_distance: 0, start_position: 0, ends_in_prefix: true },
             ]
         );
 
@@ -1184,7 +3681,7 @@ mod basic_tests {
                 (vec!["100", "e"], true),
             ], 1, 1).unwrap(),
             vec![
-                vec![FuzzyMatchResult { phrase: vec!["100".to_string(), "e".to_string()], edit_distance: 0 }],
+                vec![FuzzyMatchResult { phrase: vec!["100".to_string(), "e".to_string()], phrase_ids: vec![], displays: vec![], edit_distance: 0 }],
             ]
         );
     }
@@ -0,0 +1,101 @@
+use std::borrow::Cow;
+use std::error::Error;
+
+use caseless::default_case_fold_str;
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::is_combining_mark;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum NormalizationForm {
+    /// Canonical composition: combines characters with their accents wherever Unicode has a
+    /// precomposed character for the pair.
+    Nfc,
+    /// Compatibility composition: as `Nfc`, but also replaces compatibility characters like
+    /// ligatures, full-width letters and superscripts with their plain equivalents.
+    Nfkc,
+}
+
+/// How words are normalized before they're added to the lexicon, and before they're looked up
+/// in it at query time. The default leaves words exactly as they are.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+pub struct NormalizationOptions {
+    /// The Unicode normalization form to put words in, if any.
+    pub form: Option<NormalizationForm>,
+    /// Whether to apply Unicode full case folding, so that, e.g., "Straße", "STRASSE" and
+    /// "strasse" are all "strasse".
+    pub case_fold: bool,
+    /// Whether to remove accents and other combining marks, so that, e.g., "São" is "Sao".
+    pub strip_accents: bool,
+    /// When accents are kept, the number of edits to charge for matching a lexicon word that
+    /// differs from a query word only in its accents, in place of the real number of edits
+    /// between them; zero makes such matches as good as exact ones. Words matched this way don't
+    /// need to be within the fuzzy graph's reach, so this also finds words with more accent
+    /// differences than the query's edit distance would otherwise allow.
+    pub accent_edit_distance: Option<u8>,
+}
+
+impl NormalizationOptions {
+    /// Normalize a single word, borrowing it if it doesn't change.
+    pub fn normalize<'a>(&self, word: &'a str) -> Cow<'a, str> {
+        if self.form.is_none() && !self.case_fold && !self.strip_accents {
+            return Cow::Borrowed(word);
+        }
+
+        let mut normalized = if self.case_fold { default_case_fold_str(word) } else { word.to_owned() };
+        if self.strip_accents {
+            normalized = remove_accents(&normalized);
+        }
+        normalized = match self.form {
+            Some(NormalizationForm::Nfc) => normalized.nfc().collect(),
+            Some(NormalizationForm::Nfkc) => normalized.nfkc().collect(),
+            None => normalized,
+        };
+
+        if normalized == word {
+            Cow::Borrowed(word)
+        } else {
+            Cow::Owned(normalized)
+        }
+    }
+
+    pub(super) fn validate(&self) -> Result<(), Box<Error>> {
+        if self.strip_accents && self.accent_edit_distance.is_some() {
+            return Err("accent_edit_distance only applies to indexes that don't strip accents".into());
+        }
+        Ok(())
+    }
+}
+
+// decompose, drop the combining marks, and put back together whatever's left
+pub(super) fn remove_accents(word: &str) -> String {
+    word.nfd().filter(|c| !is_combining_mark(*c)).nfc().collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalize_words() -> () {
+        let identity = NormalizationOptions::default();
+        assert_eq!(identity.normalize("Straße"), Cow::Borrowed("Straße"));
+
+        let folded = NormalizationOptions { form: Some(NormalizationForm::Nfc), case_fold: true, ..NormalizationOptions::default() };
+        for word in &["Straße", "STRASSE", "strasse"] {
+            assert_eq!(folded.normalize(word), "strasse");
+        }
+        // decomposed input is composed
+        assert_eq!(folded.normalize("Sa\u{0303}o"), "são");
+        assert_eq!(folded.normalize("são"), Cow::Borrowed("são"));
+
+        let compatible = NormalizationOptions { form: Some(NormalizationForm::Nfkc), ..NormalizationOptions::default() };
+        assert_eq!(compatible.normalize("ﬁrst"), "first");
+
+        let stripped = NormalizationOptions { strip_accents: true, ..NormalizationOptions::default() };
+        assert_eq!(stripped.normalize("São"), "Sao");
+        assert_eq!(stripped.normalize("Ærøskøbing"), "Ærøskøbing");
+        assert_eq!(stripped.normalize("서울"), "서울");
+
+        assert!(NormalizationOptions { strip_accents: true, accent_edit_distance: Some(0), ..NormalizationOptions::default() }.validate().is_err());
+    }
+}
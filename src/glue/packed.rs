@@ -0,0 +1,357 @@
+use std::path::Path;
+use std::error::Error;
+use std::io::{Error as IoError, ErrorKind as IoErrorKind, Cursor, Read, Write, BufWriter};
+use std::fs;
+
+use byteorder::{LittleEndian, ReadBytesExt, WriteBytesExt};
+use crc::crc32;
+#[cfg(feature = "mmap")]
+use fst::raw::MmapReadOnly;
+#[cfg(feature = "mmap")]
+use serde_json;
+use serde::Deserialize;
+use rmps::Deserializer;
+
+#[cfg(feature = "mmap")]
+use ::prefix::PrefixSet;
+#[cfg(feature = "mmap")]
+use ::phrase::PhraseSet;
+#[cfg(feature = "mmap")]
+use ::fuzzy::{FuzzyMap, AliasMap};
+use super::{FuzzyPhraseSet, FuzzyPhraseSetBytes, SerializableTombstones};
+#[cfg(feature = "mmap")]
+use super::{FuzzyPhraseSetMetadata, SerializablePhraseIdList};
+
+// A packed index is a single file holding all the files of an index directory, laid out as:
+//
+// | field             | size                                               |
+// |-------------------|----------------------------------------------------|
+// | magic             | 8 bytes, `MAGIC`                                   |
+// | version           | u32, `CONTAINER_VERSION`                           |
+// | section count     | u32                                                |
+// | header CRC        | u32, CRC-32 of the version, count and table        |
+// | table of contents | one entry per section, see below                   |
+// | sections          | each starting at a multiple of `SECTION_ALIGNMENT` |
+//
+// Each table of contents entry is the section's name (its file name in the directory form,
+// padded with zeroes to `NAME_LENGTH` bytes), its offset from the start of the file and its
+// length (both u64), and the CRC-32 of its contents (u32). All integers are little-endian.
+//
+// The header CRC is checked whenever the file is read; the section CRCs are only checked when
+// unpacking, since checking them means reading every byte of the graphs, which loading a packed
+// index by memory-mapping it is meant to avoid.
+
+static MAGIC: &[u8; 8] = b"FZPHRSET";
+static CONTAINER_VERSION: u32 = 1;
+const NAME_LENGTH: usize = 16;
+const PREAMBLE_LENGTH: usize = 8 + 4 + 4 + 4;
+const ENTRY_LENGTH: usize = NAME_LENGTH + 8 + 8 + 4;
+const SECTION_ALIGNMENT: usize = 8;
+
+// every index has these; the others are only written when there's something in them, or, for
+// phonetic.fst and translit.fst, when the index was built with a phonetic or transliteration edit
+// distance
+pub(super) static REQUIRED_SECTIONS: [&str; 6] = ["metadata.json", "prefix.fst", "phrase.fst", "phrase.msg", "fuzzy.fst", "fuzzy.msg"];
+pub(super) static OPTIONAL_SECTIONS: [&str; 4] = ["deleted.msg", "display.msg", "phonetic.fst", "translit.fst"];
+
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub(super) struct PackedSection {
+    pub(super) name: String,
+    offset: usize,
+    length: usize,
+    pub(super) crc: u32,
+}
+
+impl PackedSection {
+    pub(super) fn contents<'a>(&self, bytes: &'a [u8]) -> &'a [u8] {
+        &bytes[self.offset..(self.offset + self.length)]
+    }
+}
+
+/// Combine the files of the index directory at `directory` into a single packed index file at
+/// `path`, which `FuzzyPhraseSet::from_packed_path` can load.
+pub fn pack<P: AsRef<Path>, Q: AsRef<Path>>(directory: P, path: Q) -> Result<(), Box<Error>> {
+    let directory = directory.as_ref();
+    if !directory.is_dir() {
+        return Err(Box::new(IoError::new(IoErrorKind::NotFound, "File does not exist or is not a directory")));
+    }
+
+    let mut sections: Vec<(&str, Vec<u8>)> = Vec::new();
+    for name in REQUIRED_SECTIONS.iter() {
+        sections.push((name, fs::read(directory.join(Path::new(name)))?));
+    }
+    for name in OPTIONAL_SECTIONS.iter() {
+        let section_path = directory.join(Path::new(name));
+        if section_path.exists() {
+            sections.push((name, fs::read(section_path)?));
+        }
+    }
+
+    let mut toc: Vec<u8> = Vec::with_capacity(4 + 4 + sections.len() * ENTRY_LENGTH);
+    toc.write_u32::<LittleEndian>(CONTAINER_VERSION)?;
+    toc.write_u32::<LittleEndian>(sections.len() as u32)?;
+    let mut offset = align(PREAMBLE_LENGTH + sections.len() * ENTRY_LENGTH);
+    for (name, contents) in &sections {
+        let mut padded_name = [0u8; NAME_LENGTH];
+        padded_name[..name.len()].copy_from_slice(name.as_bytes());
+        toc.write_all(&padded_name)?;
+        toc.write_u64::<LittleEndian>(offset as u64)?;
+        toc.write_u64::<LittleEndian>(contents.len() as u64)?;
+        toc.write_u32::<LittleEndian>(crc32::checksum_ieee(contents))?;
+        offset = align(offset + contents.len());
+    }
+
+    let mut writer = BufWriter::new(fs::File::create(path)?);
+    writer.write_all(MAGIC)?;
+    // the version and count come first in the checksummed table, but the CRC goes before them,
+    // so write them out separately
+    writer.write_all(&toc[..8])?;
+    writer.write_u32::<LittleEndian>(crc32::checksum_ieee(&toc))?;
+    writer.write_all(&toc[8..])?;
+    let mut written = PREAMBLE_LENGTH + sections.len() * ENTRY_LENGTH;
+    for (_name, contents) in &sections {
+        let padding = align(written) - written;
+        writer.write_all(&vec![0u8; padding])?;
+        writer.write_all(contents)?;
+        written += padding + contents.len();
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+/// Expand the packed index file at `path` back into an index directory at `directory`, checking
+/// each section's checksum along the way.
+pub fn unpack<P: AsRef<Path>, Q: AsRef<Path>>(path: P, directory: Q) -> Result<(), Box<Error>> {
+    let bytes = fs::read(path)?;
+    let sections = read_sections(&bytes)?;
+    for section in &sections {
+        if crc32::checksum_ieee(section.contents(&bytes)) != section.crc {
+            return Err(Box::new(IoError::new(
+                IoErrorKind::InvalidData,
+                format!("Checksum mismatch in packed section {}", section.name)
+            )));
+        }
+    }
+
+    let directory = directory.as_ref();
+    if directory.exists() {
+        if !directory.is_dir() {
+            return Err(Box::new(IoError::new(IoErrorKind::AlreadyExists, "File exists and is not a directory")));
+        }
+    } else {
+        fs::create_dir(directory)?;
+    }
+    for section in &sections {
+        fs::write(directory.join(Path::new(&section.name)), section.contents(&bytes))?;
+    }
+    Ok(())
+}
+
+fn align(offset: usize) -> usize {
+    (offset + SECTION_ALIGNMENT - 1) / SECTION_ALIGNMENT * SECTION_ALIGNMENT
+}
+
+// parse and validate the header and table of contents
+pub(super) fn read_sections(bytes: &[u8]) -> Result<Vec<PackedSection>, Box<Error>> {
+    let malformed = |message: &str| Box::new(IoError::new(IoErrorKind::InvalidData, message.to_owned()));
+
+    if bytes.len() < PREAMBLE_LENGTH || &bytes[..8] != MAGIC {
+        return Err(malformed("Not a packed fuzzy phrase set"));
+    }
+    let mut preamble = Cursor::new(&bytes[8..PREAMBLE_LENGTH]);
+    let version = preamble.read_u32::<LittleEndian>()?;
+    let count = preamble.read_u32::<LittleEndian>()? as usize;
+    let header_crc = preamble.read_u32::<LittleEndian>()?;
+    if version != CONTAINER_VERSION {
+        return Err(malformed("Unsupported packed fuzzy phrase set version"));
+    }
+    let toc_end = count.checked_mul(ENTRY_LENGTH).and_then(|l| l.checked_add(PREAMBLE_LENGTH));
+    let toc_end = match toc_end {
+        Some(toc_end) if toc_end <= bytes.len() => toc_end,
+        _ => return Err(malformed("Packed fuzzy phrase set is truncated")),
+    };
+
+    let mut checked = bytes[8..16].to_vec();
+    checked.extend_from_slice(&bytes[PREAMBLE_LENGTH..toc_end]);
+    if crc32::checksum_ieee(&checked) != header_crc {
+        return Err(malformed("Checksum mismatch in packed fuzzy phrase set header"));
+    }
+
+    let mut toc = Cursor::new(&bytes[PREAMBLE_LENGTH..toc_end]);
+    let mut sections: Vec<PackedSection> = Vec::with_capacity(count);
+    for _ in 0..count {
+        let mut padded_name = [0u8; NAME_LENGTH];
+        toc.read_exact(&mut padded_name)?;
+        let name_length = padded_name.iter().position(|b| *b == 0).unwrap_or(NAME_LENGTH);
+        let name = String::from_utf8(padded_name[..name_length].to_vec())?;
+        let offset = toc.read_u64::<LittleEndian>()? as usize;
+        let length = toc.read_u64::<LittleEndian>()? as usize;
+        let crc = toc.read_u32::<LittleEndian>()?;
+
+        if !REQUIRED_SECTIONS.contains(&name.as_str()) && !OPTIONAL_SECTIONS.contains(&name.as_str()) {
+            return Err(malformed("Unexpected section in packed fuzzy phrase set"));
+        }
+        if offset < toc_end || offset.checked_add(length).map_or(true, |end| end > bytes.len()) {
+            return Err(malformed("Packed fuzzy phrase set is truncated"));
+        }
+        sections.push(PackedSection { name, offset, length, crc });
+    }
+    for name in REQUIRED_SECTIONS.iter() {
+        if !sections.iter().any(|s| s.name == *name) {
+            return Err(malformed("Packed fuzzy phrase set is missing a section"));
+        }
+    }
+    Ok(sections)
+}
+
+impl FuzzyPhraseSet {
+    /// Load a packed index file, as written by `pack`. With the `mmap` feature, the graphs are
+    /// memory-mapped directly out of the file, as with `from_path`; without it, the whole file is
+    /// read into memory. The set isn't associated with a directory, so deletions aren't persisted
+    /// anywhere.
+    #[cfg(feature = "mmap")]
+    pub fn from_packed_path<P: AsRef<Path>>(path: P) -> Result<Self, Box<Error>> {
+        let mmap = unsafe { MmapReadOnly::open_path(path) }?;
+        let sections = read_sections(mmap.as_slice())?;
+        let section = |name: &str| sections.iter().find(|s| s.name == name);
+        let region = |name: &str| -> MmapReadOnly {
+            let s = section(name).expect("required sections are checked when the table is read");
+            mmap.range(s.offset, s.length)
+        };
+        let contents = |name: &str| section(name).map(|s| s.contents(mmap.as_slice()));
+
+        let metadata: FuzzyPhraseSetMetadata = serde_json::from_slice(contents("metadata.json").unwrap())?;
+        let prefix_set = PrefixSet::from_mmap(region("prefix.fst"))?;
+        let phrase_set = PhraseSet::from_mmap(region("phrase.fst"))?;
+        let phrase_id_list: SerializablePhraseIdList = Deserialize::deserialize(&mut Deserializer::new(contents("phrase.msg").unwrap()))?;
+        let fuzzy_map = FuzzyMap::from_mmap(region("fuzzy.fst"), contents("fuzzy.msg").unwrap())?;
+
+        let mut set = FuzzyPhraseSet::from_parts(metadata, prefix_set, phrase_set, phrase_id_list.0, fuzzy_map)?;
+        set.load_packed_tombstones(contents("deleted.msg"))?;
+        if let Some(display_bytes) = contents("display.msg") {
+            set.load_displays(display_bytes)?;
+        }
+        let alias_map = |name: &str| -> Result<Option<AliasMap>, Box<Error>> {
+            match section(name) {
+                Some(s) => Ok(Some(AliasMap::from_mmap(mmap.range(s.offset, s.length))?)),
+                None => Ok(None),
+            }
+        };
+        set.load_alias_maps(alias_map("phonetic.fst")?, alias_map("translit.fst")?)?;
+        Ok(set)
+    }
+
+    #[cfg(not(feature = "mmap"))]
+    pub fn from_packed_path<P: AsRef<Path>>(path: P) -> Result<Self, Box<Error>> {
+        FuzzyPhraseSet::from_packed_bytes(&fs::read(path)?)
+    }
+
+    /// Load a packed index that's already in memory. The sections are copied out of `bytes`.
+    pub fn from_packed_bytes(bytes: &[u8]) -> Result<Self, Box<Error>> {
+        let sections = read_sections(bytes)?;
+        let section = |name: &str| sections.iter().find(|s| s.name == name).map(|s| s.contents(bytes));
+        let required = |name: &str| -> Vec<u8> {
+            section(name).expect("required sections are checked when the table is read").to_vec()
+        };
+
+        let mut set = FuzzyPhraseSet::from_bytes(FuzzyPhraseSetBytes {
+            prefix_fst: required("prefix.fst"),
+            phrase_fst: required("phrase.fst"),
+            phrase_msg: required("phrase.msg"),
+            fuzzy_fst: required("fuzzy.fst"),
+            fuzzy_msg: required("fuzzy.msg"),
+            metadata_json: required("metadata.json"),
+            display_msg: section("display.msg").map(|s| s.to_vec()),
+            phonetic_fst: section("phonetic.fst").map(|s| s.to_vec()),
+            transliteration_fst: section("translit.fst").map(|s| s.to_vec()),
+        })?;
+        set.load_packed_tombstones(section("deleted.msg"))?;
+        Ok(set)
+    }
+
+    fn load_packed_tombstones(&mut self, tombstone_bytes: Option<&[u8]>) -> Result<(), Box<Error>> {
+        if let Some(tombstone_bytes) = tombstone_bytes {
+            let tombstone_list: SerializableTombstones = Deserialize::deserialize(&mut Deserializer::new(tombstone_bytes))?;
+            self.tombstones.extend(tombstone_list.0);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+
+    use super::*;
+    use super::super::{FuzzyPhraseSetBuilder, FuzzyPhraseSetBuilderOptions};
+
+    #[test]
+    fn packed_round_trip() -> () {
+        let dir = tempfile::tempdir().unwrap();
+        let original = dir.path().join("original");
+        let packed = dir.path().join("index.fzp");
+        let unpacked = dir.path().join("unpacked");
+
+        let options = FuzzyPhraseSetBuilderOptions {
+            phonetic_edit_distance: Some(1),
+            transliteration_edit_distance: Some(1),
+            ..FuzzyPhraseSetBuilderOptions::default()
+        };
+        let mut builder = FuzzyPhraseSetBuilder::new_with_options(&original, options).unwrap();
+        builder.insert_str("100 main street", 0).unwrap();
+        builder.insert_str_with_display("200 main street", 1).unwrap();
+        builder.insert_str("300 mlk blvd", 2).unwrap();
+        builder.finish().unwrap();
+        let mut original_set = FuzzyPhraseSet::from_path(&original).unwrap();
+        original_set.delete_str("300 mlk blvd").unwrap();
+
+        pack(&original, &packed).unwrap();
+        let packed_set = FuzzyPhraseSet::from_packed_path(&packed).unwrap();
+        assert!(packed_set.contains_str("100 main street").unwrap());
+        assert!(!packed_set.contains_str("300 mlk blvd").unwrap());
+        assert_eq!(
+            packed_set.fuzzy_match_str("200 man street", 1, 1).unwrap(),
+            original_set.fuzzy_match_str("200 man street", 1, 1).unwrap()
+        );
+
+        let in_memory_set = FuzzyPhraseSet::from_packed_bytes(&fs::read(&packed).unwrap()).unwrap();
+        assert!(in_memory_set.contains_str("200 main street").unwrap());
+        assert!(!in_memory_set.contains_str("300 mlk blvd").unwrap());
+
+        unpack(&packed, &unpacked).unwrap();
+        for name in REQUIRED_SECTIONS.iter().chain(OPTIONAL_SECTIONS.iter()) {
+            assert_eq!(fs::read(unpacked.join(name)).unwrap(), fs::read(original.join(name)).unwrap());
+        }
+    }
+
+    #[test]
+    fn packed_corruption() -> () {
+        let dir = tempfile::tempdir().unwrap();
+        let original = dir.path().join("original");
+        let packed = dir.path().join("index.fzp");
+
+        let mut builder = FuzzyPhraseSetBuilder::new(&original).unwrap();
+        builder.insert_str("100 main street", 0).unwrap();
+        builder.finish().unwrap();
+        pack(&original, &packed).unwrap();
+        let bytes = fs::read(&packed).unwrap();
+
+        // a damaged table of contents is caught on load
+        let mut bad_header = bytes.clone();
+        bad_header[PREAMBLE_LENGTH + NAME_LENGTH] ^= 1;
+        fs::write(&packed, &bad_header).unwrap();
+        assert!(FuzzyPhraseSet::from_packed_path(&packed).is_err());
+
+        // damaged contents are caught on unpack
+        let mut bad_contents = bytes.clone();
+        let last = bad_contents.len() - 1;
+        bad_contents[last] ^= 1;
+        fs::write(&packed, &bad_contents).unwrap();
+        assert!(unpack(&packed, dir.path().join("unpacked")).is_err());
+
+        fs::write(&packed, &bytes[..bytes.len() / 2]).unwrap();
+        assert!(FuzzyPhraseSet::from_packed_path(&packed).is_err());
+        fs::write(&packed, b"not an index").unwrap();
+        assert!(FuzzyPhraseSet::from_packed_path(&packed).is_err());
+    }
+}
@@ -0,0 +1,310 @@
+use std::cmp::Reverse;
+use std::collections::BTreeMap;
+use std::error::Error;
+use std::hash::Hash;
+use std::io::{Error as IoError, ErrorKind as IoErrorKind};
+use std::ops::Range;
+use std::ptr;
+
+use rustc_hash::FxHashSet;
+
+use ::phrase::query::QueryWord;
+
+// a query whose words could be rewritten in many ways only gets this many rewrites (including the
+// unrewritten one) searched; rewrites that apply fewer replacements come first, so every single
+// replacement is searched unless a query has more than MAX_REWRITES - 1 places rules could apply
+const MAX_REWRITES: usize = 16;
+
+/// A rule that rewrites a run of words wherever it appears in a phrase, such as "saint louis" to
+/// "st louis", "martin luther king" to "mlk", or "n" to "north". Like word replacements, these
+/// are applied to phrases as they're inserted and to queries as they're looked up, so either
+/// spelling matches phrases inserted with either one. Rules only ever rewrite the words they were
+/// given, not each other's output.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq)]
+pub struct PhraseReplacement {
+    pub from: Vec<String>,
+    pub to: Vec<String>,
+}
+
+// a phrase replacement as word IDs, after word replacement
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct PhraseReplacementRule {
+    pub from: Vec<u32>,
+    pub to: Vec<u32>,
+}
+
+// phrase replacement rules, indexed by the ID of their first word like the word replacement map;
+// the rules starting with each word are kept longest first, so that the first rule that matches
+// at a position is the longest one
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub(super) struct PhraseReplacementRules {
+    by_first_word: BTreeMap<u32, Vec<PhraseReplacementRule>>,
+}
+
+impl PhraseReplacementRules {
+    pub fn new(rules: Vec<PhraseReplacementRule>) -> PhraseReplacementRules {
+        let mut by_first_word: BTreeMap<u32, Vec<PhraseReplacementRule>> = BTreeMap::new();
+        for rule in rules {
+            by_first_word.entry(rule.from[0]).or_default().push(rule);
+        }
+        for rules in by_first_word.values_mut() {
+            rules.sort_by_key(|rule| Reverse(rule.from.len()));
+        }
+        PhraseReplacementRules { by_first_word }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.by_first_word.is_empty()
+    }
+
+    // the rules whose first word is the given one
+    fn starting_with(&self, id: u32) -> &[PhraseReplacementRule] {
+        self.by_first_word.get(&id).map_or(&[], |rules| rules.as_slice())
+    }
+
+    // the rules whose first word could be the given query word
+    fn starting_with_any<'a>(&'a self, word: &QueryWord) -> Box<Iterator<Item = &'a PhraseReplacementRule> + 'a> {
+        match *word {
+            QueryWord::Full { id, .. } => Box::new(self.starting_with(id).iter()),
+            QueryWord::Prefix { id_range, .. } => {
+                Box::new(self.by_first_word.range(id_range.0..=id_range.1).flat_map(|(_, rules)| rules.iter()))
+            },
+        }
+    }
+}
+
+// turn phrase replacements into rules, given a way to find a word's (replaced) ID
+pub(super) fn resolve_rules<F>(phrase_replacements: &[PhraseReplacement], id_of: F) -> Result<PhraseReplacementRules, Box<Error>>
+    where F: Fn(&str) -> Option<u32>
+{
+    let mut rules: Vec<PhraseReplacementRule> = Vec::with_capacity(phrase_replacements.len());
+    for phrase_replacement in phrase_replacements {
+        let ids = |words: &[String]| -> Result<Vec<u32>, String> {
+            words.iter().map(|word| id_of(word).ok_or_else(|| format!("Phrase replacement word {} not in lexicon", word))).collect()
+        };
+        let rule = PhraseReplacementRule { from: ids(&phrase_replacement.from)?, to: ids(&phrase_replacement.to)? };
+        if rule.from.is_empty() {
+            return Err(Box::new(IoError::new(IoErrorKind::InvalidInput, "Phrase replacement with nothing to replace")));
+        }
+        // word replacements can make the two sides the same, which leaves nothing to do
+        if rule.from != rule.to {
+            rules.push(rule);
+        }
+    }
+    Ok(PhraseReplacementRules::new(rules))
+}
+
+// rewrite a phrase's word IDs with the rules, scanning from the start and replacing the longest
+// match at each position
+pub(super) fn replace_phrase(phrase: &[u32], rules: &PhraseReplacementRules) -> Vec<u32> {
+    let mut replaced: Vec<u32> = Vec::with_capacity(phrase.len());
+    let mut i = 0;
+    while i < phrase.len() {
+        match rules.starting_with(phrase[i]).iter().find(|rule| phrase[i..].starts_with(&rule.from)) {
+            Some(rule) => {
+                replaced.extend_from_slice(&rule.to);
+                i += rule.from.len();
+            },
+            None => {
+                replaced.push(phrase[i]);
+                i += 1;
+            },
+        }
+    }
+    replaced
+}
+
+// one way of reading a query's word possibilities, with phrase replacements applied to some of
+// its words
+#[derive(Debug, Clone)]
+pub(super) struct Rewrite {
+    pub word_possibilities: Vec<Vec<QueryWord>>,
+    // for each slot, the positions of the query words it stands for; all the slots a replacement
+    // put in share the positions of the words it replaced
+    pub positions: Vec<Range<usize>>,
+}
+
+impl Rewrite {
+    // matches can only start and end at the edges of replacements, not partway through them
+    pub fn starts_span(&self, slot: usize) -> bool {
+        slot == 0 || self.positions[slot - 1] != self.positions[slot]
+    }
+
+    pub fn ends_span(&self, slot: usize) -> bool {
+        slot + 1 == self.positions.len() || self.positions[slot + 1] != self.positions[slot]
+    }
+}
+
+// a place in a query where a rule applies
+struct RuleMatch<'a> {
+    start: usize,
+    rule: &'a PhraseReplacementRule,
+    cost: u8,
+}
+
+impl<'a> RuleMatch<'a> {
+    fn end(&self) -> usize {
+        self.start + self.rule.from.len()
+    }
+}
+
+// the ways (up to MAX_REWRITES) the rules could apply to a query with the given possibilities,
+// starting with leaving it alone, then applying one rule, then two that don't overlap, and so on;
+// a rule applies wherever each of its words is among the possibilities for consecutive query
+// words, and the words it puts in cost as many edits as the ones it took out
+pub(super) fn rewrites(word_possibilities: Vec<Vec<QueryWord>>, rules: &PhraseReplacementRules) -> Vec<Rewrite> {
+    let mut matches: Vec<RuleMatch> = Vec::new();
+    for start in 0..word_possibilities.len() {
+        let start_len = matches.len();
+        for variant in &word_possibilities[start] {
+            for rule in rules.starting_with_any(variant) {
+                if start + rule.from.len() > word_possibilities.len()
+                    || matches[start_len..].iter().any(|m| ptr::eq(m.rule, rule)) {
+                    continue;
+                }
+                let cost: Option<u8> = rule.from.iter().zip(&word_possibilities[start..]).map(|(id, variants)| {
+                    variants.iter().filter(|variant| variant.contains_id(*id)).map(|variant| variant.edit_distance()).min()
+                }).sum();
+                if let Some(cost) = cost {
+                    matches.push(RuleMatch { start, rule, cost });
+                }
+            }
+        }
+        matches[start_len..].sort_by_key(|m| Reverse(m.rule.from.len()));
+    }
+
+    // pick sets of matches that don't overlap, smallest sets first
+    let mut match_sets: Vec<Vec<usize>> = vec![Vec::new()];
+    let mut set_size = 1;
+    while match_sets.len() < MAX_REWRITES {
+        let found = match_sets.len();
+        extend_match_sets(&matches, set_size, 0, &mut Vec::new(), &mut match_sets);
+        if match_sets.len() == found {
+            // no sets this size means no bigger ones either
+            break;
+        }
+        set_size += 1;
+    }
+
+    // words no match replaced stand for themselves
+    let keep_words = |rewrite: &mut Rewrite, positions: Range<usize>| {
+        for (i, variants) in word_possibilities.iter().enumerate().take(positions.end).skip(positions.start) {
+            rewrite.word_possibilities.push(variants.clone());
+            rewrite.positions.push(i..(i + 1));
+        }
+    };
+    match_sets.iter().map(|match_set| {
+        let mut rewrite = Rewrite { word_possibilities: Vec::new(), positions: Vec::new() };
+        let mut position = 0;
+        for m in match_set.iter().map(|i| &matches[*i]) {
+            keep_words(&mut rewrite, position..m.start);
+            for (i, id) in m.rule.to.iter().enumerate() {
+                rewrite.word_possibilities.push(vec![QueryWord::new_full(*id, if i == 0 { m.cost } else { 0 })]);
+                rewrite.positions.push(m.start..m.end());
+            }
+            position = m.end();
+        }
+        keep_words(&mut rewrite, position..word_possibilities.len());
+        rewrite
+    }).collect()
+}
+
+// add the sets of set_size non-overlapping matches that extend the current one with matches from
+// index next on, in order of where their matches are, until there are MAX_REWRITES sets
+fn extend_match_sets(matches: &[RuleMatch], set_size: usize, next: usize, current: &mut Vec<usize>, match_sets: &mut Vec<Vec<usize>>) {
+    if current.len() == set_size {
+        match_sets.push(current.clone());
+        return;
+    }
+    let free_from = current.last().map_or(0, |i| matches[*i].end());
+    for i in next..matches.len() {
+        if match_sets.len() >= MAX_REWRITES {
+            return;
+        }
+        if matches[i].start < free_from {
+            continue;
+        }
+        current.push(i);
+        extend_match_sets(matches, set_size, i + 1, current, match_sets);
+        current.pop();
+    }
+}
+
+// different rewrites of a query can find the same match; keep only the cheapest of each, and
+// leave the rest in order of edit distance
+pub(super) fn dedup_matches<T, K, F>(results: &mut Vec<T>, cost_and_key: F)
+    where K: Hash + Eq, F: Fn(&T) -> (u8, K)
+{
+    results.sort_by_key(|result| cost_and_key(result).0);
+    let mut seen: FxHashSet<K> = FxHashSet::default();
+    results.retain(|result| seen.insert(cost_and_key(result).1));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(from: &[u32], to: &[u32]) -> PhraseReplacementRule {
+        PhraseReplacementRule { from: from.to_vec(), to: to.to_vec() }
+    }
+
+    #[test]
+    fn replace_phrase_test() -> () {
+        let rules = PhraseReplacementRules::new(vec![rule(&[1, 2], &[8, 2]), rule(&[1, 2, 3], &[9]), rule(&[4], &[5])]);
+        assert_eq!(replace_phrase(&[0, 1, 2, 3, 4], &rules), vec![0, 9, 5]);
+        assert_eq!(replace_phrase(&[1, 2, 4, 1], &rules), vec![8, 2, 5, 1]);
+        assert_eq!(replace_phrase(&[0, 3], &rules), vec![0, 3]);
+    }
+
+    #[test]
+    fn rewrites_test() -> () {
+        let rules = PhraseReplacementRules::new(vec![rule(&[1, 2, 3], &[9]), rule(&[7], &[8, 6])]);
+        let word_possibilities = vec![
+            vec![QueryWord::new_full(0, 0)],
+            vec![QueryWord::new_full(1, 1), QueryWord::new_full(4, 0)],
+            vec![QueryWord::new_full(2, 0)],
+            vec![QueryWord::new_full(3, 0)],
+            vec![QueryWord::new_full(7, 0)],
+        ];
+        let rewrites = rewrites(word_possibilities.clone(), &rules);
+        assert_eq!(rewrites.len(), 4);
+        assert_eq!(rewrites[0].word_possibilities, word_possibilities);
+        assert_eq!(rewrites[3].word_possibilities, vec![
+            vec![QueryWord::new_full(0, 0)],
+            vec![QueryWord::new_full(9, 1)],
+            vec![QueryWord::new_full(8, 0)],
+            vec![QueryWord::new_full(6, 0)],
+        ]);
+        assert_eq!(rewrites[3].positions, vec![0..1, 1..4, 4..5, 4..5]);
+        assert!(rewrites[3].starts_span(2) && !rewrites[3].starts_span(3));
+        assert!(!rewrites[3].ends_span(2) && rewrites[3].ends_span(3));
+
+        // nothing to rewrite
+        let rewrites = super::rewrites(vec![vec![QueryWord::new_full(2, 0)]], &rules);
+        assert_eq!(rewrites.len(), 1);
+        assert_eq!(rewrites[0].positions, vec![0..1]);
+
+        // a rule can start with any word in a prefix's range
+        let word_possibilities = vec![vec![QueryWord::new_prefix((5, 7))]];
+        let rewrites = super::rewrites(word_possibilities, &rules);
+        assert_eq!(rewrites.len(), 2);
+        assert_eq!(rewrites[1].word_possibilities, vec![vec![QueryWord::new_full(8, 0)], vec![QueryWord::new_full(6, 0)]]);
+    }
+
+    #[test]
+    fn rewrites_cap_test() -> () {
+        // every word of a long query could be replaced, which makes far more than MAX_REWRITES
+        // combinations; the single replacements all still come before any pairs of them
+        let rules = PhraseReplacementRules::new((0..10).map(|id| rule(&[id], &[id + 100])).collect());
+        let word_possibilities: Vec<Vec<QueryWord>> = (0..10).map(|id| vec![QueryWord::new_full(id, 0)]).collect();
+        let rewrites = rewrites(word_possibilities.clone(), &rules);
+        assert_eq!(rewrites.len(), MAX_REWRITES);
+        assert_eq!(rewrites[0].word_possibilities, word_possibilities);
+        for position in 0..10 {
+            let replaced: Vec<usize> = (0..10).filter(|i| rewrites[position + 1].word_possibilities[*i] != word_possibilities[*i]).collect();
+            assert_eq!(replaced, vec![position]);
+        }
+        assert_eq!(rewrites[11].word_possibilities[0], vec![QueryWord::new_full(100, 0)]);
+        assert_eq!(rewrites[11].word_possibilities[1], vec![QueryWord::new_full(101, 0)]);
+    }
+}
@@ -6,22 +6,22 @@ use super::*;
 lazy_static! {
     static ref DIRECTORY: tempfile::TempDir = tempfile::tempdir().unwrap();
     static ref REPLACEMENTS: Vec<WordReplacement> = vec![
-        WordReplacement { from: "street".to_string(), to: "st".to_string() },
-        WordReplacement { from: "saint".to_string(), to: "st".to_string() },
-        WordReplacement { from: "avenue".to_string(), to: "ave".to_string() },
-        WordReplacement { from: "fort".to_string(), to: "ft".to_string() },
-        WordReplacement { from: "road".to_string(), to: "rd".to_string() },
+        WordReplacement::new("street", "st"),
+        WordReplacement::new("saint", "st"),
+        WordReplacement::new("avenue", "ave"),
+        WordReplacement::new("fort", "ft"),
+        WordReplacement::new("road", "rd"),
     ];
     static ref TEST_SET: FuzzyPhraseSet = {
         let mut builder = FuzzyPhraseSetBuilder::new(&DIRECTORY.path()).unwrap();
 
         builder.load_word_replacements(REPLACEMENTS.clone());
 
-        builder.insert_str("100 main street").unwrap();
-        builder.insert_str("100 main st").unwrap();
-        builder.insert_str("100 maine st").unwrap();
-        builder.insert_str("100 ft wayne rd").unwrap();
-        builder.insert_str("100 fortenberry ave").unwrap();
+        builder.insert_str("100 main street", 0).unwrap();
+        builder.insert_str("100 main st", 1).unwrap();
+        builder.insert_str("100 maine st", 2).unwrap();
+        builder.insert_str("100 ft wayne rd", 3).unwrap();
+        builder.insert_str("100 fortenberry ave", 4).unwrap();
 
         builder.finish().unwrap();
         FuzzyPhraseSet::from_path(&DIRECTORY.path()).unwrap()
@@ -46,13 +46,13 @@ fn load_word_replacements() -> () {
 fn get_nonterminal_word_possibilities() -> () {
     // regular lookup
     assert_eq!(
-        TEST_SET.get_nonterminal_word_possibilities("wayne", 1).unwrap().unwrap(),
+        TEST_SET.get_nonterminal_word_possibilities("wayne", 1, WordContext::default()).unwrap().unwrap(),
         vec![QueryWord::new_full(id_of("wayne"), 0)]
     );
 
     // typo lookup
     assert_eq!(
-        TEST_SET.get_nonterminal_word_possibilities("main", 1).unwrap().unwrap(),
+        TEST_SET.get_nonterminal_word_possibilities("main", 1, WordContext::default()).unwrap().unwrap(),
         vec![
             QueryWord::new_full(id_of("main"), 0),
             QueryWord::new_full(id_of("maine"), 1),
@@ -62,13 +62,13 @@ fn get_nonterminal_word_possibilities() -> () {
     // replacements:
     // standard replacement -- matches replaced word, doesn't match typos of replaced word
     assert_eq!(
-        TEST_SET.get_nonterminal_word_possibilities("street", 1).unwrap().unwrap(),
+        TEST_SET.get_nonterminal_word_possibilities("street", 1, WordContext::default()).unwrap().unwrap(),
         vec![QueryWord::new_full(id_of("st"), 0)]
     );
     // word is a replacement *target*, so no replacement occurs, and we match both the word
     // and a typo
     assert_eq!(
-        TEST_SET.get_nonterminal_word_possibilities("st", 1).unwrap().unwrap(),
+        TEST_SET.get_nonterminal_word_possibilities("st", 1, WordContext::default()).unwrap().unwrap(),
         vec![
             QueryWord::new_full(id_of("st"), 0),
             QueryWord::new_full(id_of("ft"), 1),
@@ -76,22 +76,22 @@ fn get_nonterminal_word_possibilities() -> () {
     );
     // match a typo of the looked-up word and then replace
     assert_eq!(
-        TEST_SET.get_nonterminal_word_possibilities("stret", 1).unwrap().unwrap(),
+        TEST_SET.get_nonterminal_word_possibilities("stret", 1, WordContext::default()).unwrap().unwrap(),
         vec![QueryWord::new_full(id_of("st"), 1)]
     );
     // match nothing on prefix matches
     assert_eq!(
-        TEST_SET.get_nonterminal_word_possibilities("s", 1).unwrap(),
+        TEST_SET.get_nonterminal_word_possibilities("s", 1, WordContext::default()).unwrap(),
         None
     );
     // spelling-correct str to st
     assert_eq!(
-        TEST_SET.get_nonterminal_word_possibilities("str", 1).unwrap().unwrap(),
+        TEST_SET.get_nonterminal_word_possibilities("str", 1, WordContext::default()).unwrap().unwrap(),
         vec![QueryWord::new_full(id_of("st"), 1)]
     );
     // match nothing on prefix match of replacement source that can't be a typo
     assert_eq!(
-        TEST_SET.get_nonterminal_word_possibilities("stre", 1).unwrap(),
+        TEST_SET.get_nonterminal_word_possibilities("stre", 1, WordContext::default()).unwrap(),
         None
     );
 }
@@ -100,40 +100,61 @@ fn get_nonterminal_word_possibilities() -> () {
 fn get_terminal_word_possibilities() -> () {
     // regular lookup -- emitted as a full word because it has no continuations
     assert_eq!(
-        TEST_SET.get_terminal_word_possibilities("wayne", 1).unwrap().unwrap(),
+        TEST_SET.get_terminal_word_possibilities("wayne", 1, WordContext::default()).unwrap().unwrap(),
         vec![QueryWord::new_full(id_of("wayne"), 0)]
     );
 
-    // typo lookup -- don't include typo if it would be covered by the prefix anyway
+    // typo lookup -- don't include typo if it would be covered by the prefix anyway; "main" is
+    // also one edit from "sain", the start of "saint", but misspelled prefixes have to keep the
+    // first character
     assert_eq!(
-        TEST_SET.get_terminal_word_possibilities("main", 1).unwrap().unwrap(),
+        TEST_SET.get_terminal_word_possibilities("main", 1, WordContext::default()).unwrap().unwrap(),
         vec![
-            QueryWord::new_prefix((id_of("main"), id_of("maine")))
+            QueryWord::new_prefix((id_of("main"), id_of("maine"))),
         ]
     );
 
     // replacements:
     // standard replacement -- matches replaced word, doesn't match typos of replaced word
     assert_eq!(
-        TEST_SET.get_terminal_word_possibilities("street", 1).unwrap().unwrap(),
+        TEST_SET.get_terminal_word_possibilities("street", 1, WordContext::default()).unwrap().unwrap(),
         vec![QueryWord::new_full(id_of("st"), 0)]
     );
     // match the prefixd, and also a typo
     assert_eq!(
-        TEST_SET.get_terminal_word_possibilities("st", 1).unwrap().unwrap(),
+        TEST_SET.get_terminal_word_possibilities("st", 1, WordContext::default()).unwrap().unwrap(),
         vec![
             QueryWord::new_prefix((id_of("st"), id_of("street"))),
             QueryWord::new_full(id_of("ft"), 1),
         ]
     );
+    // a prefix that's both misspelled and incomplete
+    assert_eq!(
+        TEST_SET.get_terminal_word_possibilities("fortne", 1, WordContext::default()).unwrap().unwrap(),
+        vec![QueryWord::new_fuzzy_prefix((id_of("fortenberry"), id_of("fortenberry")), 1)]
+    );
+    // short prefixes, and prefixes misspelled in the first character, don't expand to the words
+    // they're an edit away from the start of
+    assert_eq!(
+        TEST_SET.get_terminal_word_possibilities("frt", 1, WordContext::default()).unwrap().unwrap(),
+        vec![QueryWord::new_full(id_of("ft"), 1)]
+    );
+    assert_eq!(
+        TEST_SET.get_terminal_word_possibilities("ro", 1, WordContext::default()).unwrap().unwrap(),
+        vec![QueryWord::new_full(id_of("rd"), 0)]
+    );
+    assert_eq!(
+        TEST_SET.get_terminal_word_possibilities("gortne", 1, WordContext::default()).unwrap(),
+        None
+    );
     // match a typo of the looked-up word and then replace
     assert_eq!(
-        TEST_SET.get_terminal_word_possibilities("stret", 1).unwrap().unwrap(),
+        TEST_SET.get_terminal_word_possibilities("stret", 1, WordContext::default()).unwrap().unwrap(),
         vec![QueryWord::new_full(id_of("st"), 1)]
     );
     // match all the s words on prefix match
     assert_eq!(
-        TEST_SET.get_terminal_word_possibilities("s", 1).unwrap().unwrap(),
+        TEST_SET.get_terminal_word_possibilities("s", 1, WordContext::default()).unwrap().unwrap(),
         vec![
             QueryWord::new_prefix((id_of("saint"), id_of("street")))
         ]
@@ -142,30 +163,32 @@ fn get_terminal_word_possibilities() -> () {
     // (we could alternatively interpret str as a typo of st instead of a prefix, but we don't
     // because the prefix version is lower-distance so it takes precedence)
     assert_eq!(
-        TEST_SET.get_terminal_word_possibilities("str", 1).unwrap().unwrap(),
+        TEST_SET.get_terminal_word_possibilities("str", 1, WordContext::default()).unwrap().unwrap(),
         vec![QueryWord::new_full(id_of("st"), 0)]
     );
 
     // ft/fort/forenberry:
     // we just need a prefix here since f covers everything
     assert_eq!(
-        TEST_SET.get_terminal_word_possibilities("f", 1).unwrap().unwrap(),
+        TEST_SET.get_terminal_word_possibilities("f", 1, WordContext::default()).unwrap().unwrap(),
         vec![
             QueryWord::new_prefix((id_of("fort"), id_of("ft"))),
         ]
     );
     // here we need both the prefix and the full word, because one possible termination gets
-    // replaced and the other doesn't (note that we don't include a fuzzy possibility)
+    // replaced and the other doesn't (note that we don't include a fuzzy possibility for "ft"); "fo"
+    // is also one edit from "ro", the start of "road", but it's too short to look for misspelled
+    // prefixes
     assert_eq!(
-        TEST_SET.get_terminal_word_possibilities("fo", 1).unwrap().unwrap(),
+        TEST_SET.get_terminal_word_possibilities("fo", 1, WordContext::default()).unwrap().unwrap(),
         vec![
             QueryWord::new_prefix((id_of("fort"), id_of("fortenberry"))),
-            QueryWord::new_full(id_of("ft"), 0)
+            QueryWord::new_full(id_of("ft"), 0),
         ]
     );
     // same as above even though this is now a full replaceable word
     assert_eq!(
-        TEST_SET.get_terminal_word_possibilities("fort", 1).unwrap().unwrap(),
+        TEST_SET.get_terminal_word_possibilities("fort", 1, WordContext::default()).unwrap().unwrap(),
         vec![
             QueryWord::new_prefix((id_of("fort"), id_of("fortenberry"))),
             QueryWord::new_full(id_of("ft"), 0)
@@ -173,14 +196,14 @@ fn get_terminal_word_possibilities() -> () {
     );
     // now we only emit the prefix option, with just one element
     assert_eq!(
-        TEST_SET.get_terminal_word_possibilities("forten", 1).unwrap().unwrap(),
+        TEST_SET.get_terminal_word_possibilities("forten", 1, WordContext::default()).unwrap().unwrap(),
         vec![
             QueryWord::new_prefix((id_of("fortenberry"), id_of("fortenberry")))
         ]
     );
     // and finally, emit a full word once we have the whole word
     assert_eq!(
-        TEST_SET.get_terminal_word_possibilities("fortenberry", 1).unwrap().unwrap(),
+        TEST_SET.get_terminal_word_possibilities("fortenberry", 1, WordContext::default()).unwrap().unwrap(),
         vec![
             QueryWord::new_full(id_of("fortenberry"), 0)
         ]
@@ -239,15 +262,15 @@ fn fuzzy_match() {
     assert_eq!(
         TEST_SET.fuzzy_match_str("100 main st", 1, 1).unwrap(),
         vec![
-            FuzzyMatchResult { edit_distance: 0, phrase: vec!["100".to_string(), "main".to_string(), "st".to_string()] },
-            FuzzyMatchResult { edit_distance: 1, phrase: vec!["100".to_string(), "maine".to_string(), "st".to_string()] }
+            FuzzyMatchResult { edit_distance: 0, phrase: vec!["100".to_string(), "main".to_string(), "st".to_string()], phrase_ids: vec![0, 1], displays: vec![] },
+            FuzzyMatchResult { edit_distance: 1, phrase: vec!["100".to_string(), "maine".to_string(), "st".to_string()], phrase_ids: vec![2], displays: vec![] }
         ]
     );
 
     // match to "100 main st" by fuzzy-matching, at distance 1
     assert_eq!(
         TEST_SET.fuzzy_match_str("100 main str", 1, 1).unwrap(),
-        vec![FuzzyMatchResult { edit_distance: 1, phrase: vec!["100".to_string(), "main".to_string(), "st".to_string()] }]
+        vec![FuzzyMatchResult { edit_distance: 1, phrase: vec!["100".to_string(), "main".to_string(), "st".to_string()], phrase_ids: vec![0, 1], displays: vec![] }]
     );
 
     // don't match anything if fuzzy search is disabled
@@ -265,7 +288,7 @@ fn fuzzy_match() {
     // match to "100 main street" by fuzzy-matching and then token-replace to "100 main st" at distance 1
     assert_eq!(
         TEST_SET.fuzzy_match_str("100 main stree", 1, 1).unwrap(),
-        vec![FuzzyMatchResult { edit_distance: 1, phrase: vec!["100".to_string(), "main".to_string(), "st".to_string()] }]
+        vec![FuzzyMatchResult { edit_distance: 1, phrase: vec!["100".to_string(), "main".to_string(), "st".to_string()], phrase_ids: vec![0, 1], displays: vec![] }]
     );
 
     // exact-match to 100 main street and then replace, so match at edit distance 0
@@ -273,8 +296,8 @@ fn fuzzy_match() {
     assert_eq!(
         TEST_SET.fuzzy_match_str("100 main street", 1, 1).unwrap(),
         vec![
-            FuzzyMatchResult { edit_distance: 0, phrase: vec!["100".to_string(), "main".to_string(), "st".to_string()] },
-            FuzzyMatchResult { edit_distance: 1, phrase: vec!["100".to_string(), "maine".to_string(), "st".to_string()] }
+            FuzzyMatchResult { edit_distance: 0, phrase: vec!["100".to_string(), "main".to_string(), "st".to_string()], phrase_ids: vec![0, 1], displays: vec![] },
+            FuzzyMatchResult { edit_distance: 1, phrase: vec!["100".to_string(), "maine".to_string(), "st".to_string()], phrase_ids: vec![2], displays: vec![] }
         ]
     );
 
@@ -283,7 +306,7 @@ fn fuzzy_match() {
         assert_eq!(
             TEST_SET.fuzzy_match_str(variant, 1, 1).unwrap(),
             vec![
-                FuzzyMatchResult { edit_distance: 0, phrase: vec!["100".to_string(), "ft".to_string(), "wayne".to_string(), "rd".to_string()] }
+                FuzzyMatchResult { edit_distance: 0, phrase: vec!["100".to_string(), "ft".to_string(), "wayne".to_string(), "rd".to_string()], phrase_ids: vec![3], displays: vec![] }
             ]
         )
     }
@@ -296,8 +319,8 @@ fn fuzzy_match_prefix() {
     assert_eq!(
         TEST_SET.fuzzy_match_prefix_str("100 main s", 1, 1).unwrap(),
         vec![
-            FuzzyMatchResult { edit_distance: 0, phrase: vec!["100".to_string(), "main".to_string(), "s".to_string()] },
-            FuzzyMatchResult { edit_distance: 1, phrase: vec!["100".to_string(), "maine".to_string(), "s".to_string()] }
+            FuzzyMatchResult { edit_distance: 0, phrase: vec!["100".to_string(), "main".to_string(), "s".to_string()], phrase_ids: vec![], displays: vec![] },
+            FuzzyMatchResult { edit_distance: 1, phrase: vec!["100".to_string(), "maine".to_string(), "s".to_string()], phrase_ids: vec![], displays: vec![] }
         ]
     );
 
@@ -306,8 +329,8 @@ fn fuzzy_match_prefix() {
     assert_eq!(
         TEST_SET.fuzzy_match_prefix_str("100 main st", 1, 1).unwrap(),
         vec![
-            FuzzyMatchResult { edit_distance: 0, phrase: vec!["100".to_string(), "main".to_string(), "st".to_string()] },
-            FuzzyMatchResult { edit_distance: 1, phrase: vec!["100".to_string(), "maine".to_string(), "st".to_string()] }
+            FuzzyMatchResult { edit_distance: 0, phrase: vec!["100".to_string(), "main".to_string(), "st".to_string()], phrase_ids: vec![], displays: vec![] },
+            FuzzyMatchResult { edit_distance: 1, phrase: vec!["100".to_string(), "maine".to_string(), "st".to_string()], phrase_ids: vec![], displays: vec![] }
         ]
     );
 
@@ -315,8 +338,8 @@ fn fuzzy_match_prefix() {
     assert_eq!(
         TEST_SET.fuzzy_match_prefix_str("100 main str", 1, 1).unwrap(),
         vec![
-            FuzzyMatchResult { edit_distance: 0, phrase: vec!["100".to_string(), "main".to_string(), "st".to_string()] },
-            FuzzyMatchResult { edit_distance: 1, phrase: vec!["100".to_string(), "maine".to_string(), "st".to_string()] }
+            FuzzyMatchResult { edit_distance: 0, phrase: vec!["100".to_string(), "main".to_string(), "st".to_string()], phrase_ids: vec![0, 1], displays: vec![] },
+            FuzzyMatchResult { edit_distance: 1, phrase: vec!["100".to_string(), "maine".to_string(), "st".to_string()], phrase_ids: vec![2], displays: vec![] }
         ]
     );
 
@@ -324,7 +347,7 @@ fn fuzzy_match_prefix() {
     assert_eq!(
         TEST_SET.fuzzy_match_prefix_str("100 main str", 0, 0).unwrap(),
         vec![
-            FuzzyMatchResult { edit_distance: 0, phrase: vec!["100".to_string(), "main".to_string(), "st".to_string()] },
+            FuzzyMatchResult { edit_distance: 0, phrase: vec!["100".to_string(), "main".to_string(), "st".to_string()], phrase_ids: vec![0, 1], displays: vec![] },
         ]
     );
 
@@ -332,8 +355,8 @@ fn fuzzy_match_prefix() {
     assert_eq!(
         TEST_SET.fuzzy_match_prefix_str("100 main stre", 1, 1).unwrap(),
         vec![
-            FuzzyMatchResult { edit_distance: 0, phrase: vec!["100".to_string(), "main".to_string(), "st".to_string()] },
-            FuzzyMatchResult { edit_distance: 1, phrase: vec!["100".to_string(), "maine".to_string(), "st".to_string()] }
+            FuzzyMatchResult { edit_distance: 0, phrase: vec!["100".to_string(), "main".to_string(), "st".to_string()], phrase_ids: vec![0, 1], displays: vec![] },
+            FuzzyMatchResult { edit_distance: 1, phrase: vec!["100".to_string(), "maine".to_string(), "st".to_string()], phrase_ids: vec![2], displays: vec![] }
         ]
     );
 
@@ -341,8 +364,8 @@ fn fuzzy_match_prefix() {
     assert_eq!(
         TEST_SET.fuzzy_match_prefix_str("100 main stree", 1, 1).unwrap(),
         vec![
-            FuzzyMatchResult { edit_distance: 0, phrase: vec!["100".to_string(), "main".to_string(), "st".to_string()] },
-            FuzzyMatchResult { edit_distance: 1, phrase: vec!["100".to_string(), "maine".to_string(), "st".to_string()] }
+            FuzzyMatchResult { edit_distance: 0, phrase: vec!["100".to_string(), "main".to_string(), "st".to_string()], phrase_ids: vec![0, 1], displays: vec![] },
+            FuzzyMatchResult { edit_distance: 1, phrase: vec!["100".to_string(), "maine".to_string(), "st".to_string()], phrase_ids: vec![2], displays: vec![] }
         ]
     );
 
@@ -351,8 +374,8 @@ fn fuzzy_match_prefix() {
     assert_eq!(
         TEST_SET.fuzzy_match_prefix_str("100 main street", 1, 1).unwrap(),
         vec![
-            FuzzyMatchResult { edit_distance: 0, phrase: vec!["100".to_string(), "main".to_string(), "st".to_string()] },
-            FuzzyMatchResult { edit_distance: 1, phrase: vec!["100".to_string(), "maine".to_string(), "st".to_string()] }
+            FuzzyMatchResult { edit_distance: 0, phrase: vec!["100".to_string(), "main".to_string(), "st".to_string()], phrase_ids: vec![0, 1], displays: vec![] },
+            FuzzyMatchResult { edit_distance: 1, phrase: vec!["100".to_string(), "maine".to_string(), "st".to_string()], phrase_ids: vec![2], displays: vec![] }
         ]
     );
 
@@ -361,7 +384,7 @@ fn fuzzy_match_prefix() {
     assert_eq!(
         TEST_SET.fuzzy_match_prefix_str("100 f", 1, 1).unwrap(),
         vec![
-            FuzzyMatchResult { edit_distance: 0, phrase: vec!["100".to_string(), "f".to_string()] }
+            FuzzyMatchResult { edit_distance: 0, phrase: vec!["100".to_string(), "f".to_string()], phrase_ids: vec![], displays: vec![] }
         ]
     );
 
@@ -370,19 +393,19 @@ fn fuzzy_match_prefix() {
     assert_eq!(
         TEST_SET.fuzzy_match_prefix_str("100 fo", 1, 1).unwrap(),
         vec![
-            FuzzyMatchResult { edit_distance: 0, phrase: vec!["100".to_string(), "fo".to_string()] },
-            FuzzyMatchResult { edit_distance: 0, phrase: vec!["100".to_string(), "ft".to_string()] }
+            FuzzyMatchResult { edit_distance: 0, phrase: vec!["100".to_string(), "fo".to_string()], phrase_ids: vec![], displays: vec![] },
+            FuzzyMatchResult { edit_distance: 0, phrase: vec!["100".to_string(), "ft".to_string()], phrase_ids: vec![], displays: vec![] }
         ]
     );
 
     // this one, interestingly, matches "100 ft" in two different ways: by fuzzy-matching to "100 ft",
     // and by fuzzy-matching to "100 fort" and then replacing. Only one happened here but it doesn't
-    // matter which -- we should see only one response, "100 ft" at distance 1 (and not fortenberry
-    // at all)
+    // matter which -- we should see only one response, "100 ft" at distance 1. "frt" is also one
+    // edit away from the start of "fortenberry", but it's too short to match as a misspelled prefix
     assert_eq!(
         TEST_SET.fuzzy_match_prefix_str("100 frt", 1, 1).unwrap(),
         vec![
-            FuzzyMatchResult { edit_distance: 1, phrase: vec!["100".to_string(), "ft".to_string()] }
+            FuzzyMatchResult { edit_distance: 1, phrase: vec!["100".to_string(), "ft".to_string()], phrase_ids: vec![], displays: vec![] }
         ]
     );
 
@@ -390,8 +413,8 @@ fn fuzzy_match_prefix() {
     assert_eq!(
         TEST_SET.fuzzy_match_prefix_str("100 fort", 1, 1).unwrap(),
         vec![
-            FuzzyMatchResult { edit_distance: 0, phrase: vec!["100".to_string(), "fort".to_string()] },
-            FuzzyMatchResult { edit_distance: 0, phrase: vec!["100".to_string(), "ft".to_string()] }
+            FuzzyMatchResult { edit_distance: 0, phrase: vec!["100".to_string(), "fort".to_string()], phrase_ids: vec![], displays: vec![] },
+            FuzzyMatchResult { edit_distance: 0, phrase: vec!["100".to_string(), "ft".to_string()], phrase_ids: vec![], displays: vec![] }
         ]
     );
 
@@ -399,8 +422,8 @@ fn fuzzy_match_prefix() {
     assert_eq!(
         TEST_SET.fuzzy_match_prefix_str("100 forte", 1, 1).unwrap(),
         vec![
-            FuzzyMatchResult { edit_distance: 0, phrase: vec!["100".to_string(), "forte".to_string()] },
-            FuzzyMatchResult { edit_distance: 1, phrase: vec!["100".to_string(), "ft".to_string()] }
+            FuzzyMatchResult { edit_distance: 0, phrase: vec!["100".to_string(), "forte".to_string()], phrase_ids: vec![], displays: vec![] },
+            FuzzyMatchResult { edit_distance: 1, phrase: vec!["100".to_string(), "ft".to_string()], phrase_ids: vec![], displays: vec![] }
         ]
     );
 
@@ -408,7 +431,7 @@ fn fuzzy_match_prefix() {
     assert_eq!(
         TEST_SET.fuzzy_match_prefix_str("100 forten", 1, 1).unwrap(),
         vec![
-            FuzzyMatchResult { edit_distance: 0, phrase: vec!["100".to_string(), "forten".to_string()] },
+            FuzzyMatchResult { edit_distance: 0, phrase: vec!["100".to_string(), "forten".to_string()], phrase_ids: vec![], displays: vec![] },
         ]
     );
 
@@ -424,7 +447,7 @@ fn fuzzy_match_prefix() {
         assert_eq!(
             TEST_SET.fuzzy_match_prefix_str(variant, 1, 1).unwrap(),
             vec![
-                FuzzyMatchResult { edit_distance: 0, phrase: vec!["100".to_string(), "ft".to_string(), "wayne".to_string(), "rd".to_string()] }
+                FuzzyMatchResult { edit_distance: 0, phrase: vec!["100".to_string(), "ft".to_string(), "wayne".to_string(), "rd".to_string()], phrase_ids: vec![3], displays: vec![] }
             ]
         )
     }
@@ -446,7 +469,7 @@ fn fuzzy_match_windows() -> () {
             vec![
                 FuzzyWindowResult {
                     edit_distance: 0,
-                    phrase: vec!["100".to_string(), "ft".to_string(), "wayne".to_string(), "rd".to_string()],
+                    phrase: vec!["100".to_string(), "ft".to_string(), "wayne".to_string(), "rd".to_string()], phrase_ids: vec![3], displays: vec![],
                     start_position: 0,
                     ends_in_prefix: true
                 }
@@ -465,7 +488,7 @@ fn fuzzy_match_windows() -> () {
             vec![
                 FuzzyWindowResult {
                     edit_distance: 0,
-                    phrase: vec!["100".to_string(), "ft".to_string(), "wayne".to_string(), "rd".to_string()],
+                    phrase: vec!["100".to_string(), "ft".to_string(), "wayne".to_string(), "rd".to_string()], phrase_ids: vec![3], displays: vec![],
                     start_position: 0,
                     ends_in_prefix: false
                 }
@@ -484,7 +507,7 @@ fn fuzzy_match_windows() -> () {
             vec![
                 FuzzyWindowResult {
                     edit_distance: 0,
-                    phrase: vec!["100".to_string(), "ft".to_string(), "wayne".to_string(), "rd".to_string()],
+                    phrase: vec!["100".to_string(), "ft".to_string(), "wayne".to_string(), "rd".to_string()], phrase_ids: vec![3], displays: vec![],
                     start_position: 2,
                     ends_in_prefix: true
                 }
@@ -498,13 +521,13 @@ fn fuzzy_match_windows() -> () {
         vec![
             FuzzyWindowResult {
                 edit_distance: 0,
-                phrase: vec!["100".to_string(), "fo".to_string()],
+                phrase: vec!["100".to_string(), "fo".to_string()], phrase_ids: vec![], displays: vec![],
                 start_position: 2,
                 ends_in_prefix: true
             },
             FuzzyWindowResult {
                 edit_distance: 0,
-                phrase: vec!["100".to_string(), "ft".to_string()],
+                phrase: vec!["100".to_string(), "ft".to_string()], phrase_ids: vec![], displays: vec![],
                 start_position: 2,
                 ends_in_prefix: true
             }
@@ -0,0 +1,265 @@
+use std::error::Error;
+
+use ::phrase::query::QueryWord;
+use super::WordReplacement;
+
+/// The end of a phrase a scoped word replacement applies at.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ReplacementPosition {
+    /// The first word of a phrase.
+    Start,
+    /// The last word of a phrase.
+    End,
+}
+
+/// Where a word replacement applies. A replacement with no scope applies everywhere; otherwise,
+/// all of the conditions given have to hold, so that, e.g., "st" can stand for "saint" at the
+/// start of a phrase and for "street" at the end of one.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Default)]
+pub struct ReplacementScope {
+    /// Only replace the word when it's at this end of its phrase.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub position: Option<ReplacementPosition>,
+    /// Only replace the word when the word before it is one of these.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub preceded_by: Vec<String>,
+    /// Only replace the word when the word after it is one of these.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub followed_by: Vec<String>,
+    /// Only replace the word in sets whose `language` option is this language tag.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub language: Option<String>,
+}
+
+impl ReplacementScope {
+    /// Whether this is the default scope, with no conditions at all.
+    pub fn is_unscoped(&self) -> bool {
+        *self == ReplacementScope::default()
+    }
+
+    // whether the replacement depends on where the word is, rather than just on the set
+    pub(super) fn has_conditions(&self) -> bool {
+        self.position.is_some() || !self.preceded_by.is_empty() || !self.followed_by.is_empty()
+    }
+
+    pub(super) fn applies_to_language(&self, language: Option<&str>) -> bool {
+        match self.language {
+            Some(ref scope_language) => Some(scope_language.as_str()) == language,
+            None => true,
+        }
+    }
+}
+
+// whether a replacement applies to every occurrence of its word in a set with this language, in
+// which case it's handled by giving the word the ID of its replacement, like an unscoped one
+pub(super) fn is_unconditional(word_replacement: &WordReplacement, language: Option<&str>) -> bool {
+    !word_replacement.scope.has_conditions() && word_replacement.scope.applies_to_language(language)
+}
+
+// a word replacement that depends on where the word is, as word IDs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct ScopedReplacementRule {
+    pub from: u32,
+    pub to: u32,
+    position: Option<ReplacementPosition>,
+    preceded_by: Vec<u32>,
+    followed_by: Vec<u32>,
+}
+
+// what's on one side of a word, as far as the phrase it matches is concerned
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(super) enum Neighbor {
+    // the word is at that end of the phrase
+    Nothing,
+    // there's a word there, with this ID if it's in the lexicon
+    Word(Option<u32>),
+    // there might be this word there, or the phrase might end, as with windows
+    MaybeWord(Option<u32>),
+    // there might be anything there, as after the last word of a prefix query
+    Unknown,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(super) struct WordContext {
+    pub previous: Neighbor,
+    pub next: Neighbor,
+}
+
+impl Default for WordContext {
+    fn default() -> WordContext {
+        WordContext { previous: Neighbor::Unknown, next: Neighbor::Unknown }
+    }
+}
+
+impl ScopedReplacementRule {
+    // whether the rule applies to its word in this context: Some(true) or Some(false) if that's
+    // certain, or None if it depends on which phrase the word ends up matching
+    pub fn applies(&self, context: &WordContext) -> Option<bool> {
+        let mut conditions: Vec<Option<bool>> = Vec::with_capacity(3);
+        match self.position {
+            Some(ReplacementPosition::Start) => conditions.push(is_edge(context.previous)),
+            Some(ReplacementPosition::End) => conditions.push(is_edge(context.next)),
+            None => (),
+        }
+        if !self.preceded_by.is_empty() {
+            conditions.push(is_one_of(context.previous, &self.preceded_by));
+        }
+        if !self.followed_by.is_empty() {
+            conditions.push(is_one_of(context.next, &self.followed_by));
+        }
+
+        if conditions.contains(&Some(false)) {
+            Some(false)
+        } else if conditions.contains(&None) {
+            None
+        } else {
+            Some(true)
+        }
+    }
+}
+
+fn is_edge(neighbor: Neighbor) -> Option<bool> {
+    match neighbor {
+        Neighbor::Nothing => Some(true),
+        Neighbor::Word(_) => Some(false),
+        Neighbor::MaybeWord(_) | Neighbor::Unknown => None,
+    }
+}
+
+fn is_one_of(neighbor: Neighbor, ids: &[u32]) -> Option<bool> {
+    match neighbor {
+        Neighbor::Nothing => Some(false),
+        Neighbor::Word(Some(id)) => Some(ids.contains(&id)),
+        Neighbor::MaybeWord(Some(id)) if !ids.contains(&id) => Some(false),
+        // a word that isn't in the lexicon might be a misspelling of one of them
+        Neighbor::Word(None) | Neighbor::MaybeWord(_) | Neighbor::Unknown => None,
+    }
+}
+
+// turn the scoped word replacements that apply to a set with this language into rules, given a
+// way to find a word's ID; context words that aren't in the lexicon can't match anything, so
+// they're left out, along with any rule that has none left on a side that needs one
+pub(super) fn resolve_rules<F>(word_replacements: &[WordReplacement], language: Option<&str>, id_of: F) -> Result<Vec<ScopedReplacementRule>, Box<Error>>
+    where F: Fn(&str) -> Option<u32>
+{
+    let mut rules: Vec<ScopedReplacementRule> = Vec::new();
+    for word_replacement in word_replacements {
+        let scope = &word_replacement.scope;
+        if !scope.has_conditions() || !scope.applies_to_language(language) {
+            continue;
+        }
+        let rule = ScopedReplacementRule {
+            from: id_of(&word_replacement.from).ok_or_else(|| format!("Substitution from-word {} not in lexicon", word_replacement.from))?,
+            to: id_of(&word_replacement.to).ok_or_else(|| format!("Substitution to-word {} not in lexicon", word_replacement.to))?,
+            position: scope.position,
+            preceded_by: scope.preceded_by.iter().filter_map(|word| id_of(word)).collect(),
+            followed_by: scope.followed_by.iter().filter_map(|word| id_of(word)).collect(),
+        };
+        let unmatchable = |words: &[String], ids: &[u32]| !words.is_empty() && ids.is_empty();
+        if unmatchable(&scope.preceded_by, &rule.preceded_by) || unmatchable(&scope.followed_by, &rule.followed_by) {
+            continue;
+        }
+        rules.push(rule);
+    }
+    Ok(rules)
+}
+
+// replace the words of a complete phrase wherever a rule applies; the conditions are checked
+// against the phrase as it was, and if more than one rule applies to a word, the first one wins
+pub(super) fn replace_words(phrase: &[u32], rules: &[ScopedReplacementRule]) -> Vec<u32> {
+    let neighbor = |i: Option<usize>| match i.and_then(|i| phrase.get(i)) {
+        Some(id) => Neighbor::Word(Some(*id)),
+        None => Neighbor::Nothing,
+    };
+    phrase.iter().enumerate().map(|(i, id)| {
+        let context = WordContext { previous: neighbor(i.checked_sub(1)), next: neighbor(Some(i + 1)) };
+        rules.iter()
+            .find(|rule| rule.from == *id && rule.applies(&context) == Some(true))
+            .map_or(*id, |rule| rule.to)
+    }).collect()
+}
+
+// apply the rules to a query word's variants: a variant a rule certainly applies to is replaced
+// by the rule's word, at the same edit distance, and one it might apply to gets the rule's word
+// added alongside it
+pub(super) fn replace_variants(variants: &mut Vec<QueryWord>, context: &WordContext, rules: &[ScopedReplacementRule]) {
+    let mut replaced: Vec<u32> = Vec::new();
+    let mut additions: Vec<(u32, u8)> = Vec::new();
+    for rule in rules {
+        let (is_full, edit_distance) = match variants.iter().find(|variant| variant.contains_id(rule.from)) {
+            Some(variant) => (matches!(variant, QueryWord::Full { .. }), variant.edit_distance()),
+            None => continue,
+        };
+        match rule.applies(context) {
+            Some(false) => continue,
+            // a prefix covers more words than this one, so it has to stay
+            Some(true) if is_full => replaced.push(rule.from),
+            _ => (),
+        }
+        additions.push((rule.to, edit_distance));
+    }
+    if additions.is_empty() {
+        return;
+    }
+
+    variants.retain(|variant| match variant {
+        QueryWord::Full { id, .. } => !replaced.contains(id),
+        _ => true,
+    });
+    for (id, addition_edit_distance) in additions {
+        match variants.iter_mut().find(|variant| variant.contains_id(id)) {
+            Some(QueryWord::Full { ref mut edit_distance, .. }) => if *edit_distance > addition_edit_distance {
+                *edit_distance = addition_edit_distance;
+            },
+            // already covered by a prefix
+            Some(_) => (),
+            None => variants.push(QueryWord::new_full(id, addition_edit_distance)),
+        }
+    }
+    variants.sort_by_key(|variant| variant.edit_distance());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rule(from: u32, to: u32, position: Option<ReplacementPosition>, preceded_by: &[u32], followed_by: &[u32]) -> ScopedReplacementRule {
+        ScopedReplacementRule { from, to, position, preceded_by: preceded_by.to_vec(), followed_by: followed_by.to_vec() }
+    }
+
+    #[test]
+    fn replace_words_test() -> () {
+        let rules = vec![
+            rule(1, 2, Some(ReplacementPosition::Start), &[], &[]),
+            rule(1, 3, Some(ReplacementPosition::End), &[], &[]),
+            rule(4, 5, None, &[], &[6]),
+        ];
+        assert_eq!(replace_words(&[1, 7], &rules), vec![2, 7]);
+        assert_eq!(replace_words(&[7, 1], &rules), vec![7, 3]);
+        assert_eq!(replace_words(&[7, 1, 7], &rules), vec![7, 1, 7]);
+        assert_eq!(replace_words(&[1], &rules), vec![2]);
+        assert_eq!(replace_words(&[4, 6, 4], &rules), vec![5, 6, 4]);
+    }
+
+    #[test]
+    fn replace_variants_test() -> () {
+        let rules = vec![rule(1, 2, Some(ReplacementPosition::Start), &[], &[]), rule(4, 5, None, &[6], &[])];
+
+        // certain: the word is swapped for its replacement
+        let mut variants = vec![QueryWord::new_full(1, 0), QueryWord::new_full(7, 1)];
+        replace_variants(&mut variants, &WordContext { previous: Neighbor::Nothing, next: Neighbor::Unknown }, &rules);
+        assert_eq!(variants, vec![QueryWord::new_full(2, 0), QueryWord::new_full(7, 1)]);
+
+        // uncertain: both are kept
+        let mut variants = vec![QueryWord::new_full(1, 1)];
+        replace_variants(&mut variants, &WordContext { previous: Neighbor::MaybeWord(Some(6)), next: Neighbor::Unknown }, &rules);
+        assert_eq!(variants, vec![QueryWord::new_full(1, 1), QueryWord::new_full(2, 1)]);
+
+        // ruled out
+        let mut variants = vec![QueryWord::new_full(4, 0)];
+        replace_variants(&mut variants, &WordContext { previous: Neighbor::Word(Some(7)), next: Neighbor::Nothing }, &rules);
+        assert_eq!(variants, vec![QueryWord::new_full(4, 0)]);
+        replace_variants(&mut variants, &WordContext { previous: Neighbor::Word(None), next: Neighbor::Nothing }, &rules);
+        assert_eq!(variants, vec![QueryWord::new_full(4, 0), QueryWord::new_full(5, 0)]);
+    }
+}
@@ -0,0 +1,111 @@
+use std::cmp::Ordering;
+
+/// What we know about how a match was arrived at, for the purposes of ranking it against other
+/// matches for the same query.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct MatchFeatures {
+    // the edit distance charged to each word of the match, in order
+    pub word_edit_distances: Vec<u8>,
+    // whether the last word of the match is a prefix (i.e., the user might not be done typing it)
+    pub ends_in_prefix: bool,
+    // if the match ends in a prefix, how many characters the first word it could complete to has
+    // beyond what was typed
+    pub prefix_completion_length: usize,
+}
+
+impl MatchFeatures {
+    pub fn edit_distance(&self) -> u8 {
+        self.word_edit_distances.iter().sum()
+    }
+
+    pub fn phrase_length(&self) -> usize {
+        self.word_edit_distances.len()
+    }
+
+    pub fn corrected_positions(&self) -> Vec<usize> {
+        self.word_edit_distances.iter().enumerate().filter_map(
+            |(i, &d)| if d > 0 { Some(i) } else { None }
+        ).collect()
+    }
+}
+
+/// Assigns a score to a match; higher scores are better.
+pub trait Scorer {
+    fn score(&self, features: &MatchFeatures) -> f64;
+}
+
+/// Penalizes each unit of edit distance, with an extra penalty for corrections to the first word
+/// (people are less likely to misspell the beginning of what they're typing), penalizes prefixes
+/// by how much is left to type, and rewards longer phrases, which make for stronger evidence.
+#[derive(Debug, PartialEq, Clone)]
+pub struct DefaultScorer {
+    pub edit_distance_weight: f64,
+    pub first_word_edit_weight: f64,
+    pub completion_weight: f64,
+    pub phrase_length_weight: f64,
+}
+
+impl Default for DefaultScorer {
+    fn default() -> DefaultScorer {
+        DefaultScorer {
+            edit_distance_weight: 1.0,
+            first_word_edit_weight: 0.5,
+            completion_weight: 0.05,
+            phrase_length_weight: 0.1,
+        }
+    }
+}
+
+impl Scorer for DefaultScorer {
+    fn score(&self, features: &MatchFeatures) -> f64 {
+        let mut score = self.phrase_length_weight * features.phrase_length() as f64;
+        for (i, &d) in features.word_edit_distances.iter().enumerate() {
+            let weight = if i == 0 {
+                self.edit_distance_weight + self.first_word_edit_weight
+            } else {
+                self.edit_distance_weight
+            };
+            score -= weight * d as f64;
+        }
+        score - self.completion_weight * features.prefix_completion_length as f64
+    }
+}
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize)]
+pub struct ScoredResult<T> {
+    pub score: f64,
+    pub features: MatchFeatures,
+    pub result: T,
+}
+
+pub fn score_results<T, S: Scorer>(results: Vec<(T, MatchFeatures)>, scorer: &S, sorted: bool) -> Vec<ScoredResult<T>> {
+    let mut scored: Vec<ScoredResult<T>> = results.into_iter().map(|(result, features)| {
+        ScoredResult { score: scorer.score(&features), features, result }
+    }).collect();
+    if sorted {
+        // stable, so equally-scored results stay in traversal order
+        scored.sort_by(|a, b| b.score.partial_cmp(&a.score).unwrap_or(Ordering::Equal));
+    }
+    scored
+}
+
+#[test]
+fn default_scorer_test() {
+    let scorer = DefaultScorer::default();
+    let exact = MatchFeatures { word_edit_distances: vec![0, 0, 0], ends_in_prefix: false, prefix_completion_length: 0 };
+    let late_typo = MatchFeatures { word_edit_distances: vec![0, 0, 1], ends_in_prefix: false, prefix_completion_length: 0 };
+    let early_typo = MatchFeatures { word_edit_distances: vec![1, 0, 0], ends_in_prefix: false, prefix_completion_length: 0 };
+    let short_prefix = MatchFeatures { word_edit_distances: vec![0, 0, 0], ends_in_prefix: true, prefix_completion_length: 2 };
+    let long_prefix = MatchFeatures { word_edit_distances: vec![0, 0, 0], ends_in_prefix: true, prefix_completion_length: 8 };
+    let shorter = MatchFeatures { word_edit_distances: vec![0, 0], ends_in_prefix: false, prefix_completion_length: 0 };
+
+    assert!(scorer.score(&exact) > scorer.score(&late_typo));
+    assert!(scorer.score(&late_typo) > scorer.score(&early_typo));
+    assert!(scorer.score(&short_prefix) > scorer.score(&long_prefix));
+    assert!(scorer.score(&exact) > scorer.score(&shorter));
+    assert_eq!(early_typo.corrected_positions(), vec![0]);
+    assert_eq!(early_typo.edit_distance(), 1);
+
+    let ranked = score_results(vec![("early", early_typo), ("exact", exact), ("late", late_typo)], &scorer, true);
+    assert_eq!(ranked.iter().map(|r| r.result).collect::<Vec<_>>(), vec!["exact", "late", "early"]);
+}
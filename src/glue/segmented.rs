@@ -0,0 +1,288 @@
+use std::path::Path;
+use std::error::Error;
+use std::io::{Error as IoError, ErrorKind as IoErrorKind, BufReader};
+use std::fs;
+use std::fmt::Debug;
+
+use serde_json;
+
+use super::{FuzzyPhraseSet, FuzzyPhraseSetMetadata, FuzzyMatchResult, FuzzyWindowResult, WordReplacement, rebuild};
+
+/// A base `FuzzyPhraseSet` together with any number of smaller delta sets built afterwards, so that
+/// new phrases can be added without rebuilding everything. Queries go to every segment and their
+/// results are combined; `merge` folds all the segments into a single new set.
+///
+/// All the segments have to have been built with the same options and word and phrase
+/// replacements.
+pub struct SegmentedFuzzyPhraseSet {
+    segments: Vec<FuzzyPhraseSet>,
+    metadata: FuzzyPhraseSetMetadata,
+    // every query replacement loaded so far, for segments added later
+    query_replacements: Vec<WordReplacement>,
+}
+
+impl SegmentedFuzzyPhraseSet {
+    pub fn from_paths<P: AsRef<Path>>(paths: &[P]) -> Result<Self, Box<Error>> {
+        if paths.len() == 0 {
+            return Err(Box::new(IoError::new(IoErrorKind::InvalidInput, "At least one segment is required")));
+        }
+        let mut segmented = SegmentedFuzzyPhraseSet {
+            segments: Vec::with_capacity(paths.len()),
+            metadata: read_metadata(paths[0].as_ref())?,
+            query_replacements: Vec::new(),
+        };
+        for path in paths {
+            segmented.add_segment(path)?;
+        }
+        Ok(segmented)
+    }
+
+    /// Start querying another delta set alongside the existing segments.
+    pub fn add_segment<P: AsRef<Path>>(&mut self, path: P) -> Result<(), Box<Error>> {
+        let path = path.as_ref();
+        if read_metadata(path)? != self.metadata {
+            return Err(Box::new(IoError::new(
+                IoErrorKind::InvalidData,
+                "Segment was built with different options or replacements than the others"
+            )));
+        }
+        let mut segment = FuzzyPhraseSet::from_path(path)?;
+        segment.load_query_replacements(self.query_replacements.clone())?;
+        self.segments.push(segment);
+        Ok(())
+    }
+
+    /// Load query replacements into every segment, including ones added later; see
+    /// `FuzzyPhraseSet::load_query_replacements`.
+    pub fn load_query_replacements(&mut self, word_replacements: Vec<WordReplacement>) -> Result<(), Box<Error>> {
+        for segment in self.segments.iter_mut() {
+            segment.load_query_replacements(word_replacements.clone())?;
+        }
+        self.query_replacements.extend(word_replacements);
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.segments.len()
+    }
+
+    pub fn contains<T: AsRef<str>>(&self, phrase: &[T]) -> Result<bool, Box<Error>> {
+        for segment in &self.segments {
+            if segment.contains(phrase)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    pub fn contains_prefix<T: AsRef<str>>(&self, phrase: &[T]) -> Result<bool, Box<Error>> {
+        for segment in &self.segments {
+            if segment.contains_prefix(phrase)? {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    pub fn fuzzy_match<T: AsRef<str>>(&self, phrase: &[T], max_word_dist: u8, max_phrase_dist: u8) -> Result<Vec<FuzzyMatchResult>, Box<Error>> {
+        let mut results: Vec<FuzzyMatchResult> = Vec::new();
+        for segment in &self.segments {
+            combine_match_results(&mut results, segment.fuzzy_match(phrase, max_word_dist, max_phrase_dist)?);
+        }
+        Ok(results)
+    }
+
+    pub fn fuzzy_match_prefix<T: AsRef<str>>(&self, phrase: &[T], max_word_dist: u8, max_phrase_dist: u8) -> Result<Vec<FuzzyMatchResult>, Box<Error>> {
+        let mut results: Vec<FuzzyMatchResult> = Vec::new();
+        for segment in &self.segments {
+            combine_match_results(&mut results, segment.fuzzy_match_prefix(phrase, max_word_dist, max_phrase_dist)?);
+        }
+        Ok(results)
+    }
+
+    pub fn fuzzy_match_windows<T: AsRef<str>>(&self, phrase: &[T], max_word_dist: u8, max_phrase_dist: u8, ends_in_prefix: bool) -> Result<Vec<FuzzyWindowResult>, Box<Error>> {
+        let mut results: Vec<FuzzyWindowResult> = Vec::new();
+        for segment in &self.segments {
+            for result in segment.fuzzy_match_windows(phrase, max_word_dist, max_phrase_dist, ends_in_prefix)? {
+                let existing = results.iter().position(|r| {
+                    r.phrase == result.phrase &&
+                    r.start_position == result.start_position &&
+                    r.ends_in_prefix == result.ends_in_prefix
+                });
+                match existing {
+                    Some(i) => {
+                        results[i].edit_distance = results[i].edit_distance.min(result.edit_distance);
+                        combine_phrase_ids(&mut results[i].phrase_ids, result.phrase_ids);
+                        combine_displays(&mut results[i].displays, result.displays);
+                    },
+                    None => results.push(result),
+                }
+            }
+        }
+        Ok(results)
+    }
+
+    pub fn fuzzy_match_multi<T: AsRef<str> + Ord + Debug, U: AsRef<[T]>>(&self, phrases: &[(U, bool)], max_word_dist: u8, max_phrase_dist: u8) -> Result<Vec<Vec<FuzzyMatchResult>>, Box<Error>> {
+        let mut results: Vec<Vec<FuzzyMatchResult>> = vec![vec![]; phrases.len()];
+        for segment in &self.segments {
+            for (i, segment_results) in segment.fuzzy_match_multi(phrases, max_word_dist, max_phrase_dist)?.into_iter().enumerate() {
+                combine_match_results(&mut results[i], segment_results);
+            }
+        }
+        Ok(results)
+    }
+
+    /// Mark a phrase as deleted in every segment that has it. Returns false if none of them did.
+    pub fn delete<T: AsRef<str>>(&mut self, phrase: &[T]) -> Result<bool, Box<Error>> {
+        let mut deleted = false;
+        for segment in self.segments.iter_mut() {
+            deleted = segment.delete(phrase)? || deleted;
+        }
+        Ok(deleted)
+    }
+
+    /// Write a single set containing every phrase from every segment to `path`, leaving out
+    /// deleted ones. The lexicons are unioned and the words renumbered, and all three graphs are
+    /// rebuilt from scratch. `path` must not be the directory of any of the segments, which are
+    /// still in use while it's written.
+    pub fn merge<P: AsRef<Path>>(&self, path: P) -> Result<(), Box<Error>> {
+        let segments: Vec<&FuzzyPhraseSet> = self.segments.iter().collect();
+        rebuild(&segments, path)
+    }
+}
+
+fn read_metadata(path: &Path) -> Result<FuzzyPhraseSetMetadata, Box<Error>> {
+    let metadata_reader = BufReader::new(fs::File::open(path.join(Path::new("metadata.json")))?);
+    Ok(serde_json::from_reader(metadata_reader)?)
+}
+
+// the same phrase might turn up in more than one segment; report it once, at its best distance,
+// with all of its IDs and their display strings
+fn combine_match_results(results: &mut Vec<FuzzyMatchResult>, new_results: Vec<FuzzyMatchResult>) {
+    for result in new_results {
+        match results.iter().position(|r| r.phrase == result.phrase) {
+            Some(i) => {
+                results[i].edit_distance = results[i].edit_distance.min(result.edit_distance);
+                combine_phrase_ids(&mut results[i].phrase_ids, result.phrase_ids);
+                combine_displays(&mut results[i].displays, result.displays);
+            },
+            None => results.push(result),
+        }
+    }
+}
+
+fn combine_phrase_ids(phrase_ids: &mut Vec<u64>, new_phrase_ids: Vec<u64>) {
+    phrase_ids.extend(new_phrase_ids);
+    phrase_ids.sort();
+    phrase_ids.dedup();
+}
+
+// as with the IDs; if two segments have the same ID with different display strings, the
+// earlier segment's is kept
+fn combine_displays(displays: &mut Vec<(u64, String)>, new_displays: Vec<(u64, String)>) {
+    displays.extend(new_displays);
+    displays.sort_by_key(|d| d.0);
+    displays.dedup_by_key(|d| d.0);
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+
+    use super::*;
+    use super::super::FuzzyPhraseSetBuilder;
+
+    fn build(path: &Path, phrases: &[(&str, u64)]) {
+        let mut builder = FuzzyPhraseSetBuilder::new(path).unwrap();
+        for (phrase, id) in phrases {
+            builder.insert_str(phrase, *id).unwrap();
+        }
+        builder.finish().unwrap();
+    }
+
+    #[test]
+    fn segmented_query_and_merge() -> () {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("base");
+        let delta = dir.path().join("delta");
+        let merged = dir.path().join("merged");
+        build(&base, &[("100 main street", 0), ("200 main street", 1), ("100 main ave", 2)]);
+        build(&delta, &[("300 mlk blvd", 3), ("100 main street", 4), ("100 maine street", 5)]);
+
+        let set = SegmentedFuzzyPhraseSet::from_paths(&[&base, &delta]).unwrap();
+        assert_eq!(set.len(), 2);
+        assert!(set.contains(&["300", "mlk", "blvd"]).unwrap());
+        assert!(set.contains_prefix(&["100", "main", "av"]).unwrap());
+        assert!(!set.contains(&["300", "main", "street"]).unwrap());
+
+        let expected_match = vec![
+            FuzzyMatchResult { phrase: vec!["100".to_string(), "main".to_string(), "street".to_string()], phrase_ids: vec![0, 4], displays: vec![], edit_distance: 0 },
+            FuzzyMatchResult { phrase: vec!["100".to_string(), "maine".to_string(), "street".to_string()], phrase_ids: vec![5], displays: vec![], edit_distance: 1 },
+        ];
+        assert_eq!(set.fuzzy_match(&["100", "main", "street"], 1, 1).unwrap(), expected_match);
+        assert_eq!(
+            set.fuzzy_match_prefix(&["300", "mlk", "b"], 1, 1).unwrap(),
+            vec![FuzzyMatchResult { phrase: vec!["300".to_string(), "mlk".to_string(), "b".to_string()], phrase_ids: vec![], displays: vec![], edit_distance: 0 }]
+        );
+        assert_eq!(
+            set.fuzzy_match_windows(&["at", "300", "mlk", "blvd"], 1, 1, false).unwrap(),
+            vec![FuzzyWindowResult { phrase: vec!["300".to_string(), "mlk".to_string(), "blvd".to_string()], phrase_ids: vec![3], displays: vec![], edit_distance: 0, start_position: 1, ends_in_prefix: false }]
+        );
+        assert_eq!(
+            set.fuzzy_match_multi(&[(vec!["100", "main", "street"], false), (vec!["300", "mlk", "blvd"], false)], 1, 1).unwrap(),
+            vec![
+                expected_match.clone(),
+                vec![FuzzyMatchResult { phrase: vec!["300".to_string(), "mlk".to_string(), "blvd".to_string()], phrase_ids: vec![3], displays: vec![], edit_distance: 0 }],
+            ]
+        );
+
+        set.merge(&merged).unwrap();
+        let merged_set = FuzzyPhraseSet::from_path(&merged).unwrap();
+        assert_eq!(merged_set.fuzzy_match(&["100", "main", "street"], 1, 1).unwrap(), expected_match);
+        assert!(merged_set.contains(&["200", "main", "street"]).unwrap());
+        assert!(merged_set.contains(&["300", "mlk", "blvd"]).unwrap());
+        assert_eq!(
+            merged_set.word_list,
+            vec!["100", "200", "300", "ave", "blvd", "main", "maine", "mlk", "street"]
+        );
+    }
+
+    #[test]
+    fn segmented_delete_and_merge() -> () {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("base");
+        let delta = dir.path().join("delta");
+        let merged = dir.path().join("merged");
+        build(&base, &[("100 main street", 0), ("200 main street", 1)]);
+        build(&delta, &[("300 mlk blvd", 3), ("100 main street", 4)]);
+
+        let mut set = SegmentedFuzzyPhraseSet::from_paths(&[&base, &delta]).unwrap();
+        assert!(set.delete(&["100", "main", "street"]).unwrap());
+        assert!(!set.delete(&["100", "main", "street"]).unwrap());
+        assert!(!set.contains(&["100", "main", "street"]).unwrap());
+        assert!(set.contains(&["200", "main", "street"]).unwrap());
+        set.load_query_replacements(vec![WordReplacement::new("martin", "mlk")]).unwrap();
+        assert!(set.contains(&["300", "martin", "blvd"]).unwrap());
+
+        set.merge(&merged).unwrap();
+        let merged_set = FuzzyPhraseSet::from_path(&merged).unwrap();
+        assert!(!merged_set.contains(&["100", "main", "street"]).unwrap());
+        assert_eq!(merged_set.word_list, vec!["200", "300", "blvd", "main", "mlk", "street"]);
+    }
+
+    #[test]
+    fn segmented_requires_matching_options() -> () {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("base");
+        let delta = dir.path().join("delta");
+        build(&base, &[("100 main street", 0)]);
+
+        let mut builder = FuzzyPhraseSetBuilder::new(&delta).unwrap();
+        builder.load_word_replacements(vec![super::super::WordReplacement::new("street", "st")]);
+        builder.insert_str("100 main st", 1).unwrap();
+        builder.finish().unwrap();
+
+        assert!(SegmentedFuzzyPhraseSet::from_paths(&[&base, &delta]).is_err());
+        assert!(SegmentedFuzzyPhraseSet::from_paths::<&Path>(&[]).is_err());
+    }
+}
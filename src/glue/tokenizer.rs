@@ -0,0 +1,84 @@
+use unicode_segmentation::UnicodeSegmentation;
+
+/// Splits text into the words a `FuzzyPhraseSet` is built from and queried with. The `_str`
+/// methods on `FuzzyPhraseSetBuilder` and `FuzzyPhraseSet` tokenize with the `TokenizerType`
+/// recorded in the set's options; callers with their own tokenization can implement this trait
+/// and pass the words to the non-`_str` methods instead.
+pub trait Tokenizer {
+    fn tokenize<'a>(&self, text: &'a str) -> Vec<&'a str>;
+}
+
+/// Splits on Unicode word boundaries (UAX #29), dropping the whitespace and punctuation between
+/// words. Punctuation within a word, like the apostrophe in "o'brien" or the period in "3.5", is
+/// kept.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct UnicodeWordTokenizer;
+
+impl Tokenizer for UnicodeWordTokenizer {
+    fn tokenize<'a>(&self, text: &'a str) -> Vec<&'a str> {
+        text.unicode_words().collect()
+    }
+}
+
+/// Splits on the space character and nothing else, so punctuation stays attached to words and
+/// consecutive spaces produce empty words. This is how the `_str` methods behaved before the
+/// tokenizer was configurable.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SpaceTokenizer;
+
+impl Tokenizer for SpaceTokenizer {
+    fn tokenize<'a>(&self, text: &'a str) -> Vec<&'a str> {
+        text.split(' ').collect()
+    }
+}
+
+/// Which of the built-in tokenizers a set uses. It's recorded in the set's metadata at build time
+/// so queries are always tokenized the same way as the phrases were.
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TokenizerType {
+    UnicodeWords,
+    Space,
+}
+
+impl Default for TokenizerType {
+    fn default() -> TokenizerType {
+        TokenizerType::UnicodeWords
+    }
+}
+
+impl Tokenizer for TokenizerType {
+    fn tokenize<'a>(&self, text: &'a str) -> Vec<&'a str> {
+        match self {
+            TokenizerType::UnicodeWords => UnicodeWordTokenizer.tokenize(text),
+            TokenizerType::Space => SpaceTokenizer.tokenize(text),
+        }
+    }
+}
+
+// sets whose metadata predates the tokenizer setting (all format version 1 sets, and version 2
+// ones built before it) were built and queried by splitting on spaces, so they keep doing that
+pub(super) fn legacy_tokenizer() -> TokenizerType {
+    TokenizerType::Space
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn unicode_word_tokenizer() -> () {
+        let tokenizer = UnicodeWordTokenizer;
+        assert_eq!(tokenizer.tokenize("100  main\tstreet"), vec!["100", "main", "street"]);
+        assert_eq!(tokenizer.tokenize(" o'brien st., apt 3.5 "), vec!["o'brien", "st", "apt", "3.5"]);
+        assert_eq!(tokenizer.tokenize("rue\u{00A0}de la paix"), vec!["rue", "de", "la", "paix"]);
+        assert_eq!(tokenizer.tokenize("улица ленина"), vec!["улица", "ленина"]);
+        assert_eq!(tokenizer.tokenize(" - "), Vec::<&str>::new());
+    }
+
+    #[test]
+    fn space_tokenizer() -> () {
+        assert_eq!(SpaceTokenizer.tokenize("100  main st."), vec!["100", "", "main", "st."]);
+        assert_eq!(TokenizerType::Space.tokenize("a b"), SpaceTokenizer.tokenize("a b"));
+        assert_eq!(TokenizerType::default().tokenize("a\tb"), vec!["a", "b"]);
+    }
+}
@@ -1,4 +1,7 @@
+use std::error::Error;
+
 use regex;
+use super::unicode_ranges;
 
 #[inline(always)]
 pub fn contains_digit_or_pound(word: &str) -> bool {
@@ -21,6 +24,18 @@ pub fn can_fuzzy_match(word: &str, script_regex: &regex::Regex) -> bool {
     }
 }
 
+// build a regex that matches words made up entirely of characters from any of the named scripts,
+// to decide whether to index or query something for fuzzy matching
+pub fn get_script_regex<T: AsRef<str>>(script_names: &[T]) -> Result<regex::Regex, Box<Error>> {
+    if script_names.len() == 0 {
+        return Err("at least one fuzzy-enabled script is required".into());
+    }
+    let allowed_scripts = script_names.iter().map(
+        |s| unicode_ranges::get_script_by_name(s.as_ref())
+    ).collect::<Option<Vec<_>>>().ok_or("unknown script")?;
+    Ok(regex::Regex::new(&unicode_ranges::get_pattern_for_scripts(&allowed_scripts))?)
+}
+
 #[test]
 fn digit_test() {
     assert!(contains_digit_or_pound("1"));
@@ -32,4 +47,15 @@ fn digit_test() {
     assert!(!contains_digit_or_pound(""));
     assert!(!contains_digit_or_pound("!"));
     assert!(!contains_digit_or_pound("hello"));
-}
\ No newline at end of file
+}
+#[test]
+fn script_regex_test() {
+    let latin_cyrillic = get_script_regex(&["Latin", "Cyrillic"]).unwrap();
+    assert!(can_fuzzy_match("main", &latin_cyrillic));
+    assert!(can_fuzzy_match("Москва", &latin_cyrillic));
+    assert!(!can_fuzzy_match("καθέδρα", &latin_cyrillic));
+    assert!(!can_fuzzy_match("main1", &latin_cyrillic));
+
+    assert!(get_script_regex(&["Klingon"]).is_err());
+    assert!(get_script_regex::<&str>(&[]).is_err());
+}
@@ -0,0 +1,306 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use std::error::Error;
+use std::fs;
+
+use serde_json;
+use serde::Deserialize;
+use rmps::Deserializer;
+use crc::crc32;
+use fst::{IntoStreamer, Streamer};
+
+use ::prefix::PrefixSet;
+use ::phrase::PhraseSet;
+use ::phrase::util::key_to_word_ids;
+use ::fuzzy::{FuzzyMap, AliasMap};
+use ::fuzzy::map::FuzzyMapDanglingReference;
+use super::{FuzzyPhraseSetMetadata, SerializablePhraseIdList, SerializableTombstones, SerializableDisplayStrings, MULTI_FLAG, MULTI_MASK};
+use super::packed::{read_sections, REQUIRED_SECTIONS, OPTIONAL_SECTIONS};
+
+/// One thing wrong with an index, as found by `verify`. Sections are named by their file names in
+/// the directory form of the index.
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub enum IntegrityProblem {
+    /// A file every index needs, or that this index's options call for, isn't there.
+    MissingSection(String),
+    /// A packed section's contents don't match the checksum recorded for it.
+    ChecksumMismatch(String),
+    /// A section couldn't be parsed, or isn't the kind or version of index we expect. The section
+    /// is empty for problems with a packed file's header.
+    Malformed { section: String, message: String },
+    /// A phrase graph key isn't a whole number of words.
+    MalformedPhraseKey(Vec<u8>),
+    /// A phrase (or deleted phrase, or display string) uses a word ID past the end of the lexicon.
+    PhraseWordIdOutOfRange(u32),
+    /// A phrase's value points past the end of the list of phrases with more than one ID.
+    PhraseIdListIndexOutOfRange(u64),
+    /// A fuzzy graph variant or ID list uses a word ID past the end of the lexicon.
+    FuzzyWordIdOutOfRange(u32),
+    /// A fuzzy graph variant points past the end of the fuzzy ID lists.
+    FuzzyIdListIndexOutOfRange(u64),
+    /// A phonetic graph entry uses a word ID past the end of the lexicon.
+    PhoneticWordIdOutOfRange(u32),
+    /// A transliteration graph entry uses a word ID past the end of the lexicon.
+    TransliterationWordIdOutOfRange(u32),
+    /// A word or phrase replacement names a word that isn't in the lexicon.
+    UnresolvedReplacement(String),
+}
+
+#[derive(Debug, PartialEq, Eq, Clone, Serialize, Deserialize)]
+pub struct VerificationReport {
+    /// Whether there were checksums to check; packed files have them, directories don't.
+    pub checksums_verified: bool,
+    pub word_count: usize,
+    pub phrase_count: usize,
+    pub problems: Vec<IntegrityProblem>,
+}
+
+impl VerificationReport {
+    pub fn is_ok(&self) -> bool {
+        self.problems.is_empty()
+    }
+}
+
+/// Check the index at `path`, which can be either a directory or a packed file, for damage or
+/// inconsistencies between its parts that loading it wouldn't catch. This reads everything, so
+/// it's much slower than loading. It only returns an error if `path` can't be read at all;
+/// anything wrong with the index itself goes in the report.
+pub fn verify<P: AsRef<Path>>(path: P) -> Result<VerificationReport, Box<Error>> {
+    let path = path.as_ref();
+    let mut report = VerificationReport { checksums_verified: false, word_count: 0, phrase_count: 0, problems: Vec::new() };
+    let mut sections: BTreeMap<String, Vec<u8>> = BTreeMap::new();
+
+    if path.is_dir() {
+        for name in REQUIRED_SECTIONS.iter().chain(OPTIONAL_SECTIONS.iter()) {
+            let section_path = path.join(Path::new(name));
+            if section_path.exists() {
+                sections.insert(name.to_string(), fs::read(section_path)?);
+            }
+        }
+    } else {
+        let bytes = fs::read(path)?;
+        let packed_sections = match read_sections(&bytes) {
+            Ok(packed_sections) => packed_sections,
+            Err(e) => {
+                report.problems.push(IntegrityProblem::Malformed { section: String::new(), message: e.to_string() });
+                return Ok(report);
+            },
+        };
+        report.checksums_verified = true;
+        for section in packed_sections {
+            let contents = section.contents(&bytes);
+            if crc32::checksum_ieee(contents) != section.crc {
+                report.problems.push(IntegrityProblem::ChecksumMismatch(section.name.clone()));
+            }
+            sections.insert(section.name, contents.to_vec());
+        }
+    }
+
+    verify_sections(&sections, &mut report);
+    Ok(report)
+}
+
+fn verify_sections(sections: &BTreeMap<String, Vec<u8>>, report: &mut VerificationReport) {
+    // sets from before phrases had IDs don't have a phrase ID list
+    let has_phrase_id_list = sections.get("metadata.json")
+        .and_then(|bytes| serde_json::from_slice::<FuzzyPhraseSetMetadata>(bytes).ok())
+        .map(|metadata| metadata.has_phrase_id_list())
+        .unwrap_or(true);
+    for name in REQUIRED_SECTIONS.iter() {
+        if *name == "phrase.msg" && !has_phrase_id_list {
+            continue;
+        }
+        if !sections.contains_key(*name) {
+            report.problems.push(IntegrityProblem::MissingSection(name.to_string()));
+        }
+    }
+
+    let metadata: Option<FuzzyPhraseSetMetadata> = parse_section(sections, "metadata.json", report, |bytes| {
+        Ok(serde_json::from_slice(bytes)?)
+    });
+    let prefix_set = parse_section(sections, "prefix.fst", report, |bytes| Ok(PrefixSet::from_bytes(bytes.to_vec())?));
+    let phrase_set = parse_section(sections, "phrase.fst", report, |bytes| Ok(PhraseSet::from_bytes(bytes.to_vec())?));
+    let phrase_id_list: Option<SerializablePhraseIdList> = match sections.get("phrase.msg") {
+        None if !has_phrase_id_list => Some(SerializablePhraseIdList(Vec::new())),
+        _ => parse_section(sections, "phrase.msg", report, |bytes| Ok(Deserialize::deserialize(&mut Deserializer::new(bytes))?)),
+    };
+    let fuzzy_map = match sections.get("fuzzy.msg") {
+        Some(id_list_bytes) => parse_section(sections, "fuzzy.fst", report, |bytes| {
+            Ok(FuzzyMap::from_bytes(bytes.to_vec(), id_list_bytes)?)
+        }),
+        None => None,
+    };
+    let tombstones: Option<SerializableTombstones> = parse_section(sections, "deleted.msg", report, |bytes| {
+        Ok(Deserialize::deserialize(&mut Deserializer::new(bytes))?)
+    });
+    let phonetic_map = parse_section(sections, "phonetic.fst", report, |bytes| Ok(AliasMap::from_bytes(bytes.to_vec())?));
+    let transliteration_map = parse_section(sections, "translit.fst", report, |bytes| Ok(AliasMap::from_bytes(bytes.to_vec())?));
+    let displays: Option<SerializableDisplayStrings> = parse_section(sections, "display.msg", report, |bytes| {
+        Ok(Deserialize::deserialize(&mut Deserializer::new(bytes))?)
+    });
+
+    if let Some(ref metadata) = metadata {
+        if !metadata.is_supported() {
+            report.problems.push(IntegrityProblem::Malformed {
+                section: "metadata.json".to_owned(),
+                message: "Unexpected structure metadata".to_owned(),
+            });
+        }
+        if metadata.phonetic_edit_distance.is_some() && !sections.contains_key("phonetic.fst") {
+            report.problems.push(IntegrityProblem::MissingSection("phonetic.fst".to_owned()));
+        }
+        if metadata.transliteration_edit_distance.is_some() && !sections.contains_key("translit.fst") {
+            report.problems.push(IntegrityProblem::MissingSection("translit.fst".to_owned()));
+        }
+    }
+
+    // everything else is about whether references into the lexicon are valid
+    let prefix_set = match prefix_set {
+        Some(prefix_set) => prefix_set,
+        None => return,
+    };
+    let word_count = prefix_set.len();
+    report.word_count = word_count;
+
+    if let Some(ref metadata) = metadata {
+        for word_replacement in &metadata.word_replacements {
+            for word in &[&word_replacement.from, &word_replacement.to] {
+                if prefix_set.lookup(word).id().is_none() {
+                    report.problems.push(IntegrityProblem::UnresolvedReplacement(word.to_string()));
+                }
+            }
+        }
+        for phrase_replacement in &metadata.phrase_replacements {
+            for word in phrase_replacement.from.iter().chain(phrase_replacement.to.iter()) {
+                if prefix_set.lookup(word).id().is_none() {
+                    report.problems.push(IntegrityProblem::UnresolvedReplacement(word.to_string()));
+                }
+            }
+        }
+    }
+
+    let mut bad_word_ids: BTreeSet<u32> = BTreeSet::new();
+    if let Some(ref phrase_set) = phrase_set {
+        let mut bad_list_indexes: BTreeSet<u64> = BTreeSet::new();
+        let mut stream = phrase_set.into_stream();
+        while let Some((key, value)) = stream.next() {
+            report.phrase_count += 1;
+            if key.len() == 0 || key.len() % 3 != 0 {
+                report.problems.push(IntegrityProblem::MalformedPhraseKey(key.to_vec()));
+                continue;
+            }
+            bad_word_ids.extend(key_to_word_ids(key).into_iter().filter(|id| *id as usize >= word_count));
+            if let Some(ref phrase_id_list) = phrase_id_list {
+                if value & MULTI_FLAG != 0 && (value & MULTI_MASK) as usize >= phrase_id_list.0.len() {
+                    bad_list_indexes.insert(value & MULTI_MASK);
+                }
+            }
+        }
+        report.problems.extend(bad_list_indexes.into_iter().map(IntegrityProblem::PhraseIdListIndexOutOfRange));
+    }
+    if let Some(tombstones) = tombstones {
+        bad_word_ids.extend(tombstones.0.iter().flat_map(|phrase| phrase.iter()).filter(|id| **id as usize >= word_count));
+    }
+    if let Some(displays) = displays {
+        bad_word_ids.extend(displays.0.iter().flat_map(|(phrase, _, _)| phrase.iter()).filter(|id| **id as usize >= word_count));
+    }
+    report.problems.extend(bad_word_ids.into_iter().map(IntegrityProblem::PhraseWordIdOutOfRange));
+
+    if let Some(fuzzy_map) = fuzzy_map {
+        report.problems.extend(fuzzy_map.dangling_references(word_count).into_iter().map(|reference| match reference {
+            FuzzyMapDanglingReference::WordId(id) => IntegrityProblem::FuzzyWordIdOutOfRange(id),
+            FuzzyMapDanglingReference::IdListIndex(index) => IntegrityProblem::FuzzyIdListIndexOutOfRange(index),
+        }));
+    }
+    if let Some(phonetic_map) = phonetic_map {
+        report.problems.extend(phonetic_map.dangling_references(word_count).into_iter().map(IntegrityProblem::PhoneticWordIdOutOfRange));
+    }
+    if let Some(transliteration_map) = transliteration_map {
+        report.problems.extend(transliteration_map.dangling_references(word_count).into_iter().map(IntegrityProblem::TransliterationWordIdOutOfRange));
+    }
+}
+
+// parse a section if it's there, and note it in the report if it can't be parsed
+fn parse_section<T, F>(sections: &BTreeMap<String, Vec<u8>>, name: &str, report: &mut VerificationReport, parser: F) -> Option<T>
+    where F: FnOnce(&[u8]) -> Result<T, Box<Error>> {
+    let bytes = sections.get(name)?;
+    match parser(bytes) {
+        Ok(parsed) => Some(parsed),
+        Err(e) => {
+            report.problems.push(IntegrityProblem::Malformed { section: name.to_owned(), message: e.to_string() });
+            None
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    extern crate tempfile;
+
+    use super::*;
+    use super::super::{FuzzyPhraseSetBuilder, WordReplacement, pack};
+
+    fn build(path: &Path, phrases: &[&str], word_replacements: Vec<WordReplacement>) {
+        let mut builder = FuzzyPhraseSetBuilder::new(path).unwrap();
+        builder.load_word_replacements(word_replacements);
+        for (i, phrase) in phrases.iter().enumerate() {
+            builder.insert_str(phrase, i as u64).unwrap();
+        }
+        builder.finish().unwrap();
+    }
+
+    #[test]
+    fn verify_intact() -> () {
+        let dir = tempfile::tempdir().unwrap();
+        let index = dir.path().join("index");
+        let packed = dir.path().join("index.fzp");
+        build(&index, &["100 main street", "200 main street", "100 main ave"], vec![]);
+
+        let report = verify(&index).unwrap();
+        assert!(report.is_ok());
+        assert!(!report.checksums_verified);
+        assert_eq!((report.word_count, report.phrase_count), (5, 3));
+
+        pack(&index, &packed).unwrap();
+        let report = verify(&packed).unwrap();
+        assert!(report.is_ok());
+        assert!(report.checksums_verified);
+        assert_eq!((report.word_count, report.phrase_count), (5, 3));
+    }
+
+    #[test]
+    fn verify_damaged() -> () {
+        let dir = tempfile::tempdir().unwrap();
+        let index = dir.path().join("index");
+        let other = dir.path().join("other");
+        let packed = dir.path().join("index.fzp");
+        build(&index, &["100 main street", "200 main street"], vec![WordReplacement::new("street", "st")]);
+        build(&other, &["a b"], vec![]);
+
+        let mut bytes = {
+            pack(&index, &packed).unwrap();
+            fs::read(&packed).unwrap()
+        };
+        let last = bytes.len() - 1;
+        bytes[last] ^= 1;
+        fs::write(&packed, &bytes).unwrap();
+        assert!(verify(&packed).unwrap().problems.contains(&IntegrityProblem::ChecksumMismatch("fuzzy.msg".to_string())));
+        fs::write(&packed, b"not an index").unwrap();
+        assert!(!verify(&packed).unwrap().is_ok());
+
+        // swap in a lexicon that's too small for the other graphs
+        fs::copy(other.join("prefix.fst"), index.join("prefix.fst")).unwrap();
+        fs::remove_file(index.join("phrase.msg")).unwrap();
+        let report = verify(&index).unwrap();
+        assert_eq!(report.word_count, 2);
+        for problem in &[
+            IntegrityProblem::MissingSection("phrase.msg".to_string()),
+            IntegrityProblem::UnresolvedReplacement("street".to_string()),
+            IntegrityProblem::UnresolvedReplacement("st".to_string()),
+            IntegrityProblem::PhraseWordIdOutOfRange(3),
+            IntegrityProblem::FuzzyWordIdOutOfRange(4),
+        ] {
+            assert!(report.problems.contains(problem), "{:?} not in {:?}", problem, report.problems);
+        }
+    }
+}
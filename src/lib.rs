@@ -1,9 +1,14 @@
 extern crate fst;
 extern crate itertools;
+#[cfg(feature = "mmap")]
 extern crate memmap;
 extern crate byteorder;
 extern crate regex;
 extern crate rustc_hash;
+extern crate crc;
+extern crate unicode_segmentation;
+extern crate unicode_normalization;
+extern crate caseless;
 
 extern crate serde;
 #[macro_use]
@@ -21,6 +26,10 @@ pub use prefix::PrefixSetBuilder;
 pub mod fuzzy;
 pub use fuzzy::FuzzyMap;
 pub use fuzzy::FuzzyMapBuilder;
+pub use fuzzy::AliasMap;
+pub use fuzzy::AliasMapBuilder;
+pub use fuzzy::PhoneticMap;
+pub use fuzzy::PhoneticMapBuilder;
 
 pub mod phrase;
 
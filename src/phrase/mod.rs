@@ -6,7 +6,7 @@ use std::io;
 use std::path::Path;
 
 use fst;
-use fst::{IntoStreamer, Set, SetBuilder, Streamer};
+use fst::{IntoStreamer, Map, MapBuilder, Streamer};
 use fst::raw::{CompiledAddr, Node};
 
 use self::util::{word_ids_to_key};
@@ -17,7 +17,45 @@ use self::query::{QueryPhrase, QueryWord};
 
 type WordKey = [u8; 3];
 
-pub struct PhraseSet(Set);
+pub struct PhraseSet(Map);
+
+/// A test for whether a complete phrase, given as its word IDs, should be returned by a search.
+pub type PhraseFilter<'a> = &'a Fn(&[u32]) -> bool;
+
+// Constraints on a phrase graph search. The limited searches run once per edit distance, from
+// zero up, keeping only the results that use up exactly that search's budget, so that they find
+// the cheapest results first and can stop as soon as they have enough. Results the filter turns
+// down don't count toward the limit.
+#[derive(Copy, Clone)]
+struct SearchBounds<'a> {
+    limit: usize,
+    exact_cost: bool,
+    filter: Option<PhraseFilter<'a>>,
+}
+
+impl<'a> SearchBounds<'a> {
+    fn unlimited() -> SearchBounds<'a> {
+        SearchBounds { limit: usize::max_value(), exact_cost: false, filter: None }
+    }
+
+    fn exact(limit: usize, filter: Option<PhraseFilter<'a>>) -> SearchBounds<'a> {
+        SearchBounds { limit, exact_cost: true, filter }
+    }
+
+    fn accepts(&self, budget_remaining: u8) -> bool {
+        !self.exact_cost || budget_remaining == 0
+    }
+}
+
+/// How to order the words that can follow a phrase.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ContinuationOrder {
+    /// In order of word ID, which is alphabetical order.
+    Lexicographic,
+    /// Words that begin the most complete phrases first. This has to visit everything below each
+    /// candidate, so it's slower for short phrases in big sets.
+    MostPhrases,
+}
 
 /// PhraseSet is a lexicographically ordered set of phrases.
 ///
@@ -32,6 +70,8 @@ pub struct PhraseSet(Set);
 /// | main    | 457       | [0,   1, 201] |
 /// | street  | 109821    | [1, 172, 253] |
 ///
+/// Each phrase also carries a single u64 output, supplied at insertion time, which callers can use
+/// to associate an application-level identifier with the phrase.
 impl PhraseSet {
 
     /// Test membership of a single phrase. Returns true iff the phrase matches a complete phrase
@@ -41,7 +81,17 @@ impl PhraseSet {
             return Err(PhraseSetError::new("The query submitted has a QueryWord::Prefix. Set::contains only accepts QueryWord:Full"));
         }
         let key = phrase.full_word_key();
-        Ok(self.0.contains(key))
+        Ok(self.0.contains_key(key))
+    }
+
+    /// Look up the output associated with a single phrase. Returns None if the phrase isn't a
+    /// complete phrase in the set. Wraps the underlying Map::get method.
+    pub fn get(&self, phrase: QueryPhrase) -> Result<Option<u64>, PhraseSetError> {
+        if phrase.has_prefix {
+            return Err(PhraseSetError::new("The query submitted has a QueryWord::Prefix. PhraseSet::get only accepts QueryWord:Full"));
+        }
+        let key = phrase.full_word_key();
+        Ok(self.0.get(key))
     }
 
     /// Test whether a query phrase can be found at the beginning of any phrase in the Set. Also
@@ -74,7 +124,45 @@ impl PhraseSet {
         let fst = self.0.as_fst();
         let root = fst.root();
         let mut out: Vec<Vec<QueryWord>> = Vec::new();
-        self.exact_recurse(word_possibilities, 0, &root, max_phrase_dist, Vec::new(), &mut out)?;
+        self.exact_recurse(word_possibilities, 0, &root, max_phrase_dist, SearchBounds::unlimited(), Vec::new(), &mut out)?;
+        Ok(out)
+    }
+
+    /// Like `match_combinations`, but stops once `limit` results have been found. Results are
+    /// found in order of increasing edit distance, so these will be the `limit` best ones.
+    pub fn match_combinations_limited(
+        &self,
+        word_possibilities: &[Vec<QueryWord>],
+        max_phrase_dist: u8,
+        limit: usize
+    ) -> Result<Vec<Vec<QueryWord>>, PhraseSetError> {
+        self.exact_limited(word_possibilities, max_phrase_dist, SearchBounds::exact(limit, None))
+    }
+
+    /// Like `match_combinations_limited`, but skips phrases that `filter` turns down, so the
+    /// search keeps going until it has `limit` phrases that pass.
+    pub fn match_combinations_limited_filtered(
+        &self,
+        word_possibilities: &[Vec<QueryWord>],
+        max_phrase_dist: u8,
+        limit: usize,
+        filter: PhraseFilter
+    ) -> Result<Vec<Vec<QueryWord>>, PhraseSetError> {
+        self.exact_limited(word_possibilities, max_phrase_dist, SearchBounds::exact(limit, Some(filter)))
+    }
+
+    fn exact_limited(
+        &self,
+        word_possibilities: &[Vec<QueryWord>],
+        max_phrase_dist: u8,
+        bounds: SearchBounds
+    ) -> Result<Vec<Vec<QueryWord>>, PhraseSetError> {
+        let fst = self.0.as_fst();
+        let root = fst.root();
+        let mut out: Vec<Vec<QueryWord>> = Vec::new();
+        for budget in 0..(max_phrase_dist + 1) {
+            self.exact_recurse(word_possibilities, 0, &root, budget, bounds, Vec::new(), &mut out)?;
+        }
         Ok(out)
     }
 
@@ -84,12 +172,16 @@ impl PhraseSet {
         position: usize,
         node: &Node,
         budget_remaining: u8,
+        bounds: SearchBounds,
         so_far: Vec<QueryWord>,
         out: &mut Vec<Vec<QueryWord>>,
     ) -> Result<(), PhraseSetError> {
         let fst = self.0.as_fst();
 
         for word in possibilities[position].iter() {
+            if out.len() >= bounds.limit {
+                break
+            }
             let (key, edit_distance) = match word {
                 QueryWord::Full { key, edit_distance, .. } => (*key, *edit_distance),
                 _ => return Err(PhraseSetError::new(
@@ -123,12 +215,14 @@ impl PhraseSet {
                         position + 1,
                         &search_node,
                         budget_remaining - edit_distance,
+                        bounds,
                         rec_so_far,
                         out,
                     )?;
                 } else {
                     // if we're at the end of the line, we'll only keep this result if it's final
-                    if search_node.is_final() {
+                    if search_node.is_final() && bounds.accepts(budget_remaining - edit_distance) &&
+                        self.passes_filter(bounds, &rec_so_far, &search_node, true) {
                         out.push(rec_so_far);
                     }
                 }
@@ -149,7 +243,45 @@ impl PhraseSet {
         let fst = self.0.as_fst();
         let root = fst.root();
         let mut out: Vec<Vec<QueryWord>> = Vec::new();
-        self.prefix_recurse(word_possibilities, 0, &root, max_phrase_dist, Vec::new(), &mut out)?;
+        self.prefix_recurse(word_possibilities, 0, &root, max_phrase_dist, SearchBounds::unlimited(), Vec::new(), &mut out)?;
+        Ok(out)
+    }
+
+    /// Like `match_combinations_as_prefixes`, but stops once the `limit` lowest-distance results
+    /// have been found.
+    pub fn match_combinations_as_prefixes_limited(
+        &self,
+        word_possibilities: &[Vec<QueryWord>],
+        max_phrase_dist: u8,
+        limit: usize
+    ) -> Result<Vec<Vec<QueryWord>>, PhraseSetError> {
+        self.prefix_limited(word_possibilities, max_phrase_dist, SearchBounds::exact(limit, None))
+    }
+
+    /// Like `match_combinations_as_prefixes_limited`, but only keeps prefixes that begin at least
+    /// one complete phrase that `filter` accepts.
+    pub fn match_combinations_as_prefixes_limited_filtered(
+        &self,
+        word_possibilities: &[Vec<QueryWord>],
+        max_phrase_dist: u8,
+        limit: usize,
+        filter: PhraseFilter
+    ) -> Result<Vec<Vec<QueryWord>>, PhraseSetError> {
+        self.prefix_limited(word_possibilities, max_phrase_dist, SearchBounds::exact(limit, Some(filter)))
+    }
+
+    fn prefix_limited(
+        &self,
+        word_possibilities: &[Vec<QueryWord>],
+        max_phrase_dist: u8,
+        bounds: SearchBounds
+    ) -> Result<Vec<Vec<QueryWord>>, PhraseSetError> {
+        let fst = self.0.as_fst();
+        let root = fst.root();
+        let mut out: Vec<Vec<QueryWord>> = Vec::new();
+        for budget in 0..(max_phrase_dist + 1) {
+            self.prefix_recurse(word_possibilities, 0, &root, budget, bounds, Vec::new(), &mut out)?;
+        }
         Ok(out)
     }
 
@@ -159,12 +291,16 @@ impl PhraseSet {
         position: usize,
         node: &Node,
         budget_remaining: u8,
+        bounds: SearchBounds,
         so_far: Vec<QueryWord>,
         out: &mut Vec<Vec<QueryWord>>,
     ) -> Result<(), PhraseSetError> {
         let fst = self.0.as_fst();
 
         for word in possibilities[position].iter() {
+            if out.len() >= bounds.limit {
+                break
+            }
             match word {
                 QueryWord::Full { key, edit_distance, .. } => {
                     if *edit_distance > budget_remaining {
@@ -194,24 +330,34 @@ impl PhraseSet {
                                 position + 1,
                                 &search_node,
                                 budget_remaining - edit_distance,
+                                bounds,
                                 rec_so_far,
                                 out,
                             )?;
-                        } else {
+                        } else if bounds.accepts(budget_remaining - edit_distance) &&
+                            self.passes_filter(bounds, &rec_so_far, &search_node, false) {
                             out.push(rec_so_far);
                         }
                     }
                 },
-                QueryWord::Prefix { key_range, .. } => {
+                QueryWord::Prefix { key_range, edit_distance, .. } => {
+                    if *edit_distance > budget_remaining {
+                        break
+                    }
+
                     if self.matches_prefix_range(
                         node.addr(),
                         *key_range
                     ) {
                         // presumably the prefix is at the end, so we don't need to consider the
                         // possibility of recursing, just of being done
-                        let mut rec_so_far = so_far.clone();
-                        rec_so_far.push(word.clone());
-                        out.push(rec_so_far);
+                        if bounds.accepts(budget_remaining - edit_distance) {
+                            let mut rec_so_far = so_far.clone();
+                            rec_so_far.push(word.clone());
+                            if self.passes_filter(bounds, &rec_so_far, node, false) {
+                                out.push(rec_so_far);
+                            }
+                        }
                     }
                 },
             }
@@ -232,7 +378,48 @@ impl PhraseSet {
         let fst = self.0.as_fst();
         let root = fst.root();
         let mut out: Vec<(Vec<QueryWord>, bool)> = Vec::new();
-        self.window_recurse(word_possibilities, 0, &root, max_phrase_dist, ends_in_prefix, Vec::new(), &mut out)?;
+        self.window_recurse(word_possibilities, 0, &root, max_phrase_dist, SearchBounds::unlimited(), ends_in_prefix, Vec::new(), &mut out)?;
+        Ok(out)
+    }
+
+    /// Like `match_combinations_as_windows`, but stops once the `limit` lowest-distance results
+    /// have been found.
+    pub fn match_combinations_as_windows_limited(
+        &self,
+        word_possibilities: &[Vec<QueryWord>],
+        max_phrase_dist: u8,
+        ends_in_prefix: bool,
+        limit: usize
+    ) -> Result<Vec<(Vec<QueryWord>, bool)>, PhraseSetError> {
+        self.window_limited(word_possibilities, max_phrase_dist, ends_in_prefix, SearchBounds::exact(limit, None))
+    }
+
+    /// Like `match_combinations_as_windows_limited`, but only keeps windows that are, or (when
+    /// they end in a prefix) begin, a complete phrase that `filter` accepts.
+    pub fn match_combinations_as_windows_limited_filtered(
+        &self,
+        word_possibilities: &[Vec<QueryWord>],
+        max_phrase_dist: u8,
+        ends_in_prefix: bool,
+        limit: usize,
+        filter: PhraseFilter
+    ) -> Result<Vec<(Vec<QueryWord>, bool)>, PhraseSetError> {
+        self.window_limited(word_possibilities, max_phrase_dist, ends_in_prefix, SearchBounds::exact(limit, Some(filter)))
+    }
+
+    fn window_limited(
+        &self,
+        word_possibilities: &[Vec<QueryWord>],
+        max_phrase_dist: u8,
+        ends_in_prefix: bool,
+        bounds: SearchBounds
+    ) -> Result<Vec<(Vec<QueryWord>, bool)>, PhraseSetError> {
+        let fst = self.0.as_fst();
+        let root = fst.root();
+        let mut out: Vec<(Vec<QueryWord>, bool)> = Vec::new();
+        for budget in 0..(max_phrase_dist + 1) {
+            self.window_recurse(word_possibilities, 0, &root, budget, bounds, ends_in_prefix, Vec::new(), &mut out)?;
+        }
         Ok(out)
     }
 
@@ -242,6 +429,7 @@ impl PhraseSet {
         position: usize,
         node: &Node,
         budget_remaining: u8,
+        bounds: SearchBounds,
         ends_in_prefix: bool,
         so_far: Vec<QueryWord>,
         out: &mut Vec<(Vec<QueryWord>, bool)>,
@@ -249,6 +437,9 @@ impl PhraseSet {
         let fst = self.0.as_fst();
 
         for word in possibilities[position].iter() {
+            if out.len() >= bounds.limit {
+                break
+            }
             match word {
                 QueryWord::Full { key, edit_distance, .. } => {
                     if *edit_distance > budget_remaining {
@@ -275,7 +466,8 @@ impl PhraseSet {
                         let mut rec_so_far = so_far.clone();
                         rec_so_far.push(word.clone());
                         if position < possibilities.len() - 1 {
-                            if search_node.is_final() {
+                            if search_node.is_final() && bounds.accepts(budget_remaining - edit_distance) &&
+                                self.passes_filter(bounds, &rec_so_far, &search_node, true) {
                                 out.push((rec_so_far.clone(), false));
                             }
                             self.window_recurse(
@@ -283,33 +475,42 @@ impl PhraseSet {
                                 position + 1,
                                 &search_node,
                                 budget_remaining - edit_distance,
+                                bounds,
                                 ends_in_prefix,
                                 rec_so_far,
                                 out,
                             )?;
                         } else {
                             // if we're at the end, require final node unless autocomplete is on
-                            if search_node.is_final() || ends_in_prefix {
+                            if (search_node.is_final() || ends_in_prefix) && bounds.accepts(budget_remaining - edit_distance) &&
+                                self.passes_filter(bounds, &rec_so_far, &search_node, !ends_in_prefix) {
                                 out.push((rec_so_far, ends_in_prefix));
                             }
                         }
                     }
                 },
-                QueryWord::Prefix { key_range, .. } => {
+                QueryWord::Prefix { key_range, edit_distance, .. } => {
                     if !ends_in_prefix {
                         return Err(PhraseSetError::new(
                             "The query submitted has a QueryWord::Prefix. This function only accepts QueryWord:Full"
                         ))
                     }
+                    if *edit_distance > budget_remaining {
+                        break
+                    }
                     if self.matches_prefix_range(
                         node.addr(),
                         *key_range
                     ) {
                         // presumably the prefix is at the end, so we don't need to consider the
                         // possibility of recursing, just of being done; we can also assume AC is on
-                        let mut rec_so_far = so_far.clone();
-                        rec_so_far.push(word.clone());
-                        out.push((rec_so_far, ends_in_prefix));
+                        if bounds.accepts(budget_remaining - edit_distance) {
+                            let mut rec_so_far = so_far.clone();
+                            rec_so_far.push(word.clone());
+                            if self.passes_filter(bounds, &rec_so_far, node, false) {
+                                out.push((rec_so_far, ends_in_prefix));
+                            }
+                        }
                     }
                 },
             }
@@ -317,6 +518,202 @@ impl PhraseSet {
         Ok(())
     }
 
+    // Check a search result against the search's filter. A complete result has to pass itself;
+    // one that might be the beginning of a longer phrase needs at least one completion below
+    // `node` (where the walk for its full words ended) that passes, and we stop at the first.
+    fn passes_filter(&self, bounds: SearchBounds, phrase: &[QueryWord], node: &Node, complete: bool) -> bool {
+        let filter = match bounds.filter {
+            None => return true,
+            Some(filter) => filter,
+        };
+        let mut key: Vec<u8> = Vec::new();
+        let mut key_range: Option<(WordKey, WordKey)> = None;
+        for word in phrase {
+            match word {
+                QueryWord::Full { key: word_key, .. } => key.extend_from_slice(word_key),
+                QueryWord::Prefix { key_range: word_range, .. } => key_range = Some(*word_range),
+            }
+        }
+        if complete {
+            return filter(&util::key_to_word_ids(&key));
+        }
+        let base = key.len();
+        let mut out: Vec<(Vec<u32>, u64)> = Vec::new();
+        self.completion_recurse(node, fst::raw::Output::zero(), &mut key, base, key_range, 1, filter, &mut out);
+        !out.is_empty()
+    }
+
+    /// Find up to `limit` complete phrases that begin with the given phrase, in lexicographic
+    /// order, by walking the graph below the node the phrase leads to. If the phrase ends in a
+    /// prefix, the next word of each completion has to fall within the prefix's range. Each
+    /// completion comes back as its word IDs along with the value stored for it.
+    pub fn completions(&self, phrase: QueryPhrase, limit: usize) -> Result<Vec<(Vec<u32>, u64)>, PhraseSetError> {
+        self.completions_filtered(phrase, limit, &|_| true)
+    }
+
+    /// Like `completions`, but skips phrases that `filter` turns down, so the walk keeps going
+    /// until it has `limit` phrases that pass.
+    pub fn completions_filtered(&self, phrase: QueryPhrase, limit: usize, filter: PhraseFilter) -> Result<Vec<(Vec<u32>, u64)>, PhraseSetError> {
+        let mut key = phrase.full_word_key();
+        let (node, output) = match self.output_search(&key) {
+            None => return Ok(Vec::new()),
+            Some(found) => found,
+        };
+
+        let mut out: Vec<(Vec<u32>, u64)> = Vec::new();
+        let base = key.len();
+        self.completion_recurse(&node, output, &mut key, base, phrase.prefix_key_range(), limit, filter, &mut out);
+        Ok(out)
+    }
+
+    /// Find up to `limit` words that can come next after the given phrase, i.e., the words that
+    /// follow the node the phrase leads to in the graph. If the phrase ends in a prefix, the
+    /// candidates are instead the words within the prefix's range that can occupy that final
+    /// position. Each word comes back with the value stored for the phrase it would complete, if
+    /// adding it makes a complete phrase.
+    pub fn continuations(&self, phrase: QueryPhrase, limit: usize, order: ContinuationOrder) -> Result<Vec<(u32, Option<u64>)>, PhraseSetError> {
+        self.continuations_filtered(phrase, limit, order, &|_| true)
+    }
+
+    /// Like `continuations`, but only considers complete phrases that `filter` accepts: a word
+    /// only counts if at least one of them goes through it, and only comes back with a value if
+    /// the phrase it completes is one of them.
+    pub fn continuations_filtered(&self, phrase: QueryPhrase, limit: usize, order: ContinuationOrder, filter: PhraseFilter) -> Result<Vec<(u32, Option<u64>)>, PhraseSetError> {
+        let fst = self.0.as_fst();
+        let mut key = phrase.full_word_key();
+        let (node, output) = match self.output_search(&key) {
+            None => return Ok(Vec::new()),
+            Some(found) => found,
+        };
+        let base = key.len();
+        let (min_key, max_key) = phrase.prefix_key_range().unwrap_or(([0u8; 3], [255u8; 3]));
+
+        let mut candidates: Vec<(u32, Option<u64>, usize)> = Vec::new();
+        'outer: for t0 in node.transitions() {
+            if t0.inp < min_key[0] || t0.inp > max_key[0] {
+                continue;
+            }
+            for t1 in fst.node(t0.addr).transitions() {
+                let partial = [t0.inp, t1.inp];
+                if partial < [min_key[0], min_key[1]] || partial > [max_key[0], max_key[1]] {
+                    continue;
+                }
+                for t2 in fst.node(t1.addr).transitions() {
+                    let word_key = [t0.inp, t1.inp, t2.inp];
+                    if word_key < min_key || word_key > max_key {
+                        continue;
+                    }
+                    // in lexicographic order we can stop as soon as we have enough
+                    if order == ContinuationOrder::Lexicographic && candidates.len() >= limit {
+                        break 'outer;
+                    }
+                    let word_node = fst.node(t2.addr);
+                    key.truncate(base);
+                    key.extend_from_slice(&word_key);
+                    let mut first: Vec<(Vec<u32>, u64)> = Vec::new();
+                    self.completion_recurse(&word_node, fst::raw::Output::zero(), &mut key, base, None, 1, filter, &mut first);
+                    if first.is_empty() {
+                        continue;
+                    }
+                    let value = if word_node.is_final() && filter(&util::key_to_word_ids(&key)) {
+                        Some(output.cat(t0.out).cat(t1.out).cat(t2.out).cat(word_node.final_output()).value())
+                    } else {
+                        None
+                    };
+                    let phrase_count = match order {
+                        ContinuationOrder::Lexicographic => 0,
+                        ContinuationOrder::MostPhrases => self.count_phrases(&word_node, &mut key, filter),
+                    };
+                    candidates.push((util::three_byte_decode(&word_key), value, phrase_count));
+                }
+            }
+        }
+
+        if order == ContinuationOrder::MostPhrases {
+            // stable, so ties stay in lexicographic order
+            candidates.sort_by(|a, b| b.2.cmp(&a.2));
+        }
+        candidates.truncate(limit);
+        Ok(candidates.into_iter().map(|(word_id, value, _)| (word_id, value)).collect())
+    }
+
+    /// Count the complete phrases at or below a node that pass the filter, given the key that
+    /// leads to the node.
+    fn count_phrases(&self, node: &Node, key: &mut Vec<u8>, filter: PhraseFilter) -> usize {
+        let fst = self.0.as_fst();
+        let mut count = 0;
+        if node.is_final() && filter(&util::key_to_word_ids(key)) {
+            count += 1;
+        }
+        for t in node.transitions() {
+            key.push(t.inp);
+            count += self.count_phrases(&fst.node(t.addr), key, filter);
+            key.pop();
+        }
+        count
+    }
+
+    /// Walk a key from the root, keeping track of the output along the way. Not to be used
+    /// directly.
+    fn output_search(&self, key: &[u8]) -> Option<(Node, fst::raw::Output)> {
+        let fst = self.0.as_fst();
+        let mut node = fst.root();
+        let mut output = fst::raw::Output::zero();
+        for b in key {
+            node = match node.find_input(*b) {
+                None => return None,
+                Some(i) => {
+                    let t = node.transition(i);
+                    output = output.cat(t.out);
+                    fst.node(t.addr)
+                }
+            }
+        }
+        Some((node, output))
+    }
+
+    fn completion_recurse(
+        &self,
+        node: &Node,
+        output: fst::raw::Output,
+        key: &mut Vec<u8>,
+        base: usize,
+        key_range: Option<(WordKey, WordKey)>,
+        limit: usize,
+        filter: PhraseFilter,
+        out: &mut Vec<(Vec<u32>, u64)>,
+    ) {
+        let fst = self.0.as_fst();
+        // a phrase that ends in a prefix has to have at least one more word than its full words
+        let min_length = if key_range.is_some() { base + 3 } else { base };
+        if node.is_final() && key.len() >= min_length && out.len() < limit {
+            let word_ids = util::key_to_word_ids(key);
+            if filter(&word_ids) {
+                out.push((word_ids, output.cat(node.final_output()).value()));
+            }
+        }
+
+        for t in node.transitions() {
+            if out.len() >= limit {
+                return;
+            }
+            key.push(t.inp);
+            // while we're within the first word after the full words, only take bytes that could
+            // still be the beginning of a key within the prefix range
+            let in_range = match key_range {
+                Some((min_key, max_key)) if key.len() <= base + 3 => {
+                    let partial = &key[base..];
+                    partial >= &min_key[..partial.len()] && partial <= &max_key[..partial.len()]
+                },
+                _ => true,
+            };
+            if in_range {
+                self.completion_recurse(&fst.node(t.addr), output.cat(t.out), key, base, key_range, limit, filter, out);
+            }
+            key.pop();
+        }
+    }
+
     /// Helper function for doing a byte-by-byte walk through the phrase graph, staring at any
     /// arbitrary node. Not to be used directly.
     fn partial_search(&self, start_addr: CompiledAddr, key: &[u8]) -> Option<CompiledAddr> {
@@ -380,30 +777,44 @@ impl PhraseSet {
 
     /// Create from a raw byte sequence, which must be written by `PhraseSetBuilder`.
     pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, fst::Error> {
-        Set::from_bytes(bytes).map(PhraseSet)
+        Map::from_bytes(bytes).map(PhraseSet)
+    }
+
+    /// Read a whole graph written by `PhraseSetBuilder` into memory; this is how to load one from
+    /// a file without the `mmap` feature.
+    pub fn from_reader<R: io::Read>(mut rdr: R) -> Result<Self, fst::Error> {
+        let mut bytes: Vec<u8> = Vec::new();
+        rdr.read_to_end(&mut bytes)?;
+        PhraseSet::from_bytes(bytes)
     }
 
     #[cfg(feature = "mmap")]
     pub unsafe fn from_path<P: AsRef<Path>>(path: P) -> Result<Self, fst::Error> {
-        Set::from_path(path).map(PhraseSet)
+        Map::from_path(path).map(PhraseSet)
+    }
+
+    /// Create from a region of a memory map, which must hold a graph written by `PhraseSetBuilder`.
+    #[cfg(feature = "mmap")]
+    pub fn from_mmap(mmap: fst::raw::MmapReadOnly) -> Result<Self, fst::Error> {
+        fst::raw::Fst::from_mmap(mmap).map(|fst| PhraseSet(Map::from(fst)))
     }
 
 }
 
 impl<'s, 'a> IntoStreamer<'a> for &'s PhraseSet {
-    type Item = &'a [u8];
-    type Into = fst::set::Stream<'s>;
+    type Item = (&'a [u8], u64);
+    type Into = fst::map::Stream<'s>;
 
     fn into_stream(self) -> Self::Into {
         self.0.stream()
     }
 }
 
-pub struct PhraseSetBuilder<W>(SetBuilder<W>);
+pub struct PhraseSetBuilder<W>(MapBuilder<W>);
 
 impl PhraseSetBuilder<Vec<u8>> {
     pub fn memory() -> Self {
-        PhraseSetBuilder(SetBuilder::memory())
+        PhraseSetBuilder(MapBuilder::memory())
     }
 
 }
@@ -411,13 +822,15 @@ impl PhraseSetBuilder<Vec<u8>> {
 impl<W: io::Write> PhraseSetBuilder<W> {
 
     pub fn new(wtr: W) -> Result<PhraseSetBuilder<W>, fst::Error> {
-        SetBuilder::new(wtr).map(PhraseSetBuilder)
+        MapBuilder::new(wtr).map(PhraseSetBuilder)
     }
 
-    /// Insert a phrase, specified as an array of word identifiers.
-    pub fn insert(&mut self, phrase: &[u32]) -> Result<(), fst::Error> {
+    /// Insert a phrase, specified as an array of word identifiers, along with the output value to
+    /// associate with it. Phrases must be inserted in lexicographic order, and each phrase may
+    /// only be inserted once.
+    pub fn insert(&mut self, phrase: &[u32], id: u64) -> Result<(), fst::Error> {
         let key = word_ids_to_key(phrase);
-        self.0.insert(key)
+        self.0.insert(key, id)
     }
 
     pub fn into_inner(self) -> Result<W, fst::Error> {
@@ -12,10 +12,12 @@ pub enum QueryWord {
     },
 
     /// A `Prefix` is a string that is the prefix to more than one full word, and includes an id_range field,
-    /// which of identifiers.
+    /// which of identifiers. A prefix that was reached by correcting a misspelled partial word carries
+    /// the edit distance of that correction.
     Prefix {
         id_range: (u32, u32),
         key_range: (WordKey, WordKey),
+        edit_distance: u8,
     },
 }
 
@@ -28,10 +30,29 @@ impl QueryWord
     }
 
     pub fn new_prefix(id_range: (u32, u32)) -> QueryWord {
+        QueryWord::new_fuzzy_prefix(id_range, 0)
+    }
+
+    pub fn new_fuzzy_prefix(id_range: (u32, u32), edit_distance: u8) -> QueryWord {
         let min_key: [u8; 3] = util::three_byte_encode(id_range.0);
         let max_key: [u8; 3] = util::three_byte_encode(id_range.1);
         let key_range = (min_key, max_key);
-        QueryWord::Prefix { id_range, key_range }
+        QueryWord::Prefix { id_range, key_range, edit_distance }
+    }
+
+    pub fn edit_distance(&self) -> u8 {
+        match self {
+            &QueryWord::Full { edit_distance, .. } => edit_distance,
+            &QueryWord::Prefix { edit_distance, .. } => edit_distance,
+        }
+    }
+
+    /// Whether this word is, or (for a prefix) covers, the word with the given identifier.
+    pub fn contains_id(&self, word_id: u32) -> bool {
+        match self {
+            &QueryWord::Full { id, .. } => id == word_id,
+            &QueryWord::Prefix { id_range, .. } => word_id >= id_range.0 && word_id <= id_range.1,
+        }
     }
 
     pub fn to_string<'a, T:Fn(u32) -> &'a str>(&self, id_to_string: T) -> String {
@@ -103,16 +124,11 @@ impl<'a> QueryPhrase<'a> {
         self.length
     }
 
-    /// Sum the edit distances of the full words in the phrase
+    /// Sum the edit distances of the words in the phrase
     pub fn total_edit_distance(&self) -> u8 {
         let mut total_edit_distance = 0;
         for word in self.words {
-            match word {
-                QueryWord::Full{ ref edit_distance, .. } => {
-                    total_edit_distance += *edit_distance;
-                },
-                _ => (),
-            }
+            total_edit_distance += word.edit_distance();
         }
         total_edit_distance
     }
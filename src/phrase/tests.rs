@@ -14,16 +14,16 @@ use self::util::three_byte_decode;
 #[test]
 fn insert_phrases_memory() {
     let mut build = PhraseSetBuilder::memory();
-    build.insert(&[1u32, 61_528_u32, 561_528u32]).unwrap();
-    build.insert(&[61_528_u32, 561_528u32, 1u32]).unwrap();
-    build.insert(&[561_528u32, 1u32, 61_528_u32]).unwrap();
+    build.insert(&[1u32, 61_528_u32, 561_528u32], 0).unwrap();
+    build.insert(&[61_528_u32, 561_528u32, 1u32], 0).unwrap();
+    build.insert(&[561_528u32, 1u32, 61_528_u32], 0).unwrap();
     let bytes = build.into_inner().unwrap();
 
     let phrase_set = PhraseSet::from_bytes(bytes).unwrap();
 
     let mut keys = vec![];
     let mut stream = phrase_set.into_stream();
-    while let Some(key) = stream.next() {
+    while let Some((key, _id)) = stream.next() {
         keys.push(key.to_vec());
     }
     assert_eq!(
@@ -49,20 +49,60 @@ fn insert_phrases_memory() {
 }
 
 #[test]
+#[cfg(feature = "mmap")]
 fn insert_phrases_file() {
     let wtr = io::BufWriter::new(File::create("/tmp/phrase-set.fst").unwrap());
 
     let mut build = PhraseSetBuilder::new(wtr).unwrap();
-    build.insert(&[1u32, 61_528_u32, 561_528u32]).unwrap();
-    build.insert(&[61_528_u32, 561_528u32, 1u32]).unwrap();
-    build.insert(&[561_528u32, 1u32, 61_528_u32]).unwrap();
+    build.insert(&[1u32, 61_528_u32, 561_528u32], 0).unwrap();
+    build.insert(&[61_528_u32, 561_528u32, 1u32], 0).unwrap();
+    build.insert(&[561_528u32, 1u32, 61_528_u32], 0).unwrap();
     build.finish().unwrap();
 
     let phrase_set = unsafe { PhraseSet::from_path("/tmp/phrase-set.fst") }.unwrap();
 
     let mut keys = vec![];
     let mut stream = phrase_set.into_stream();
-    while let Some(key) = stream.next() {
+    while let Some((key, _id)) = stream.next() {
+        keys.push(key.to_vec());
+    }
+    assert_eq!(
+        keys,
+        vec![
+            vec![
+                0u8, 0u8,   1u8,     // 1
+                0u8, 240u8, 88u8,    // 61_528
+                8u8, 145u8, 120u8    // 561_528
+            ],
+            vec![
+                0u8, 240u8, 88u8,    // 61_528
+                8u8, 145u8, 120u8,   // 561_528
+                0u8, 0u8,   1u8      // 1
+            ],
+            vec![
+                8u8, 145u8, 120u8,   // 561_528
+                0u8, 0u8,   1u8,     // 1
+                0u8, 240u8, 88u8     // 61_528
+            ],
+        ]
+    );
+}
+
+#[test]
+fn insert_phrases_reader() {
+    let wtr = io::BufWriter::new(File::create("/tmp/phrase-set-reader.fst").unwrap());
+
+    let mut build = PhraseSetBuilder::new(wtr).unwrap();
+    build.insert(&[1u32, 61_528_u32, 561_528u32], 0).unwrap();
+    build.insert(&[61_528_u32, 561_528u32, 1u32], 0).unwrap();
+    build.insert(&[561_528u32, 1u32, 61_528_u32], 0).unwrap();
+    build.finish().unwrap();
+
+    let phrase_set = PhraseSet::from_reader(File::open("/tmp/phrase-set-reader.fst").unwrap()).unwrap();
+
+    let mut keys = vec![];
+    let mut stream = phrase_set.into_stream();
+    while let Some((key, _id)) = stream.next() {
         keys.push(key.to_vec());
     }
     assert_eq!(
@@ -90,9 +130,9 @@ fn insert_phrases_file() {
 #[test]
 fn contains_query() {
     let mut build = PhraseSetBuilder::memory();
-    build.insert(&[1u32, 61_528_u32, 561_528u32]).unwrap();
-    build.insert(&[61_528_u32, 561_528u32, 1u32]).unwrap();
-    build.insert(&[561_528u32, 1u32, 61_528_u32]).unwrap();
+    build.insert(&[1u32, 61_528_u32, 561_528u32], 0).unwrap();
+    build.insert(&[61_528_u32, 561_528u32, 1u32], 0).unwrap();
+    build.insert(&[561_528u32, 1u32, 61_528_u32], 0).unwrap();
     let bytes = build.into_inner().unwrap();
 
     let phrase_set = PhraseSet::from_bytes(bytes).unwrap();
@@ -117,12 +157,42 @@ fn contains_query() {
     assert!(phrase_set.contains(has_prefix_phrase).is_err());
 }
 
+#[test]
+fn get_query() {
+    let mut build = PhraseSetBuilder::memory();
+    build.insert(&[1u32, 61_528_u32, 561_528u32], 7).unwrap();
+    build.insert(&[61_528_u32, 561_528u32, 1u32], 8).unwrap();
+    build.insert(&[561_528u32, 1u32, 61_528_u32], 9).unwrap();
+    let bytes = build.into_inner().unwrap();
+
+    let phrase_set = PhraseSet::from_bytes(bytes).unwrap();
+
+    let words = vec![
+        QueryWord::new_full(1u32, 0),
+        QueryWord::new_full(61_528u32, 0),
+        QueryWord::new_full(561_528u32, 0),
+    ];
+
+    let matching_word_seq = [ words[1], words[2], words[0] ];
+    let matching_phrase = QueryPhrase::new(&matching_word_seq).unwrap();
+    assert_eq!(Some(8), phrase_set.get(matching_phrase).unwrap());
+
+    let missing_word_seq = [ words[0], words[1] ];
+    let missing_phrase = QueryPhrase::new(&missing_word_seq).unwrap();
+    assert_eq!(None, phrase_set.get(missing_phrase).unwrap());
+
+    let prefix = QueryWord::new_prefix((561_528u32, 561_531u32));
+    let has_prefix_word_seq = [ words[0], words[1], prefix ];
+    let has_prefix_phrase = QueryPhrase::new(&has_prefix_word_seq).unwrap();
+    assert!(phrase_set.get(has_prefix_phrase).is_err());
+}
+
 #[test]
 fn contains_prefix_query() {
     let mut build = PhraseSetBuilder::memory();
-    build.insert(&[1u32, 61_528_u32, 561_528u32]).unwrap();
-    build.insert(&[61_528_u32, 561_528u32, 1u32]).unwrap();
-    build.insert(&[561_528u32, 1u32, 61_528_u32]).unwrap();
+    build.insert(&[1u32, 61_528_u32, 561_528u32], 0).unwrap();
+    build.insert(&[61_528_u32, 561_528u32, 1u32], 0).unwrap();
+    build.insert(&[561_528u32, 1u32, 61_528_u32], 0).unwrap();
     let bytes = build.into_inner().unwrap();
 
     let phrase_set = PhraseSet::from_bytes(bytes).unwrap();
@@ -145,16 +215,16 @@ fn contains_prefix_query() {
 #[test]
 fn contains_prefix_range() {
     let mut build = PhraseSetBuilder::memory();
-    build.insert(&[1u32, 61_528_u32, three_byte_decode(&[2u8, 1u8, 0u8])]).unwrap();
-    build.insert(&[1u32, 61_528_u32, three_byte_decode(&[2u8, 3u8, 2u8])]).unwrap();
-    build.insert(&[1u32, 61_528_u32, three_byte_decode(&[2u8, 3u8, 4u8])]).unwrap();
-    build.insert(&[1u32, 61_528_u32, three_byte_decode(&[2u8, 5u8, 6u8])]).unwrap();
-    build.insert(&[1u32, 61_528_u32, three_byte_decode(&[4u8, 1u8, 1u8])]).unwrap();
-    build.insert(&[1u32, 61_528_u32, three_byte_decode(&[4u8, 3u8, 3u8])]).unwrap();
-    build.insert(&[1u32, 61_528_u32, three_byte_decode(&[4u8, 5u8, 5u8])]).unwrap();
-    build.insert(&[1u32, 61_528_u32, three_byte_decode(&[6u8, 3u8, 4u8])]).unwrap();
-    build.insert(&[1u32, 61_528_u32, three_byte_decode(&[6u8, 3u8, 7u8])]).unwrap();
-    build.insert(&[1u32, 61_528_u32, three_byte_decode(&[6u8, 5u8, 8u8])]).unwrap();
+    build.insert(&[1u32, 61_528_u32, three_byte_decode(&[2u8, 1u8, 0u8])], 0).unwrap();
+    build.insert(&[1u32, 61_528_u32, three_byte_decode(&[2u8, 3u8, 2u8])], 0).unwrap();
+    build.insert(&[1u32, 61_528_u32, three_byte_decode(&[2u8, 3u8, 4u8])], 0).unwrap();
+    build.insert(&[1u32, 61_528_u32, three_byte_decode(&[2u8, 5u8, 6u8])], 0).unwrap();
+    build.insert(&[1u32, 61_528_u32, three_byte_decode(&[4u8, 1u8, 1u8])], 0).unwrap();
+    build.insert(&[1u32, 61_528_u32, three_byte_decode(&[4u8, 3u8, 3u8])], 0).unwrap();
+    build.insert(&[1u32, 61_528_u32, three_byte_decode(&[4u8, 5u8, 5u8])], 0).unwrap();
+    build.insert(&[1u32, 61_528_u32, three_byte_decode(&[6u8, 3u8, 4u8])], 0).unwrap();
+    build.insert(&[1u32, 61_528_u32, three_byte_decode(&[6u8, 3u8, 7u8])], 0).unwrap();
+    build.insert(&[1u32, 61_528_u32, three_byte_decode(&[6u8, 5u8, 8u8])], 0).unwrap();
     let bytes = build.into_inner().unwrap();
     let phrase_set = PhraseSet::from_bytes(bytes).unwrap();
 
@@ -235,16 +305,16 @@ fn contains_prefix_nested_range() {
     // keys are not in the graph. that means we need to make sure that there is at least one
     // path that is actually within the sought range.
     let mut build = PhraseSetBuilder::memory();
-    build.insert(&[1u32, 61_528_u32, three_byte_decode(&[2u8, 1u8, 0u8])]).unwrap();
-    build.insert(&[1u32, 61_528_u32, three_byte_decode(&[2u8, 3u8, 2u8])]).unwrap();
-    build.insert(&[1u32, 61_528_u32, three_byte_decode(&[2u8, 3u8, 4u8])]).unwrap();
-    build.insert(&[1u32, 61_528_u32, three_byte_decode(&[2u8, 5u8, 6u8])]).unwrap();
-    build.insert(&[1u32, 61_528_u32, three_byte_decode(&[4u8, 1u8, 1u8])]).unwrap();
-    build.insert(&[1u32, 61_528_u32, three_byte_decode(&[4u8, 3u8, 3u8])]).unwrap();
-    build.insert(&[1u32, 61_528_u32, three_byte_decode(&[4u8, 5u8, 5u8])]).unwrap();
-    build.insert(&[1u32, 61_528_u32, three_byte_decode(&[6u8, 3u8, 4u8])]).unwrap();
-    build.insert(&[1u32, 61_528_u32, three_byte_decode(&[6u8, 3u8, 7u8])]).unwrap();
-    build.insert(&[1u32, 61_528_u32, three_byte_decode(&[6u8, 5u8, 8u8])]).unwrap();
+    build.insert(&[1u32, 61_528_u32, three_byte_decode(&[2u8, 1u8, 0u8])], 0).unwrap();
+    build.insert(&[1u32, 61_528_u32, three_byte_decode(&[2u8, 3u8, 2u8])], 0).unwrap();
+    build.insert(&[1u32, 61_528_u32, three_byte_decode(&[2u8, 3u8, 4u8])], 0).unwrap();
+    build.insert(&[1u32, 61_528_u32, three_byte_decode(&[2u8, 5u8, 6u8])], 0).unwrap();
+    build.insert(&[1u32, 61_528_u32, three_byte_decode(&[4u8, 1u8, 1u8])], 0).unwrap();
+    build.insert(&[1u32, 61_528_u32, three_byte_decode(&[4u8, 3u8, 3u8])], 0).unwrap();
+    build.insert(&[1u32, 61_528_u32, three_byte_decode(&[4u8, 5u8, 5u8])], 0).unwrap();
+    build.insert(&[1u32, 61_528_u32, three_byte_decode(&[6u8, 3u8, 4u8])], 0).unwrap();
+    build.insert(&[1u32, 61_528_u32, three_byte_decode(&[6u8, 3u8, 7u8])], 0).unwrap();
+    build.insert(&[1u32, 61_528_u32, three_byte_decode(&[6u8, 5u8, 8u8])], 0).unwrap();
     let bytes = build.into_inner().unwrap();
     let phrase_set = PhraseSet::from_bytes(bytes).unwrap();
 
@@ -422,8 +492,9 @@ lazy_static! {
             phrase.split(' ').map(|w| WORDS[w]).collect::<Vec<_>>()
         }).collect::<Vec<_>>();
         id_phrases.sort();
-        for id_phrase in id_phrases {
-            builder.insert(&id_phrase).unwrap();
+        id_phrases.dedup();
+        for (i, id_phrase) in id_phrases.iter().enumerate() {
+            builder.insert(&id_phrase, i as u64).unwrap();
         }
         let bytes = builder.into_inner().unwrap();
         PhraseSet::from_bytes(bytes).unwrap()
@@ -710,4 +781,187 @@ fn sample_prefix_contains_windows_substring() {
         true
     ).unwrap();
     assert_eq!(results, vec![]);
-}
\ No newline at end of file
+}
+#[test]
+fn sample_match_combinations_limited() {
+    fn distance(words: &Vec<QueryWord>) -> u8 {
+        words.iter().map(|w| w.edit_distance()).sum()
+    }
+
+    // the limited searches should find some set of the best results that the unlimited ones do,
+    // in order of increasing distance
+    fn check(full: Vec<Vec<QueryWord>>, limited: Vec<Vec<QueryWord>>, limit: usize) {
+        let mut full_distances: Vec<u8> = full.iter().map(distance).collect();
+        full_distances.sort();
+        full_distances.truncate(limit);
+        let limited_distances: Vec<u8> = limited.iter().map(distance).collect();
+        assert_eq!(limited_distances, full_distances);
+        assert!(limited.iter().all(|r| full.contains(r)));
+    }
+
+    for phrase in PHRASES.iter().take(100) {
+        let full_variants = get_full_variants(phrase);
+        let prefix_variants = get_prefix_variants(phrase);
+        for &limit in &[1, 2, 10] {
+            check(
+                SET.match_combinations(&full_variants, 2).unwrap(),
+                SET.match_combinations_limited(&full_variants, 2, limit).unwrap(),
+                limit
+            );
+            check(
+                SET.match_combinations_as_prefixes(&prefix_variants, 2).unwrap(),
+                SET.match_combinations_as_prefixes_limited(&prefix_variants, 2, limit).unwrap(),
+                limit
+            );
+            check(
+                SET.match_combinations_as_windows(&prefix_variants, 2, true).unwrap().into_iter().map(|r| r.0).collect(),
+                SET.match_combinations_as_windows_limited(&prefix_variants, 2, true, limit).unwrap().into_iter().map(|r| r.0).collect(),
+                limit
+            );
+        }
+    }
+}
+
+#[test]
+fn sample_filtered_searches() {
+    fn ids(words: &[QueryWord]) -> Vec<u32> {
+        words.iter().map(|w| match w {
+            QueryWord::Full { id, .. } => *id,
+            _ => panic!("expected a full word"),
+        }).collect()
+    }
+
+    for phrase in PHRASES.iter().take(50) {
+        let full_variants = get_full_variants(phrase);
+        let prefix_variants = get_prefix_variants(phrase);
+        let exact = ids(&get_full(phrase));
+        let not_exact = |word_ids: &[u32]| word_ids != &exact[..];
+
+        // turning down the phrase itself makes room for the next best result, rather than
+        // leaving a hole
+        let all = SET.match_combinations_limited(&full_variants, 2, 1000).unwrap();
+        let expected: Vec<Vec<QueryWord>> = all.into_iter().filter(|r| ids(r) != exact).take(2).collect();
+        assert_eq!(SET.match_combinations_limited_filtered(&full_variants, 2, 2, &not_exact).unwrap(), expected);
+        assert_eq!(SET.match_combinations_limited_filtered(&full_variants, 2, 1000, &|_| false).unwrap().len(), 0);
+
+        // a prefix only passes if something it could complete to does
+        let prefixes = SET.match_combinations_as_prefixes_limited(&prefix_variants, 2, 1000).unwrap();
+        assert_eq!(SET.match_combinations_as_prefixes_limited_filtered(&prefix_variants, 2, 1000, &|_| true).unwrap(), prefixes);
+        assert_eq!(SET.match_combinations_as_prefixes_limited_filtered(&prefix_variants, 2, 1000, &|_| false).unwrap().len(), 0);
+        assert_eq!(SET.match_combinations_as_windows_limited_filtered(&prefix_variants, 2, true, 1000, &|_| false).unwrap().len(), 0);
+
+        let query: Vec<QueryWord> = prefix_variants.iter().map(|v| v[0]).collect();
+        let completions = SET.completions(QueryPhrase::new(&query).unwrap(), 1000).unwrap();
+        let without_first: Vec<(Vec<u32>, u64)> = completions.iter().skip(1).take(1).cloned().collect();
+        let first = completions[0].0.clone();
+        assert_eq!(SET.completions_filtered(QueryPhrase::new(&query).unwrap(), 1, &|word_ids| word_ids != &first[..]).unwrap(), without_first);
+    }
+
+    // a continuation only counts if a phrase that passes goes through it
+    let query = get_full("53# Country");
+    let continuations = SET.continuations(QueryPhrase::new(&query).unwrap(), 1000, ContinuationOrder::Lexicographic).unwrap();
+    let view = WORDS["View"];
+    let no_view = |word_ids: &[u32]| word_ids.get(2) != Some(&view);
+    let filtered = SET.continuations_filtered(QueryPhrase::new(&query).unwrap(), 1000, ContinuationOrder::Lexicographic, &no_view).unwrap();
+    assert!(continuations.iter().any(|(k, _)| *k == view));
+    assert_eq!(filtered, continuations.into_iter().filter(|(k, _)| *k != view).collect::<Vec<_>>());
+}
+
+#[test]
+fn sample_completions() {
+    let mut id_phrases = PHRASES.iter().map(|phrase| {
+        phrase.split(' ').map(|w| WORDS[w]).collect::<Vec<_>>()
+    }).collect::<Vec<_>>();
+    id_phrases.sort();
+    id_phrases.dedup();
+
+    for phrase in PHRASES.iter().take(50) {
+        // chop a couple of characters off the end to get a prefix query
+        let chars: Vec<char> = phrase.chars().collect();
+        let partial: String = chars[..(chars.len() - 2)].iter().collect();
+        if partial.ends_with(' ') {
+            continue;
+        }
+        let query = get_prefix(&partial);
+        let (full_words, last) = query.split_at(query.len() - 1);
+        let id_range = match last[0] {
+            QueryWord::Prefix { id_range, .. } => id_range,
+            _ => panic!("expected a prefix"),
+        };
+        let full_ids: Vec<u32> = full_words.iter().map(|w| match w {
+            QueryWord::Full { id, .. } => *id,
+            _ => panic!("expected a full word"),
+        }).collect();
+
+        let expected: Vec<(Vec<u32>, u64)> = id_phrases.iter().enumerate().filter(|(_i, p)| {
+            p.len() > full_ids.len() &&
+                p[..full_ids.len()] == full_ids[..] &&
+                p[full_ids.len()] >= id_range.0 && p[full_ids.len()] <= id_range.1
+        }).map(|(i, p)| (p.clone(), i as u64)).collect();
+
+        let completions = SET.completions(QueryPhrase::new(&query).unwrap(), 1000).unwrap();
+        assert_eq!(completions, expected, "completions of {:?}", partial);
+        assert!(completions.iter().any(|c| c.0 == get_full(phrase).iter().map(|w| match w {
+            QueryWord::Full { id, .. } => *id,
+            _ => 0,
+        }).collect::<Vec<_>>()));
+
+        let limited = SET.completions(QueryPhrase::new(&query).unwrap(), 1).unwrap();
+        assert_eq!(limited, expected[..1].to_vec());
+    }
+
+    // a full-word query completes to itself (if it's a phrase) and anything longer
+    let query = get_full("53# Country View");
+    let completions = SET.completions(QueryPhrase::new(&query).unwrap(), 1000).unwrap();
+    assert!(completions.len() > 0);
+    assert!(completions.iter().all(|c| c.0[..3] == [WORDS["53#"], WORDS["Country"], WORDS["View"]]));
+}
+
+#[test]
+fn sample_continuations() {
+    let mut id_phrases = PHRASES.iter().map(|phrase| {
+        phrase.split(' ').map(|w| WORDS[w]).collect::<Vec<_>>()
+    }).collect::<Vec<_>>();
+    id_phrases.sort();
+    id_phrases.dedup();
+
+    for phrase in PHRASES.iter().take(50) {
+        let words: Vec<&str> = phrase.split(' ').collect();
+        if words.len() < 2 {
+            continue;
+        }
+        let start: Vec<u32> = words[..(words.len() - 1)].iter().map(|w| WORDS[w]).collect();
+        let query = get_full(&words[..(words.len() - 1)].join(" "));
+
+        // every distinct next word, and how many phrases start with the query plus that word
+        let mut expected: BTreeMap<u32, (Option<u64>, usize)> = BTreeMap::new();
+        for (i, p) in id_phrases.iter().enumerate() {
+            if p.len() > start.len() && p[..start.len()] == start[..] {
+                let entry = expected.entry(p[start.len()]).or_insert((None, 0));
+                entry.1 += 1;
+                if p.len() == start.len() + 1 {
+                    entry.0 = Some(i as u64);
+                }
+            }
+        }
+        let lexicographic: Vec<(u32, Option<u64>)> = expected.iter().map(|(k, v)| (*k, v.0)).collect();
+        let mut most_phrases: Vec<(u32, Option<u64>, usize)> = expected.iter().map(|(k, v)| (*k, v.0, v.1)).collect();
+        most_phrases.sort_by(|a, b| b.2.cmp(&a.2));
+        let most_phrases: Vec<(u32, Option<u64>)> = most_phrases.into_iter().map(|(k, v, _)| (k, v)).collect();
+
+        assert_eq!(SET.continuations(QueryPhrase::new(&query).unwrap(), 1000, ContinuationOrder::Lexicographic).unwrap(), lexicographic);
+        assert_eq!(SET.continuations(QueryPhrase::new(&query).unwrap(), 1000, ContinuationOrder::MostPhrases).unwrap(), most_phrases);
+        assert_eq!(SET.continuations(QueryPhrase::new(&query).unwrap(), 1, ContinuationOrder::Lexicographic).unwrap(), lexicographic[..1].to_vec());
+
+        // with a prefix at the end, only words within the prefix's range come back
+        let last = words[words.len() - 1];
+        let partial: String = last.chars().take(1).collect();
+        let prefix_query = get_prefix(&(words[..(words.len() - 1)].join(" ") + " " + &partial));
+        let in_range: Vec<(u32, Option<u64>)> = lexicographic.iter().filter(|(k, _)| match prefix_query.last().unwrap() {
+            QueryWord::Prefix { id_range, .. } => *k >= id_range.0 && *k <= id_range.1,
+            _ => false,
+        }).cloned().collect();
+        assert_eq!(SET.continuations(QueryPhrase::new(&prefix_query).unwrap(), 1000, ContinuationOrder::Lexicographic).unwrap(), in_range);
+        assert!(in_range.iter().any(|(k, _)| *k == WORDS[last]));
+    }
+}
@@ -20,10 +20,22 @@ impl PrefixSet {
         raw::Fst::from_path(path).map(PrefixSet)
     }
 
+    #[cfg(feature = "mmap")]
+    pub fn from_mmap(mmap: raw::MmapReadOnly) -> Result<Self, FstError> {
+        raw::Fst::from_mmap(mmap).map(PrefixSet)
+    }
+
     pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, FstError> {
         raw::Fst::from_bytes(bytes).map(PrefixSet)
     }
 
+    // not from upstream: for loading without memory maps, read the whole graph into memory
+    pub fn from_reader<R: Read>(mut rdr: R) -> Result<Self, FstError> {
+        let mut bytes: Vec<u8> = Vec::new();
+        rdr.read_to_end(&mut bytes)?;
+        PrefixSet::from_bytes(bytes)
+    }
+
     pub fn from_iter<T, I>(iter: I) -> Result<Self, FstError>
             where T: AsRef<[u8]>, I: IntoIterator<Item=T> {
         let mut builder = PrefixSetBuilder::memory();
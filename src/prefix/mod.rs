@@ -24,6 +24,35 @@ impl PrefixSet {
         PrefixSetLookupResult::Found { fst, node, output_so_far: out }
     }
 
+    /// Find the words in the set that start with something within `max_distance` edits of `query`,
+    /// so that a partially-typed word can be both incomplete and misspelled. This walks the fst
+    /// depth-first, carrying a row of the (optimal string alignment) edit distance matrix between
+    /// `query` and the key so far, and abandons any branch whose row can no longer come in under
+    /// the limit.
+    ///
+    /// The result is a sorted list of non-overlapping ID ranges. Each word is assigned the smallest
+    /// distance between `query` and any of the word's own prefixes, so the range below an exact
+    /// match for `query` has distance zero.
+    pub fn fuzzy_lookup(&self, query: &str, max_distance: u8) -> Vec<PrefixSetFuzzyResult> {
+        let walk = FuzzyPrefixWalk {
+            fst: &self.as_fst(),
+            query: query.chars().collect(),
+            max_distance: max_distance as u32,
+        };
+        let first_row: Vec<u32> = (0..(walk.query.len() as u32 + 1)).collect();
+        let mut out: Vec<PrefixSetFuzzyResult> = Vec::new();
+        walk.visit_boundary(
+            walk.fst.root(),
+            raw::Output::zero(),
+            &first_row,
+            None,
+            None,
+            walk.max_distance + 1,
+            &mut out
+        );
+        out
+    }
+
     pub fn get_by_id(&self, id: raw::Output) -> Option<Vec<u8>> {
         let mut id = id.clone();
         let fst = &self.as_fst();
@@ -123,4 +152,141 @@ impl<'a> PrefixSetLookupResult<'a> {
             PrefixSetLookupResult::Found { node, .. } => node.len() > 0
         }
     }
-}
\ No newline at end of file
+}
+#[derive(Debug, PartialEq, Eq, Clone, Copy)]
+pub struct PrefixSetFuzzyResult {
+    pub id_range: (raw::Output, raw::Output),
+    pub edit_distance: u8,
+    // whether the matched key can be continued, as with `PrefixSetLookupResult::has_continuations`
+    pub has_continuations: bool,
+}
+
+struct FuzzyPrefixWalk<'a> {
+    fst: &'a raw::Fst,
+    query: Vec<char>,
+    max_distance: u32,
+}
+
+impl<'a> FuzzyPrefixWalk<'a> {
+    // called at nodes that sit on a character boundary, where `row` holds the distances between
+    // every prefix of the query and the key so far; `best` is the distance already claimed for
+    // this subtree by an ancestor, which descendants only override if they can beat it
+    fn visit_boundary(
+        &self,
+        node: raw::Node<'a>,
+        output_so_far: raw::Output,
+        row: &[u32],
+        prev_row: Option<&[u32]>,
+        last_char: Option<char>,
+        best: u32,
+        out: &mut Vec<PrefixSetFuzzyResult>
+    ) {
+        let distance = row[self.query.len()];
+        let emit = distance < best;
+        let child_best = if emit { distance } else { best };
+        let start = out.len();
+
+        if row.iter().min().map_or(false, |&m| m < child_best) {
+            for t in node.transitions() {
+                self.visit_byte(
+                    self.fst.node(t.addr),
+                    output_so_far.cat(t.out),
+                    vec![t.inp],
+                    row,
+                    prev_row,
+                    last_char,
+                    child_best,
+                    out
+                );
+            }
+        }
+
+        if emit {
+            let lookup = PrefixSetLookupResult::Found { fst: self.fst, node, output_so_far };
+            let (range_start, range_end) = lookup.range().unwrap();
+            // the descendants found better matches for some subranges of ours, so we fill in the
+            // gaps between them at our own distance
+            let children: Vec<PrefixSetFuzzyResult> = out.drain(start..).collect();
+            let has_continuations = node.len() > 0;
+            let mut cursor = range_start.value();
+            for child in children {
+                if child.id_range.0.value() > cursor {
+                    out.push(PrefixSetFuzzyResult {
+                        id_range: (raw::Output::new(cursor), raw::Output::new(child.id_range.0.value() - 1)),
+                        edit_distance: distance as u8,
+                        has_continuations,
+                    });
+                }
+                cursor = child.id_range.1.value() + 1;
+                out.push(child);
+            }
+            if cursor <= range_end.value() {
+                out.push(PrefixSetFuzzyResult {
+                    id_range: (raw::Output::new(cursor), range_end),
+                    edit_distance: distance as u8,
+                    has_continuations,
+                });
+            }
+        }
+    }
+
+    // called after following a transition; keys are UTF-8 and distances are counted in characters,
+    // so bytes accumulate in `pending` until they make up a whole character
+    fn visit_byte(
+        &self,
+        node: raw::Node<'a>,
+        output_so_far: raw::Output,
+        pending: Vec<u8>,
+        row: &[u32],
+        prev_row: Option<&[u32]>,
+        last_char: Option<char>,
+        best: u32,
+        out: &mut Vec<PrefixSetFuzzyResult>
+    ) {
+        let width = match pending[0] {
+            b if b >= 0xF0 => 4,
+            b if b >= 0xE0 => 3,
+            b if b >= 0xC0 => 2,
+            _ => 1,
+        };
+        if pending.len() < width {
+            for t in node.transitions() {
+                let mut pending = pending.clone();
+                pending.push(t.inp);
+                self.visit_byte(
+                    self.fst.node(t.addr),
+                    output_so_far.cat(t.out),
+                    pending,
+                    row,
+                    prev_row,
+                    last_char,
+                    best,
+                    out
+                );
+            }
+            return;
+        }
+
+        let c = ::std::str::from_utf8(&pending).ok()
+            .and_then(|s| s.chars().next())
+            .unwrap_or('\u{FFFD}');
+        let next_row = self.next_row(c, row, prev_row, last_char);
+        self.visit_boundary(node, output_so_far, &next_row, Some(row), Some(c), best, out);
+    }
+
+    fn next_row(&self, c: char, row: &[u32], prev_row: Option<&[u32]>, last_char: Option<char>) -> Vec<u32> {
+        let mut next_row: Vec<u32> = Vec::with_capacity(row.len());
+        next_row.push(row[0] + 1);
+        for j in 1..row.len() {
+            let cost = if self.query[j - 1] == c { 0 } else { 1 };
+            let mut d = (row[j] + 1).min(next_row[j - 1] + 1).min(row[j - 1] + cost);
+            if let (Some(prev_row), Some(last_char)) = (prev_row, last_char) {
+                if j > 1 && c == self.query[j - 2] && last_char == self.query[j - 1] {
+                    d = d.min(prev_row[j - 2] + cost);
+                }
+            }
+            next_row.push(d);
+        }
+        next_row
+    }
+}
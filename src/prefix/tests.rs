@@ -1,8 +1,10 @@
 extern crate lazy_static;
+extern crate strsim;
 
 use std::collections::BTreeSet;
 use super::PrefixSet;
 use fst::raw;
+use self::strsim::osa_distance;
 
 lazy_static! {
     static ref DATA: [&'static str; 4] = [
@@ -56,6 +58,16 @@ fn simple_build() {
     assert_eq!(format!("{:?}", pf), "PrefixSet([(one, 0), (three, 1), (two, 2)])");
 }
 
+#[test]
+fn reader_build() {
+    let mut builder = super::PrefixSetBuilder::memory();
+    builder.extend_iter(&["one", "three", "two"]).unwrap();
+    let bytes = builder.into_inner().unwrap();
+
+    let pf = PrefixSet::from_reader(&bytes[..]).expect("tried to read prefix set");
+    assert_eq!(format!("{:?}", pf), "PrefixSet([(one, 0), (three, 1), (two, 2)])");
+}
+
 #[test]
 fn complex_build() {
     lazy_static::initialize(&SET);
@@ -160,4 +172,38 @@ fn get_by_id() {
         SET.get_by_id(raw::Output::new(WORDS.len() as u64)).is_none(),
         "PrefixSet inverse lookup returns none on out of bounds lookup"
     );
-}
\ No newline at end of file
+}
+#[test]
+fn fuzzy_lookup() {
+    // compare against a brute-force search over every prefix of every word
+    for query in &["Co", "Cmo", "Calo", "Mosocw", "Москв", "Мсоква", "qqqq", ""] {
+        for max_distance in 0..3 {
+            let mut expected: Vec<(u64, u8)> = Vec::new();
+            for (id, word) in WORDS.iter().enumerate() {
+                let chars: Vec<char> = word.chars().collect();
+                let best = (0..(chars.len() + 1))
+                    .map(|i| osa_distance(query, &chars[..i].iter().collect::<String>()))
+                    .min()
+                    .unwrap();
+                if best <= max_distance {
+                    expected.push((id as u64, best as u8));
+                }
+            }
+
+            let results = SET.fuzzy_lookup(query, max_distance as u8);
+            let mut found: Vec<(u64, u8)> = Vec::new();
+            for result in &results {
+                for id in result.id_range.0.value()..(result.id_range.1.value() + 1) {
+                    found.push((id, result.edit_distance));
+                }
+            }
+            assert_eq!(found, expected, "fuzzy prefix lookup for {:?} within {}", query, max_distance);
+        }
+    }
+
+    let results = SET.fuzzy_lookup("Co", 0);
+    let co_range = SET.lookup("Co").range().unwrap();
+    assert_eq!(results.len(), 1, "exact fuzzy prefix lookup produces a single range");
+    assert_eq!(results[0].id_range, co_range, "exact fuzzy prefix lookup matches the prefix range");
+    assert_eq!(results[0].has_continuations, SET.lookup("Co").has_continuations(), "exact fuzzy prefix lookup agrees on continuations");
+}